@@ -0,0 +1,57 @@
+use crate::menu::{Action, ActionError};
+use std::sync::{Arc, Mutex};
+use crate::client::ClientContext;
+use crate::utils::*;
+use l1::common::audit::{AuditEntry, LockoutInfo};
+
+
+pub struct AuditLogGetAction {}
+
+impl Action for AuditLogGetAction {
+    fn name(&self) -> &'static str {
+        "GET AUDIT log"
+    }
+
+    fn description(&self) -> &'static str {
+        "Get security events such as login lockouts"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let resp = get_with_params(API!("/audit"), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let yaml = json_to_yaml::<Vec<AuditEntry>>(resp_s).ok_or(
+            "Server sent wrong response".to_string()
+        )?;
+
+        println!("Audit log : \n\n{}", yaml);
+        Ok(())
+    }
+}
+
+
+pub struct LockoutsGetAction {}
+
+impl Action for LockoutsGetAction {
+    fn name(&self) -> &'static str {
+        "GET LOCKED logins and addresses"
+    }
+
+    fn description(&self) -> &'static str {
+        "Get logins and addresses currently locked after failed logins"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let resp = get_with_params(API!("/auth/lockouts"), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let yaml = json_to_yaml::<Vec<LockoutInfo>>(resp_s).ok_or(
+            "Server sent wrong response".to_string()
+        )?;
+
+        println!("Locked : \n\n{}", yaml);
+        Ok(())
+    }
+}
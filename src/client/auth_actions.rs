@@ -1,11 +1,15 @@
 
-use crate::menu::Action;
+use crate::menu::{Action, ActionError};
 use std::sync::{Arc, Mutex};
 use crate::client::ClientContext;
 use crate::utils::*;
 use crate::inputtable::*;
-use l1::common::auth::{LoginReq, SessionResponse, RegisterUserReq};
+use l1::common::auth::*;
 
+use colored::Colorize;
+
+// wrong credentials are asked again this many times in a row
+const LOGIN_ATTEMPTS: usize = 3;
 
 pub struct LoginAction {}
 
@@ -20,23 +24,102 @@ impl Action for LoginAction {
     }
     
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>> ) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>> ) -> Result<(), ActionError> {
                 let mut ctx = ctx_ref.lock().expect("Mutex");
-                let login_data =
-                    LoginReq::input("Please enter yout credentials. \n", 0).ok_or("Wrong input")?;
-                let response = post_with_params(
-                    API!("/auth/login"),
-                    serde_json::to_string(&login_data).unwrap(),
-                    &ctx,
-                )?;
-                let resp_str = handle_errors(response)?;
-                let token_data: SessionResponse =
-                    serde_json::from_str(&resp_str).map_err(|_| "Server sent wrong response")?;
-                ctx.auth_info = Some(token_data);
-                ctx.login = Some(login_data.login.clone());
-                println!("Successfully authorized as {}\n", login_data.login);
-                Ok(())
+                let mut attempt = 0;
+                loop {
+                    attempt += 1;
+                    let login_data =
+                        LoginReq::input("Please enter yout credentials. \n", 0).ok_or("Wrong input")?;
+                    let response = post_with_params(
+                        API!("/auth/login"),
+                        serde_json::to_string(&login_data).unwrap(),
+                        &ctx,
+                    )?;
+                    match finish_login(&ctx, response) {
+                        Ok(token_data) => {
+                            ctx.auth_info = Some(token_data);
+                            ctx.login = Some(login_data.login.clone());
+                            println!("Successfully authorized as {}\n", login_data.login);
+                            return Ok(());
+                        }
+                        // wrong password or code, or the login took too long
+                        Err(err) if err.is_unauthorized() && attempt < LOGIN_ATTEMPTS => {
+                            println!("\n{} : {}. Please try again\n", "ERROR".red(), err);
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+    }
+}
+
+/* Walks through the login steps the server asks for until a session is given */
+fn finish_login(ctx: &ClientContext, response: reqwest::blocking::Response) -> Result<SessionResponse, ActionError> {
+    let resp_str = handle_errors(response)?;
+    let login_resp: LoginResponse =
+        serde_json::from_str(&resp_str).map_err(|_| "Server sent wrong response")?;
+
+    match login_resp {
+        LoginResponse::Session(session) => Ok(session),
+        LoginResponse::SecondFactorRequired { challenge } => {
+            second_factor(ctx, challenge, "Code from authenticator app (or recovery code) : ")
+        }
+        LoginResponse::EnrollmentRequired { challenge, enrollment } => {
+            println!("Two-factor authentication is mandatory for your account.");
+            print_enrollment(&enrollment);
+            second_factor(ctx, challenge, "Code from authenticator app : ")
+        }
+        LoginResponse::PasswordChangeRequired { challenge } => {
+            println!("Your password was reset. Please choose a new one.");
+            let new_password = input_new_password()?;
+            let response = post_with_params(
+                API!("/auth/login/password"),
+                serde_json::to_string(&NewPasswordReq { challenge, new_password }).unwrap(),
+                ctx,
+            )?;
+            finish_login(ctx, response)
+        }
+    }
+}
+
+fn input_new_password() -> Result<String, String> {
+    println!("At least 8 characters, both letters and digits.");
+    let new_password = String::input("New password : ", 0).ok_or("Wrong input")?;
+    let repeated = String::input("Repeat new password : ", 0).ok_or("Wrong input")?;
+    if new_password != repeated {
+        return Err("Passwords do not match".to_string());
     }
+    Ok(new_password)
+}
+
+fn print_enrollment(enrollment: &TotpEnrollResponse) {
+    println!("Add the following key to your authenticator app :");
+    println!("   {}", enrollment.secret);
+    println!("or use this URI : \n   {}\n", enrollment.otpauth_uri);
+}
+
+fn print_recovery_codes(codes: &[String]) {
+    println!("Your recovery codes. Each one can be used once instead of TOTP code.");
+    println!("Keep them in a safe place, they will not be shown again :");
+    for code in codes {
+        println!("   {}", code);
+    }
+    println!();
+}
+
+fn second_factor(ctx: &ClientContext, challenge: Token, invitation: &str) -> Result<SessionResponse, ActionError> {
+    let code = String::input(invitation, 0).ok_or("Wrong input")?;
+    let response = post_with_params(
+        API!("/auth/login/2fa"),
+        serde_json::to_string(&SecondFactorReq { challenge, code }).unwrap(),
+        ctx,
+    )?;
+    let resp: SecondFactorResponse = serde_json::from_str(&handle_errors(response)?)
+        .map_err(|_| "Server sent wrong response")?;
+    if !resp.recovery_codes.is_empty() {
+        print_recovery_codes(&resp.recovery_codes);
+    }
+    Ok(resp.session)
 }
 
 pub struct RegisterAction {}
@@ -54,16 +137,196 @@ After manager will accept your request, you will be able to login in system"#
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
                 let ctx = ctx_ref.lock().expect("Mutex");
                 let register_data = RegisterUserReq::input("Register as a new user : \n", 0)
                     .ok_or("Wrong input")?;
-                let _ = post_with_params(
+                let resp = post_with_params(
                     API!("/auth/register"),
                     serde_json::to_string(&register_data).unwrap(),
                     &ctx,
                 )?;
+                handle_errors(resp)?;
+                println!("Registration request sent. Check its status after a manager reviews it\n");
                 Ok(())
     }
 
 }
+
+
+pub struct RegisterEnterpriseAction {}
+
+impl Action for RegisterEnterpriseAction {
+    fn name(&self) -> &'static str {
+        "register ENTERPRISE"
+    }
+
+    fn description(&self) -> &'static str {
+r#"
+Register a legal entity together with its responsible specialist.
+After manager will accept your request, the specialist will be able to login"#
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().expect("Mutex");
+        let register_data = RegisterEnterpriseReq::input("Register an enterprise : \n", 0)
+            .ok_or("Wrong input")?;
+        let resp = post_with_params(
+            API!("/auth/register/enterprise"),
+            serde_json::to_string(&register_data).unwrap(),
+            &ctx,
+        )?;
+        handle_errors(resp)?;
+        println!("Registration request sent. Check its status after a manager reviews it\n");
+        Ok(())
+    }
+}
+
+
+pub struct RegistrationStatusAction {}
+
+impl Action for RegistrationStatusAction {
+    fn name(&self) -> &'static str {
+        "registration STATUS"
+    }
+
+    fn description(&self) -> &'static str {
+        "Check whether your registration request was accepted or rejected"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().expect("Mutex");
+        let login_data =
+            LoginReq::input("Credentials used for registration : \n", 0).ok_or("Wrong input")?;
+        let resp = post_with_params(
+            API!("/auth/register/status"),
+            serde_json::to_string(&login_data).unwrap(),
+            &ctx,
+        )?;
+        let status: RegistrationStatus = serde_json::from_str(&handle_errors(resp)?)
+            .map_err(|_| "Server sent wrong response")?;
+        println!("Registration status : {}\n", status);
+        Ok(())
+    }
+}
+
+
+pub struct TwoFactorEnableAction {}
+
+impl Action for TwoFactorEnableAction {
+    fn name(&self) -> &'static str {
+        "ENABLE two-factor authentication"
+    }
+
+    fn description(&self) -> &'static str {
+        r#"Protect your account with one-time codes from an authenticator app.
+After enabling you will be asked for a code on every login"#
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().expect("Mutex");
+        let resp = post_with_params(API!("/auth/2fa/enroll"), String::new(), &ctx)?;
+        let enrollment: TotpEnrollResponse = serde_json::from_str(&handle_errors(resp)?)
+            .map_err(|_| "Server sent wrong response")?;
+        print_enrollment(&enrollment);
+
+        let code = String::input("Code from authenticator app : ", 0).ok_or("Wrong input")?;
+        let resp = post_with_params(
+            API!("/auth/2fa/confirm"),
+            serde_json::to_string(&TotpCodeReq { code }).unwrap(),
+            &ctx,
+        )?;
+        let codes: RecoveryCodesResponse = serde_json::from_str(&handle_errors(resp)?)
+            .map_err(|_| "Server sent wrong response")?;
+        print_recovery_codes(&codes.recovery_codes);
+        Ok(())
+    }
+}
+
+pub struct TwoFactorDisableAction {}
+
+impl Action for TwoFactorDisableAction {
+    fn name(&self) -> &'static str {
+        "DISABLE two-factor authentication"
+    }
+
+    fn description(&self) -> &'static str {
+        "Turn off two-factor authentication. Not available for bank staff"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().expect("Mutex");
+        let code = String::input("Code from authenticator app (or recovery code) : ", 0).ok_or("Wrong input")?;
+        let resp = post_with_params(
+            API!("/auth/2fa/disable"),
+            serde_json::to_string(&TotpCodeReq { code }).unwrap(),
+            &ctx,
+        )?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct RecoveryCodesAction {}
+
+impl Action for RecoveryCodesAction {
+    fn name(&self) -> &'static str {
+        "NEW recovery codes"
+    }
+
+    fn description(&self) -> &'static str {
+        "Generate new recovery codes. All previous ones stop working"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().expect("Mutex");
+        let code = String::input("Code from authenticator app : ", 0).ok_or("Wrong input")?;
+        let resp = post_with_params(
+            API!("/auth/2fa/recovery"),
+            serde_json::to_string(&TotpCodeReq { code }).unwrap(),
+            &ctx,
+        )?;
+        let codes: RecoveryCodesResponse = serde_json::from_str(&handle_errors(resp)?)
+            .map_err(|_| "Server sent wrong response")?;
+        print_recovery_codes(&codes.recovery_codes);
+        Ok(())
+    }
+}
+
+pub struct PasswordChangeAction {}
+
+impl Action for PasswordChangeAction {
+    fn name(&self) -> &'static str {
+        "CHANGE PASSWORD"
+    }
+
+    fn description(&self) -> &'static str {
+        "Change your password. All your sessions on other devices will be closed"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let mut ctx = ctx_ref.lock().expect("Mutex");
+        let old_password = String::input("Current password : ", 0).ok_or("Wrong input")?;
+        let new_password = input_new_password()?;
+        let resp = post_with_params(
+            API!("/auth/password"),
+            serde_json::to_string(&PasswordChangeReq { old_password, new_password: new_password.clone() }).unwrap(),
+            &ctx,
+        )?;
+        handle_errors(resp)?;
+
+        // every session is revoked after the change, including this one
+        let login_data = LoginReq {
+            login: ctx.login.clone().ok_or("Not logged in")?,
+            password: new_password,
+        };
+        let response = post_with_params(
+            API!("/auth/login"),
+            serde_json::to_string(&login_data).unwrap(),
+            &ctx,
+        )?;
+        ctx.auth_info = Some(finish_login(&ctx, response)?);
+        println!("Password changed\n");
+        Ok(())
+    }
+}
@@ -1,4 +1,3 @@
-use crate::inputtable::Inputtable;
 use crate::menu::*;
 use l1::common::auth::SessionResponse;
 use l1::common::user:: *;
@@ -9,6 +8,8 @@ use crate::client_actions::*;
 use crate::manager_actions::*;
 use crate::operator_actions::*;
 use crate::enterprise_action::*;
+use crate::administrator_actions::*;
+use crate::config::ClientConfig;
 
 
 
@@ -18,6 +19,8 @@ use std::sync::{Arc, Mutex};
 type AuthInfo = SessionResponse;
 
 pub struct ClientContext {
+    // base url of the server, requests go to `server` + `API!(...)`
+    pub server: String,
     pub auth_info: Option<AuthInfo>,
     pub login : Option<String>,
     pub bik: Option<BIK>,
@@ -30,13 +33,21 @@ pub struct Client<'a> {
     client_menu: Menu<'a>,
     operator_menu: Menu<'a>,
     enterprise_menu: Menu<'a>,
+    administrator_menu: Menu<'a>,
 }
 
 
+impl<'a> Default for Client<'a> {
+    fn default() -> Self {
+        Self::new(&ClientConfig::default())
+    }
+}
+
 impl<'a> Client<'a> {
-    pub fn new() -> Self {
+    pub fn new(config: &ClientConfig) -> Self {
         let mut client = Client {
             ctx: Arc::new(Mutex::new(ClientContext {
+                server: config.server.clone(),
                 auth_info: None,
                 login:None,
                 bik: None,
@@ -46,112 +57,259 @@ impl<'a> Client<'a> {
             manager_menu: Menu::new(),
             operator_menu: Menu::new(),
             enterprise_menu: Menu::new(),
+            administrator_menu: Menu::new(),
         };
 
         client.build_auth_menu();
-        client.build_client_menu();
-        client.build_manager_menu();
-        client.build_operator_menu();
-        client.build_enterprise_menu();
+        client.build_user_menus();
 
         client
     }
 
+    /* Role menus are taken by `take_user_menu`, so they are built again at every login */
+    fn build_user_menus(&mut self) {
+        self.build_client_menu();
+        self.build_manager_menu();
+        self.build_operator_menu();
+        self.build_enterprise_menu();
+        self.build_administrator_menu();
+    }
+
 
     fn build_auth_menu(&mut self) {
-        self.auth_menu.add_action('l' as u8, Box::new(LoginAction{}));
-        self.auth_menu.add_action('r' as u8, Box::new(RegisterAction{}));
+        self.auth_menu.add_action(b'l', Box::new(LoginAction{}));
+        self.auth_menu.add_action(b'r', Box::new(RegisterAction{}));
+        self.auth_menu.add_action(b'e', Box::new(RegisterEnterpriseAction{}));
+        self.auth_menu.add_action(b's', Box::new(RegistrationStatusAction{}));
     }
 
     pub fn user_type(&self) -> Option<UserType> {
         let ctx = self.ctx.lock().expect("Mutex");
         let auth_info = ctx.auth_info.as_ref()?;
-        Some(auth_info.user_type.clone())
+        Some(auth_info.user_type)
     }
 
     pub fn build_client_menu(&mut self) {
 
-        self.client_menu.add_action('t' as u8, Box::new(TransacionAction{}));
+        self.client_menu.add_action(b't', Box::new(TransacionAction{}));
 
         let mut acc_menu = Menu::new();
         acc_menu.set_name("ACCOUNT");
-        acc_menu.add_action('g' as u8, Box::new(AccountsGetAction{}));
-        acc_menu.add_action('o' as u8, Box::new(AccountOpenAction{}));
-        self.client_menu.add_action('a' as u8, Box::new(acc_menu));
+        acc_menu.add_action(b'g', Box::new(AccountsGetAction{}));
+        acc_menu.add_action(b'o', Box::new(AccountOpenAction{}));
+        acc_menu.add_action(b's', Box::new(AccountStatementAction{}));
+        acc_menu.add_action(b'e', Box::new(AccountStatementExportAction{}));
+        self.client_menu.add_action(b'a', Box::new(acc_menu));
 
 
         let mut deposit_menu = Menu::new();
         deposit_menu.set_name("DEPOSITS");
-        deposit_menu.add_action('o' as u8, Box::new(DepositOpen{}));
-        deposit_menu.add_action('g' as u8, Box::new(DepositGet{}));
-        deposit_menu.add_action('w' as u8, Box::new(DepositWithdrawAction{}));
-        self.client_menu.add_action('d' as u8, Box::new(deposit_menu));
+        deposit_menu.add_action(b'o', Box::new(DepositOpen{}));
+        deposit_menu.add_action(b'g', Box::new(DepositGet{}));
+        deposit_menu.add_action(b'w', Box::new(DepositWithdrawAction{}));
+        self.client_menu.add_action(b'd', Box::new(deposit_menu));
 
 
         let mut credit_menu = Menu::new();
         credit_menu.set_name("CREDIT");
-        credit_menu.add_action('g' as u8, Box::new(CreditGetAction{}));
-        credit_menu.add_action('n' as u8, Box::new(CreditNewAction{}));
-        credit_menu.add_action('d' as u8, Box::new(DiscountNewAction{}));
-        self.client_menu.add_action('c' as u8, Box::new(credit_menu));
+        credit_menu.add_action(b'g', Box::new(CreditGetAction{}));
+        credit_menu.add_action(b'n', Box::new(CreditNewAction{}));
+        credit_menu.add_action(b'd', Box::new(DiscountNewAction{}));
+        self.client_menu.add_action(b'c', Box::new(credit_menu));
 
 
         let mut salary_menu = Menu::new();
         salary_menu.set_name("SALARY");
-        salary_menu.add_action('r' as u8, Box::new(SalaryRequestAction{}));
-        self.client_menu.add_action('s' as u8, Box::new(salary_menu));
+        salary_menu.add_action(b'r', Box::new(SalaryRequestAction{}));
+        salary_menu.add_action(b'g', Box::new(SalaryMembershipsAction{}));
+        salary_menu.add_action(b'l', Box::new(SalaryLeaveAction{}));
+        salary_menu.add_action(b'p', Box::new(PayslipGetAction{}));
+        self.client_menu.add_action(b's', Box::new(salary_menu));
+
 
+        let mut order_menu = Menu::new();
+        order_menu.set_name("STANDING ORDERS");
+        order_menu.add_action(b'n', Box::new(StandingOrderNewAction{}));
+        order_menu.add_action(b'g', Box::new(StandingOrdersGetAction{}));
+        order_menu.add_action(b'c', Box::new(StandingOrderCancelAction{}));
+        self.client_menu.add_action(b'o', Box::new(order_menu));
 
-        self.client_menu.add_action('b' as u8, Box::new(SelectBankAction{}));
-        self.client_menu.add_action('i' as u8, Box::new(GetAuthInfoAction{}));
+
+        let mut security_menu = Menu::new();
+        security_menu.set_name("SECURITY");
+        security_menu.add_action(b'e', Box::new(TwoFactorEnableAction{}));
+        security_menu.add_action(b'd', Box::new(TwoFactorDisableAction{}));
+        security_menu.add_action(b'r', Box::new(RecoveryCodesAction{}));
+        security_menu.add_action(b'p', Box::new(PasswordChangeAction{}));
+        self.client_menu.add_action(b'f', Box::new(security_menu));
+
+        self.client_menu.add_action(b'b', Box::new(SelectBankAction{}));
+        self.client_menu.add_action(b'i', Box::new(GetAuthInfoAction{}));
 
     }
 
     pub fn build_manager_menu(&mut self) {
-        self.manager_menu.add_action('b' as u8 , Box::new(SelectBankAction{}));
-        self.manager_menu.add_action('a' as u8 , Box::new(AcceptRegistrationRequestsAction{}));
-        self.manager_menu.add_action('t' as u8 , Box::new(AdvanceTimeAction{}));
-        self.manager_menu.add_action('e' as u8 , Box::new(GetTimeAction{}));
-        self.manager_menu.add_action('c' as u8 , Box::new(CreditAcceptAction{}));
-        self.manager_menu.add_action('T' as u8 , Box::new(TransactionUnprotecredAction{}));
+        self.manager_menu.add_action(b'b' , Box::new(SelectBankAction{}));
+        self.manager_menu.add_action(b'a' , Box::new(AcceptRegistrationRequestsAction{}));
+        self.manager_menu.add_action(b'R' , Box::new(RejectRegistrationRequestsAction{}));
+        self.manager_menu.add_action(b't' , Box::new(AdvanceTimeAction{}));
+        self.manager_menu.add_action(b'e' , Box::new(GetTimeAction{}));
+        self.manager_menu.add_action(b's' , Box::new(TimeStepAction{}));
+        self.manager_menu.add_action(b'z' , Box::new(TimePauseAction{}));
+        self.manager_menu.add_action(b'Z' , Box::new(TimeResumeAction{}));
+        self.manager_menu.add_action(b'v' , Box::new(TimeEventsAction{}));
+        self.manager_menu.add_action(b'u' , Box::new(TimeUpcomingAction{}));
+        self.manager_menu.add_action(b'n' , Box::new(TimeSnapshotAction{}));
+        self.manager_menu.add_action(b'N' , Box::new(TimeRestoreAction{}));
+        self.manager_menu.add_action(b'c' , Box::new(CreditAcceptAction{}));
+        self.manager_menu.add_action(b'T' , Box::new(TransactionUnprotecredAction{}));
+        self.manager_menu.add_action(b'f' , Box::new(RecoveryCodesAction{}));
+        self.manager_menu.add_action(b'p' , Box::new(PasswordChangeAction{}));
+        self.manager_menu.add_action(b'P' , Box::new(PasswordResetAction{}));
     }
 
 
 
     pub fn build_operator_menu(&mut self){
-        self.operator_menu.add_action('t' as u8, Box::new(TransactionsGetAction{}));
-        self.operator_menu.add_action('r' as u8, Box::new(TransactionsRevertAction{}));
-        self.operator_menu.add_action('a' as u8, Box::new(SalaryAcceptProjAction{}));
+        self.operator_menu.add_action(b't', Box::new(TransactionsGetAction{}));
+        self.operator_menu.add_action(b'x', Box::new(TransactionsExportAction{}));
+        self.operator_menu.add_action(b'r', Box::new(TransactionsRevertAction{}));
+        self.operator_menu.add_action(b'b', Box::new(SelectBankAction{}));
+        self.operator_menu.add_action(b'a', Box::new(SalaryAcceptProjAction{}));
+        self.operator_menu.add_action(b'D', Box::new(SalaryDeclineProjAction{}));
+        self.operator_menu.add_action(b'S', Box::new(SalarySuspendProjAction{}));
+        self.operator_menu.add_action(b'U', Box::new(SalaryResumeProjAction{}));
+        self.operator_menu.add_action(b'P', Box::new(PayoutReviewAction{}));
+        self.operator_menu.add_action(b'f', Box::new(RecoveryCodesAction{}));
+        self.operator_menu.add_action(b'p', Box::new(PasswordChangeAction{}));
     }
 
 
     pub fn build_enterprise_menu(&mut self){
-        self.enterprise_menu.add_action('b' as u8, Box::new(SelectBankAction{}));
-        self.enterprise_menu.add_action('i' as u8, Box::new(SalaryInitProjectAction{}));
-        self.enterprise_menu.add_action('a' as u8, Box::new(SalaryAcceptAction{}));
-        self.enterprise_menu.add_action('o' as u8, Box::new(AccountOpenAction{}));
-        self.enterprise_menu.add_action('g' as u8, Box::new(AccountsGetAction{}));
-        self.enterprise_menu.add_action('G' as u8, Box::new(SalaryProjectGetAction{}));
-        self.enterprise_menu.add_action('t' as u8, Box::new(TransacionAction{}));
+        self.enterprise_menu.add_action(b'b', Box::new(SelectBankAction{}));
+        self.enterprise_menu.add_action(b'i', Box::new(SalaryInitProjectAction{}));
+        self.enterprise_menu.add_action(b'I', Box::new(SalaryProjectSubmitAction{}));
+        self.enterprise_menu.add_action(b'C', Box::new(SalaryProjectCloseAction{}));
+        self.enterprise_menu.add_action(b'a', Box::new(SalaryAcceptAction{}));
+        self.enterprise_menu.add_action(b'o', Box::new(AccountOpenAction{}));
+        self.enterprise_menu.add_action(b'g', Box::new(AccountsGetAction{}));
+        self.enterprise_menu.add_action(b'G', Box::new(SalaryProjectGetAction{}));
+        self.enterprise_menu.add_action(b'r', Box::new(SalaryRunsGetAction{}));
+
+        let mut employees_menu = Menu::new();
+        employees_menu.set_name("EMPLOYEES");
+        employees_menu.add_action(b'g', Box::new(EmployeesGetAction{}));
+        employees_menu.add_action(b's', Box::new(EmployeeSalaryChangeAction{}));
+        employees_menu.add_action(b'a', Box::new(EmployeeAccountChangeAction{}));
+        employees_menu.add_action(b'd', Box::new(EmployeeDismissAction{}));
+        employees_menu.add_action(b'p', Box::new(PayoutSubmitAction{}));
+        employees_menu.add_action(b'P', Box::new(PayoutsGetAction{}));
+        employees_menu.add_action(b'w', Box::new(SalaryDeductionsSetAction{}));
+        employees_menu.add_action(b'l', Box::new(PayslipsGetAction{}));
+        self.enterprise_menu.add_action(b'e', Box::new(employees_menu));
+        self.enterprise_menu.add_action(b't', Box::new(TransacionAction{}));
+        self.enterprise_menu.add_action(b'B', Box::new(BulkPaymentAction{}));
+
+        let mut order_menu = Menu::new();
+        order_menu.set_name("STANDING ORDERS");
+        order_menu.add_action(b'n', Box::new(StandingOrderNewAction{}));
+        order_menu.add_action(b'g', Box::new(StandingOrdersGetAction{}));
+        order_menu.add_action(b'c', Box::new(StandingOrderCancelAction{}));
+        self.enterprise_menu.add_action(b'S', Box::new(order_menu));
+        self.enterprise_menu.add_action(b'u', Box::new(GetAuthInfoAction{}));
+
+        let mut specialists_menu = Menu::new();
+        specialists_menu.set_name("SPECIALISTS");
+        specialists_menu.add_action(b'a', Box::new(SpecialistAddAction{}));
+        specialists_menu.add_action(b'g', Box::new(SpecialistsGetAction{}));
+        self.enterprise_menu.add_action(b's', Box::new(specialists_menu));
+
+        let mut security_menu = Menu::new();
+        security_menu.set_name("SECURITY");
+        security_menu.add_action(b'e', Box::new(TwoFactorEnableAction{}));
+        security_menu.add_action(b'd', Box::new(TwoFactorDisableAction{}));
+        security_menu.add_action(b'r', Box::new(RecoveryCodesAction{}));
+        security_menu.add_action(b'p', Box::new(PasswordChangeAction{}));
+        self.enterprise_menu.add_action(b'f', Box::new(security_menu));
+    }
+
+    pub fn build_administrator_menu(&mut self){
+        self.administrator_menu.add_action(b'b', Box::new(SelectBankAction{}));
+        self.administrator_menu.add_action(b'a', Box::new(AcceptRegistrationRequestsAction{}));
+        self.administrator_menu.add_action(b'R', Box::new(RejectRegistrationRequestsAction{}));
+        self.administrator_menu.add_action(b'c', Box::new(CreditAcceptAction{}));
+        self.administrator_menu.add_action(b'e', Box::new(GetTimeAction{}));
+        self.administrator_menu.add_action(b'E', Box::new(AdvanceTimeAction{}));
+        self.administrator_menu.add_action(b'k', Box::new(TimeStepAction{}));
+        self.administrator_menu.add_action(b'z', Box::new(TimePauseAction{}));
+        self.administrator_menu.add_action(b'Z', Box::new(TimeResumeAction{}));
+        self.administrator_menu.add_action(b'v', Box::new(TimeEventsAction{}));
+        self.administrator_menu.add_action(b'u', Box::new(TimeUpcomingAction{}));
+        self.administrator_menu.add_action(b'n', Box::new(TimeSnapshotAction{}));
+        self.administrator_menu.add_action(b'N', Box::new(TimeRestoreAction{}));
+        self.administrator_menu.add_action(b't', Box::new(TransactionsGetAction{}));
+        self.administrator_menu.add_action(b'x', Box::new(TransactionsExportAction{}));
+        self.administrator_menu.add_action(b'r', Box::new(TransactionsRevertAction{}));
+        self.administrator_menu.add_action(b'T', Box::new(TransactionUnprotecredAction{}));
+        self.administrator_menu.add_action(b's', Box::new(SalaryAcceptProjAction{}));
+        self.administrator_menu.add_action(b'D', Box::new(SalaryDeclineProjAction{}));
+        self.administrator_menu.add_action(b'S', Box::new(SalarySuspendProjAction{}));
+        self.administrator_menu.add_action(b'U', Box::new(SalaryResumeProjAction{}));
+        self.administrator_menu.add_action(b'y', Box::new(PayoutReviewAction{}));
+        self.administrator_menu.add_action(b'f', Box::new(RecoveryCodesAction{}));
+        self.administrator_menu.add_action(b'p', Box::new(PasswordChangeAction{}));
+        self.administrator_menu.add_action(b'P', Box::new(PasswordResetAction{}));
+        self.administrator_menu.add_action(b'l', Box::new(AuditLogGetAction{}));
+        self.administrator_menu.add_action(b'L', Box::new(LockoutsGetAction{}));
+    }
+
+    /* One menu per role the user holds. If there are several, they are
+     * offered as submenus of a single top-level menu. */
+    fn take_user_menu(&mut self, user_type: UserType) -> Menu<'a> {
+        let mut role_menus = vec![
+            (CLIENT, b'c', "CLIENT", std::mem::take(&mut self.client_menu)),
+            (MANAGER, b'm', "MANAGER", std::mem::take(&mut self.manager_menu)),
+            (OPERATOR, b'o', "OPERATOR", std::mem::take(&mut self.operator_menu)),
+            (ENTERPRISE, b'e', "ENTERPRISE", std::mem::take(&mut self.enterprise_menu)),
+            (ADMINISTRATOR, b'a', "ADMINISTRATOR", std::mem::take(&mut self.administrator_menu)),
+        ];
+        role_menus.retain(|(role, _, _, _)| user_type & role != 0);
+
+        if role_menus.len() == 1 {
+            return role_menus.pop().unwrap().3;
+        }
+
+        let mut user_menu = Menu::new();
+        for (_, key, name, mut menu) in role_menus {
+            menu.set_name(name);
+            user_menu.add_action(key, Box::new(menu));
+        }
+        user_menu
     }
 
     pub fn run(&mut self) {
         loop {
-            let _ = self.auth_menu.exec(self.ctx.clone());
-            if self.user_type().is_some() {
-                break;
+            while self.user_type().is_none() {
+                let _ = self.auth_menu.exec(self.ctx.clone());
             }
-        }
 
-        let user_menu = match self.user_type().unwrap() {
-           CLIENT => &mut self.client_menu,
-           MANAGER => &mut self.manager_menu,
-           OPERATOR => &mut self.operator_menu,
-           ENTERPRISE => &mut self.enterprise_menu,
-            _ => unimplemented!()
-        };
-        loop {
-            let _ = user_menu.exec(self.ctx.clone());
+            let mut user_menu = self.take_user_menu(self.user_type().unwrap());
+            loop {
+                if let Err(err) = user_menu.exec(self.ctx.clone()) {
+                    if err.is_unauthorized() {
+                        break;
+                    }
+                }
+            }
+
+            println!("Your session has ended. Please log in again\n");
+            {
+                let mut ctx = self.ctx.lock().expect("Mutex");
+                ctx.auth_info = None;
+                ctx.login = None;
+                ctx.bik = None;
+            }
+            self.build_user_menus();
         }
     }
 }
@@ -1,6 +1,6 @@
 use crate::client::ClientContext;
 use crate::inputtable::*;
-use crate::menu::Action;
+use crate::menu::{Action, ActionError};
 use crate::selector::{select_from, select_idx};
 use crate::utils::*;
 use l1::common::account::*;
@@ -8,19 +8,21 @@ use l1::common::bank::AccountID;
 use l1::common::bank::BanksGetResp;
 use l1::common::credit::*;
 use l1::common::deposit::*;
+use l1::common::interest::DayCount;
 use l1::common::transaction::{Transaction, TransactionEndPoint};
-use l1::common::user::Client;
+use l1::common::auth::AuthInfoResp;
 use l1::common::Money;
 use l1::common::salary::*;
+use l1::common::standing_order::*;
 use std::sync::{Arc, Mutex};
 
-fn select_account(ctx: &ClientContext) -> Result<AccountID, String> {
-    let resp = get_with_params(API!("/account"), &ctx)?;
+fn select_account(ctx: &ClientContext) -> Result<AccountID, ActionError> {
+    let resp = get_with_params(API!("/account"), ctx)?;
     let resp_s = handle_errors(resp)?;
     let result: AccountsGetResp =
         serde_json::from_str(&resp_s).map_err(|_| "Wrong response".to_string())?;
 
-    let acc_id = select_from(&result.accounts.iter().map(|acc| acc.id).collect())
+    let acc_id = select_from(&result.accounts.iter().map(|acc| acc.id).collect::<Vec<_>>())
         .ok_or("Cancelled".to_string())?;
 
     Ok(acc_id)
@@ -30,7 +32,7 @@ pub struct GetAuthInfoAction {}
 
 fn ensure_bank_selected(ctx: &ClientContext) -> Result<(), String> {
     if ctx.bik.is_none() {
-        return Err("Select the bank first".to_string());
+        return Err("Select the bank first".into());
     }
     Ok(())
 }
@@ -44,11 +46,11 @@ impl Action for GetAuthInfoAction {
         "Below the public personal info of currently logged user will be printed"
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         let response = get_with_params(API!("/auth"), &ctx)?;
         let response_str = handle_errors(response)?;
-        let yaml = json_to_yaml::<Client>(response_str).ok_or("Server sent wrong response")?;
+        let yaml = json_to_yaml::<AuthInfoResp>(response_str).ok_or("Server sent wrong response")?;
         println!("{}", yaml);
         Ok(())
     }
@@ -65,7 +67,7 @@ impl Action for SelectBankAction {
         "Select bank for further operations"
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let mut ctx = ctx_ref.lock().unwrap();
         let resp = get_with_params(API!("/banks"), &ctx)?;
         let response_str = handle_errors(resp)?;
@@ -80,14 +82,14 @@ impl Action for SelectBankAction {
             yaml
         );
 
-        let bank_options = banks.banks.iter().map(|b| b.bik).collect();
+        let bank_options: Vec<_> = banks.banks.iter().map(|b| b.bik).collect();
         let maybe_opt = crate::selector::select_from(&bank_options);
 
         if let Some(opt) = maybe_opt {
             ctx.bik = Some(opt);
             Ok(())
         } else {
-            Err("Cancelled".to_string())
+            Err("Cancelled".into())
         }
     }
 }
@@ -103,7 +105,7 @@ impl Action for AccountOpenAction {
         "New account in currently selected bank will be opened"
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         ensure_bank_selected(&ctx)?;
 
@@ -128,7 +130,7 @@ impl Action for AccountsGetAction {
         "Get you accounts in selected bank"
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         ensure_bank_selected(&ctx)?;
 
@@ -141,6 +143,110 @@ impl Action for AccountsGetAction {
     }
 }
 
+fn print_statement(statement: &Statement) {
+    let date = |time: Option<chrono::DateTime<chrono::Utc>>| {
+        time.map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or("...".to_string())
+    };
+    println!(
+        "Statement of account {} in bank {}, {} - {}",
+        statement.account_id,
+        statement.bik,
+        date(statement.from),
+        date(statement.to)
+    );
+    println!("Opening balance : {} BYN\n", *statement.opening_balance);
+    println!(
+        "{:>5} | {:<16} | {:<20} | {:<20} | {:>10} | {:>10}",
+        "#", "Date", "Counterparty", "Description", "Amount", "Balance"
+    );
+    println!("{}", "-".repeat(96));
+    for line in &statement.lines {
+        let counterparty = if line.counterparty.account_id == 0 {
+            "bank".to_string()
+        } else {
+            format!("{} / {}", line.counterparty.bik, line.counterparty.account_id)
+        };
+        println!(
+            "{:>5} | {:<16} | {:<20} | {:<20} | {:>10} | {:>10}",
+            line.entry_id,
+            line.time.format("%Y-%m-%d %H:%M"),
+            counterparty,
+            line.description,
+            *line.amount,
+            *line.balance
+        );
+    }
+    println!("{}", "-".repeat(96));
+    println!("Closing balance : {} BYN", *statement.closing_balance);
+}
+
+pub struct AccountStatementAction {}
+
+impl Action for AccountStatementAction {
+    fn name(&self) -> &'static str {
+        "STATEMENT of account"
+    }
+
+    fn description(&self) -> &'static str {
+        r#"Every operation with the account for a period, with running balance.
+Dates look like 2024-01-31, leave empty for no bound"#
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        ensure_bank_selected(&ctx)?;
+
+        let acc_id = select_account(&ctx)?;
+        let from = String::input("From : ", 0).ok_or("Wrong input")?;
+        let to = String::input("To : ", 0).ok_or("Wrong input")?;
+
+        let resp = get_with_query(
+            &format!("{}/{}/statement", API!("/account"), acc_id),
+            &[("from", from), ("to", to)],
+            &ctx,
+        )?;
+        let resp_s = handle_errors(resp)?;
+        let statement: Statement =
+            serde_json::from_str(&resp_s).map_err(|_| "Server sent wrong response".to_string())?;
+        print_statement(&statement);
+        Ok(())
+    }
+}
+
+pub struct AccountStatementExportAction {}
+
+impl Action for AccountStatementExportAction {
+    fn name(&self) -> &'static str {
+        "EXPORT statement"
+    }
+
+    fn description(&self) -> &'static str {
+        r#"Statement of account saved to a file as CSV, JSON or camt.053 XML.
+Dates look like 2024-01-31, leave empty for no bound"#
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        ensure_bank_selected(&ctx)?;
+
+        let acc_id = select_account(&ctx)?;
+        let from = String::input("From : ", 0).ok_or("Wrong input")?;
+        let to = String::input("To : ", 0).ok_or("Wrong input")?;
+        println!("Format : ");
+        let format = select_from(&EXPORT_FORMATS).ok_or("Cancelled".to_string())?;
+        let path = String::input("File : ", 0).ok_or("Wrong input")?;
+
+        let resp = get_with_query(
+            &format!("{}/{}/statement", API!("/account"), acc_id),
+            &[("from", from), ("to", to), ("format", format.to_string())],
+            &ctx,
+        )?;
+        let data = handle_errors(resp)?;
+        save_export(&path, &data).map_err(ActionError::from)
+    }
+}
+
 pub struct TransacionAction {}
 
 impl Action for TransacionAction {
@@ -156,7 +262,7 @@ and account id.
         "#
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         ensure_bank_selected(&ctx)?;
 
@@ -205,7 +311,7 @@ and transferred to bank's account. After specified amount
 of time you will be able to withdraw you money back"#
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         ensure_bank_selected(&ctx)?;
         let deposit_req =
@@ -233,7 +339,7 @@ impl Action for DepositGet {
         "Below all deposits you've opened in currently seleteed bank will be printed."
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         ensure_bank_selected(&ctx)?;
 
@@ -257,7 +363,7 @@ impl Action for DepositWithdrawAction {
 it was expired"#
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         ensure_bank_selected(&ctx)?;
 
@@ -298,7 +404,7 @@ impl Action for CreditNewAction {
         "Request a credit from a bank. You will be given the money after it will be accepted."
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
 
         ensure_bank_selected(&ctx)?;
@@ -310,7 +416,8 @@ impl Action for CreditNewAction {
             amount : Money(i32::input("Amount of money : ", 0).ok_or("Wrong input")?),
             interest_rate : u8::input("Interest rate : ", 0).ok_or("Wrong input")?,
             term : u8::input("Term : ", 0).ok_or("Wrong input")?,
-            src_account:src_acc
+            src_account:src_acc,
+            day_count : DayCount::input("Day count : ", 0).ok_or("Wrong input")?
         };
 
 
@@ -337,7 +444,7 @@ impl Action for DiscountNewAction {
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
 
         let ctx = ctx_ref.lock().unwrap();
 
@@ -349,7 +456,8 @@ impl Action for DiscountNewAction {
             amount : Money(i32::input("Amount of money : ", 0).ok_or("Wrong input")?),
             interest_rate : 0,
             term : u8::input("Term : ", 0).ok_or("Wrong input")?,
-            src_account:src_acc
+            src_account:src_acc,
+            day_count : DayCount::default()
         };
 
 
@@ -376,7 +484,7 @@ impl Action for CreditGetAction {
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
 
 
         let ctx = ctx_ref.lock().unwrap();
@@ -411,7 +519,7 @@ impl Action for SalaryRequestAction {
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
 
         let enterprise = String::input("Input enterprise name : ", 0).ok_or("Cancelled")?;
@@ -437,3 +545,238 @@ impl Action for SalaryRequestAction {
 }
 
 
+
+fn get_memberships(ctx: &ClientContext) -> Result<Vec<SalaryMembership>, ActionError> {
+    let resp = get_with_params(API!("/salary/memberships"), ctx)?;
+    let resp_s = handle_errors(resp)?;
+    serde_json::from_str(&resp_s).map_err(|_| "Server sent wrong response".into())
+}
+
+pub struct SalaryMembershipsAction {}
+
+impl Action for SalaryMembershipsAction {
+    fn name(&self) -> &'static str {
+        "GET salary projects"
+    }
+
+    fn description(&self) -> &'static str {
+        "Enterprises that pay you salary, now or before"
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let memberships = get_memberships(&ctx)?;
+        if memberships.is_empty() {
+            println!("You are not in any salary project");
+        }
+        for membership in memberships {
+            println!("{}", membership);
+        }
+        Ok(())
+    }
+}
+
+pub struct PayslipGetAction {}
+
+impl Action for PayslipGetAction {
+    fn name(&self) -> &'static str {
+        "GET payslip"
+    }
+
+    fn description(&self) -> &'static str {
+        "Your salary and what was withheld from it for a month"
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let enterprises: Vec<String> = get_memberships(&ctx)?
+            .into_iter()
+            .map(|membership| membership.enterprise)
+            .collect();
+        let idx = select_idx(&enterprises).ok_or("Cancelled")?;
+        let period = input_period()?;
+
+        let resp = get_with_query(
+            API!("/salary/payslip"),
+            &[("enterprise", enterprises[idx].clone()), ("period", period.to_string())],
+            &ctx,
+        )?;
+        let resp_s = handle_errors(resp)?;
+        let payslip: Payslip = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        print_payslips(&[payslip]).map_err(ActionError::from)
+    }
+}
+
+pub struct SalaryLeaveAction {}
+
+impl Action for SalaryLeaveAction {
+    fn name(&self) -> &'static str {
+        "LEAVE salary project"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stop receiving salary from an enterprise, the days worked this month are paid"
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let enterprises: Vec<String> = get_memberships(&ctx)?
+            .into_iter()
+            .filter(|membership| membership.employee.is_active())
+            .map(|membership| membership.enterprise)
+            .collect();
+        let idx = select_idx(&enterprises).ok_or("Cancelled")?;
+
+        let req = SalaryLeaveRequest { enterprise: enterprises[idx].clone() };
+        let resp = post_with_params(API!("/salary/leave"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+fn input_date(invitation: &str, required: bool) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+    let value = String::input(invitation, 0).ok_or("Wrong input")?;
+    if value.is_empty() && !required {
+        return Ok(None);
+    }
+    let date = chrono::NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+        .map_err(|_| "Date must look like 2024-01-31".to_string())?;
+    Ok(date.and_hms_opt(0, 0, 0).map(|time| time.and_utc()))
+}
+
+fn get_standing_orders(ctx: &ClientContext) -> Result<Vec<StandingOrder>, ActionError> {
+    let resp = get_with_params(API!("/order"), ctx)?;
+    let resp_s = handle_errors(resp)?;
+    let result: StandingOrdersGetResp =
+        serde_json::from_str(&resp_s).map_err(|_| "Server sent wrong response".to_string())?;
+    Ok(result.orders)
+}
+
+pub struct StandingOrderNewAction {}
+
+impl Action for StandingOrderNewAction {
+    fn name(&self) -> &'static str {
+        "NEW standing order"
+    }
+
+    fn description(&self) -> &'static str {
+        r#"Schedule a transfer from your account : once on a date, weekly or monthly.
+Failed transfers are retried the next day, up to 3 times"#
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        ensure_bank_selected(&ctx)?;
+
+        let src_account = select_account(&ctx)?;
+        let dst = TransactionEndPoint::input("Receiver : \n", 0).ok_or("Wrong input")?;
+        let amount = Money::input("Amount : ", 0).ok_or("Wrong input")?;
+        println!("Frequency : ");
+        let frequency = match select_from(&["once", "weekly", "monthly"]).ok_or("Cancelled")? {
+            "weekly" => Frequency::Weekly,
+            "monthly" => Frequency::Monthly,
+            _ => Frequency::Once,
+        };
+        let start = input_date("First transfer date (2024-01-31) : ", true)?.ok_or("Wrong input")?;
+        let end = if frequency == Frequency::Once {
+            None
+        } else {
+            input_date("Last date, empty for no end : ", false)?
+        };
+        let reference = String::input("Reference : ", 0).ok_or("Wrong input")?;
+
+        let req = StandingOrderNewRequest { src_account, dst, amount, frequency, start, end, reference };
+        let resp = post_with_params(
+            API!("/order/new"),
+            serde_json::to_string(&req).expect("Unserializable"),
+            &ctx,
+        )?;
+        let resp_s = handle_errors(resp)?;
+        let result: StandingOrderNewResp =
+            serde_json::from_str(&resp_s).map_err(|_| "Server sent wrong response".to_string())?;
+        println!("Created standing order #{}", result.id);
+        Ok(())
+    }
+}
+
+pub struct StandingOrdersGetAction {}
+
+impl Action for StandingOrdersGetAction {
+    fn name(&self) -> &'static str {
+        "GET standing orders"
+    }
+
+    fn description(&self) -> &'static str {
+        "Your standing orders in the selected bank with their recent attempts"
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        ensure_bank_selected(&ctx)?;
+
+        let orders = get_standing_orders(&ctx)?;
+        if orders.is_empty() {
+            println!("You have no standing orders");
+            return Ok(());
+        }
+        println!(
+            "{:>4} | {:<9} | {:<7} | {:>4} | {:<16} | {:>10} | {:<16} | Reference",
+            "#", "Status", "Every", "From", "To", "Amount", "Next attempt"
+        );
+        for order in &orders {
+            println!(
+                "{:>4} | {:<9} | {:<7} | {:>4} | {:<16} | {:>10} | {:<16} | {}",
+                order.id,
+                format!("{:?}", order.status).to_lowercase(),
+                format!("{:?}", order.frequency).to_lowercase(),
+                order.src.account_id,
+                format!("{} / {}", order.dst.bik, order.dst.account_id),
+                *order.amount,
+                order
+                    .next_attempt()
+                    .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or("-".to_string()),
+                order.reference
+            );
+            if let Some(last) = order.history.last() {
+                if let Some(error) = &last.error {
+                    println!("       last attempt {} failed : {}", last.attempt, error);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct StandingOrderCancelAction {}
+
+impl Action for StandingOrderCancelAction {
+    fn name(&self) -> &'static str {
+        "CANCEL standing order"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stop an active standing order, transfers made already stay in place"
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        ensure_bank_selected(&ctx)?;
+
+        let active: Vec<OrderID> = get_standing_orders(&ctx)?
+            .iter()
+            .filter(|order| order.status == OrderStatus::Active)
+            .map(|order| order.id)
+            .collect();
+        let id = select_from(&active).ok_or("Cancelled")?;
+
+        let resp = post_with_params(
+            API!("/order/cancel"),
+            serde_json::to_string(&StandingOrderCancelRequest { id }).expect("Unserializable"),
+            &ctx,
+        )?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+/*
+ * Client settings. Taken from the YAML file given by `--config`,
+ * then `BANK_SERVER` environment variable, then `--server` flag.
+ */
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ClientConfig {
+    // base url of the server, without `/api/v1`
+    pub server: String,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            server: "http://127.0.0.1:8080".to_string(),
+        }
+    }
+}
+
+const USAGE: &str = "Usage : bank-client [--config <file.yaml>] [--server <url>]";
+
+impl ClientConfig {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut path = None;
+        let mut server = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => path = Some(args.next().ok_or(USAGE)?),
+                "--server" => server = Some(args.next().ok_or(USAGE)?),
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+            }
+        }
+
+        let mut config = match path {
+            Some(path) => Self::load(&path)?,
+            None => ClientConfig::default(),
+        };
+        if let Ok(env_server) = std::env::var("BANK_SERVER") {
+            config.server = env_server;
+        }
+        if let Some(server) = server {
+            config.server = server;
+        }
+        config.server = config.server.trim_end_matches('/').to_string();
+        if !config.server.starts_with("http://") && !config.server.starts_with("https://") {
+            return Err(format!("Server url must start with http:// or https:// : {}", config.server));
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Can't read config {} : {}", path, e))?;
+        serde_yaml::from_str(&text).map_err(|e| format!("Invalid config {} : {}", path, e))
+    }
+}
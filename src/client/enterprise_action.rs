@@ -1,3 +1,5 @@
+use l1::common::auth::{AddSpecialistReq, SpecialistResp};
+use l1::common::payment::*;
 use l1::common::salary::*;
 use l1::common::Money;
 use l1::common::transaction::*;
@@ -20,7 +22,7 @@ impl Action for SalaryAcceptAction {
         "Accept or decline salary request"
     }
 
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
 
         let resp = get_with_params(API!("/salary/accept"), &ctx)?;
@@ -75,17 +77,26 @@ impl Action for SalaryInitProjectAction {
     }
 
     fn description(&self) -> &'static str {
-        "Create new salary project (only 1 for enterprise)"
+        r#"Set up a salary project (only 1 for enterprise), then submit it to the bank.
+Salary is paid once a month on the pay day"#
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
 
         let acc = TransactionEndPoint::input("Account to pay salary : \n", 0).ok_or("Cancelled")?;
+        let pay_day = u32::input("Pay day of month (1-31) : ", 0).ok_or("Cancelled")?;
+        println!("If the account can't cover everyone : ");
+        let policy = match select_from(&["pay nobody", "pay while money lasts"]).ok_or("Cancelled")? {
+            "pay nobody" => PayrollPolicy::AllOrNothing,
+            _ => PayrollPolicy::Partial,
+        };
 
         let resp = post_with_params(API!("/salary/new"), serde_json::to_string(&SalaryInitProjRequest{
-            account : acc
+            account : acc,
+            pay_day,
+            policy
         }).unwrap(), &ctx)?;
 
         handle_errors(resp)?;
@@ -95,6 +106,45 @@ impl Action for SalaryInitProjectAction {
 }
 
 
+pub struct SalaryProjectSubmitAction {}
+
+impl Action for SalaryProjectSubmitAction {
+    fn name(&self) -> &'static str {
+        "SUBMIT salary project"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send the salary project to the bank of its account for approval"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = post_with_params(API!("/salary/submit"), String::new(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct SalaryProjectCloseAction {}
+
+impl Action for SalaryProjectCloseAction {
+    fn name(&self) -> &'static str {
+        "CLOSE salary project"
+    }
+
+    fn description(&self) -> &'static str {
+        "End the salary project, every employee has to be dismissed and paid first"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = post_with_params(API!("/salary/close"), String::new(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+
 pub struct SalaryProjectGetAction {}
 
 impl Action for SalaryProjectGetAction {
@@ -107,7 +157,7 @@ impl Action for SalaryProjectGetAction {
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
 
 
@@ -119,3 +169,462 @@ impl Action for SalaryProjectGetAction {
         Ok(())
     }
 }
+
+
+pub struct SalaryRunsGetAction {}
+
+impl Action for SalaryRunsGetAction {
+    fn name(&self) -> &'static str {
+        "GET payroll runs"
+    }
+
+    fn description(&self) -> &'static str {
+        "Salary payments of every period, unpaid employees are retried on the next update"
+    }
+
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let resp = get_with_params(API!("/salary/runs"), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let runs: Vec<PayrollRun> = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        if runs.is_empty() {
+            println!("No payroll runs yet");
+        }
+
+        for run in &runs {
+            println!(
+                "\n{} {:?} (paid on {}) : {:?}, {} BYN paid, {} attempt(s)",
+                run.period,
+                run.kind,
+                run.pay_date.format("%Y-%m-%d"),
+                run.status,
+                *run.total_paid(),
+                run.attempts
+            );
+            for line in &run.lines {
+                println!(
+                    "    {:<16} | {:>8} / {:<4} | {:>8} | {:?} {} {}",
+                    line.login,
+                    line.account.bik,
+                    line.account.account_id,
+                    *line.amount,
+                    line.status,
+                    line.description.as_deref().unwrap_or(""),
+                    line.error.as_deref().unwrap_or("")
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+
+pub struct SpecialistAddAction {}
+
+impl Action for SpecialistAddAction {
+    fn name(&self) -> &'static str {
+        "ADD specialist"
+    }
+
+    fn description(&self) -> &'static str {
+        "Give a colleague own login to act on behalf of the enterprise"
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let req = AddSpecialistReq::input("New specialist : \n", 0).ok_or("Cancelled".to_string())?;
+        let resp = post_with_params(
+            API!("/enterprise/specialists"),
+            serde_json::to_string(&req).unwrap(),
+            &ctx,
+        )?;
+        handle_errors(resp)?;
+        println!("Pass the password to your colleague, it has to be changed at the first login\n");
+        Ok(())
+    }
+}
+
+
+pub struct SpecialistsGetAction {}
+
+impl Action for SpecialistsGetAction {
+    fn name(&self) -> &'static str {
+        "GET specialists"
+    }
+
+    fn description(&self) -> &'static str {
+        "List logins acting on behalf of the enterprise"
+    }
+
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = get_with_params(API!("/enterprise/specialists"), &ctx)?;
+        let yaml = json_to_yaml::<Vec<SpecialistResp>>(handle_errors(resp)?)
+            .ok_or("Server sent wrong response".to_string())?;
+        println!("Specialists : \n\n{}", yaml);
+        Ok(())
+    }
+}
+
+
+pub struct BulkPaymentAction {}
+
+impl Action for BulkPaymentAction {
+    fn name(&self) -> &'static str {
+        "BULK payments"
+    }
+
+    fn description(&self) -> &'static str {
+        r#"Upload a batch of payments from the selected bank.
+Files ending with .xml are read as ISO 20022 pain.001, others as CSV :
+    src_account,dst_bik,dst_account,amount,reference
+Atomic batch is executed only if every payment is valid"#
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        if ctx.bik.is_none() {
+            return Err("Select the bank first".into());
+        }
+
+        let path = String::input("File : ", 0).ok_or("Wrong input")?;
+        let batch = std::fs::read_to_string(&path).map_err(|e| format!("Can't read {} : {}", path, e))?;
+        let format = if path.to_lowercase().ends_with(".xml") { "pain001" } else { "csv" };
+        println!("Mode : ");
+        let mode = select_from(&["atomic", "partial"]).ok_or("Cancelled".to_string())?;
+
+        let resp = post_with_query(
+            API!("/payment/batch"),
+            batch,
+            &[("format", format.to_string()), ("mode", mode.to_string())],
+            &ctx,
+        )?;
+        let resp_s = handle_errors(resp)?;
+        let result: PaymentBatchResp =
+            serde_json::from_str(&resp_s).map_err(|_| "Server sent wrong response".to_string())?;
+
+        println!("{:>5} | {:<9} | {:<24} | Error", "Line", "Status", "Reference");
+        for line in &result.lines {
+            let status = match line.status {
+                PaymentStatus::Executed => "executed",
+                PaymentStatus::Failed => "failed",
+                PaymentStatus::Skipped => "skipped",
+            };
+            println!(
+                "{:>5} | {:<9} | {:<24} | {}",
+                line.line,
+                status,
+                line.reference,
+                line.error.as_deref().unwrap_or("")
+            );
+        }
+        println!(
+            "\nExecuted {} of {} payments, {} BYN in total",
+            result.executed,
+            result.lines.len(),
+            *result.total
+        );
+        if result.mode == BatchMode::Atomic && result.failed > 0 {
+            println!("Nothing was executed, fix the failed lines and upload the batch again");
+        }
+        Ok(())
+    }
+}
+
+
+fn select_employee(ctx: &ClientContext) -> Result<Employee, ActionError> {
+    let resp = get_with_params(API!("/salary/proj"), ctx)?;
+    let proj_s = handle_errors(resp)?;
+    let proj: SalaryProject = serde_json::from_str(&proj_s)
+        .map_err(|_| "Server sent wrong response".to_string())?;
+    let employees: Vec<Employee> = proj.employees.into_iter().filter(|employee| employee.is_active()).collect();
+    let logins: Vec<&str> = employees.iter().map(|employee| employee.login.as_str()).collect();
+    let idx = select_idx(&logins).ok_or("Cancelled")?;
+    Ok(employees[idx].clone())
+}
+
+fn print_employee_event(entry: &EmployeeHistoryEntry) {
+    let event = match &entry.event {
+        EmployeeEvent::Hired { salary } => format!("hired, salary {}", **salary),
+        EmployeeEvent::SalaryChanged { salary, effective } => {
+            format!("salary {} from {}", **salary, effective.format("%Y-%m-%d"))
+        }
+        EmployeeEvent::AccountChanged { account } => {
+            format!("pay moved to {} / {}", account.bik, account.account_id)
+        }
+        EmployeeEvent::Dismissed { final_payment } => format!("dismissed, final payment {}", **final_payment),
+        EmployeeEvent::Left { final_payment } => format!("left, final payment {}", **final_payment),
+    };
+    println!("        {} {}", entry.time.format("%Y-%m-%d %H:%M"), event);
+}
+
+pub struct EmployeesGetAction {}
+
+impl Action for EmployeesGetAction {
+    fn name(&self) -> &'static str {
+        "GET employees"
+    }
+
+    fn description(&self) -> &'static str {
+        "Employees of your salary project with their history"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let resp = get_with_params(API!("/salary/proj"), &ctx)?;
+        let proj_s = handle_errors(resp)?;
+        let proj: SalaryProject = serde_json::from_str(&proj_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        let now = chrono::Utc::now();
+
+        for employee in &proj.employees {
+            println!(
+                "{:<16} | {:>8} / {:<4} | {:>8} | {}",
+                employee.login,
+                employee.account.bik,
+                employee.account.account_id,
+                *employee.salary_on(&now),
+                if employee.is_active() { "employed" } else { "dismissed" }
+            );
+            for entry in &employee.history {
+                print_employee_event(entry);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct EmployeeSalaryChangeAction {}
+
+impl Action for EmployeeSalaryChangeAction {
+    fn name(&self) -> &'static str {
+        "CHANGE salary"
+    }
+
+    fn description(&self) -> &'static str {
+        "New salary of an employee, paid from the given date on"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let employee = select_employee(&ctx)?;
+        let salary = Money::input("New salary : ", 0).ok_or("Cancelled")?;
+        let effective = String::input("Effective from (2024-01-31) : ", 0).ok_or("Cancelled")?;
+        let effective = chrono::NaiveDate::parse_from_str(&effective, "%Y-%m-%d")
+            .map_err(|_| "Date must look like 2024-01-31".to_string())?
+            .and_hms_opt(0, 0, 0)
+            .ok_or("Wrong date")?
+            .and_utc();
+
+        let req = EmployeeSalaryChangeRequest { login: employee.login, salary, effective };
+        let resp = post_with_params(API!("/salary/employee/salary"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct EmployeeAccountChangeAction {}
+
+impl Action for EmployeeAccountChangeAction {
+    fn name(&self) -> &'static str {
+        "MOVE salary account"
+    }
+
+    fn description(&self) -> &'static str {
+        "Pay an employee to another account of theirs"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let employee = select_employee(&ctx)?;
+        let account = TransactionEndPoint::input("New account : \n", 0).ok_or("Cancelled")?;
+
+        let req = EmployeeAccountChangeRequest { login: employee.login, account };
+        let resp = post_with_params(API!("/salary/employee/account"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct EmployeeDismissAction {}
+
+impl Action for EmployeeDismissAction {
+    fn name(&self) -> &'static str {
+        "DISMISS employee"
+    }
+
+    fn description(&self) -> &'static str {
+        "The days worked this month are paid right away, if not paid already"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let employee = select_employee(&ctx)?;
+        let req = EmployeeDismissRequest { login: employee.login };
+        let resp = post_with_params(API!("/salary/employee/dismiss"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct PayoutSubmitAction {}
+
+impl Action for PayoutSubmitAction {
+    fn name(&self) -> &'static str {
+        "SUBMIT payouts"
+    }
+
+    fn description(&self) -> &'static str {
+        "Bonuses and reimbursements to employees, paid once the bank approves them"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let mut payouts = Vec::new();
+        loop {
+            let employee = select_employee(&ctx)?;
+            let amount = Money::input("Amount : ", 0).ok_or("Cancelled")?;
+            let description = String::input("Description : ", 0).ok_or("Cancelled")?;
+            payouts.push(PayoutLine { login: employee.login, amount, description });
+            if !bool::input("Add another payout? (y/n) : ", 0).unwrap_or(false) {
+                break;
+            }
+        }
+        println!("Pay : ");
+        let timing = match select_from(&["when approved", "with the next salary"]).ok_or("Cancelled")? {
+            "when approved" => PayoutTiming::Immediate,
+            _ => PayoutTiming::NextRun,
+        };
+
+        let req = PayoutBatchRequest { payouts, timing };
+        let resp = post_with_params(API!("/salary/payout"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let resp: PayoutBatchResp = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        println!("Payout batch {} is sent to the bank", resp.id);
+        Ok(())
+    }
+}
+
+pub struct PayoutsGetAction {}
+
+impl Action for PayoutsGetAction {
+    fn name(&self) -> &'static str {
+        "GET payouts"
+    }
+
+    fn description(&self) -> &'static str {
+        "Payout batches and their review, paid ones are in the payroll runs"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let resp = get_with_params(API!("/salary/payout"), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let batches: Vec<PayoutBatch> = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        if batches.is_empty() {
+            println!("No payouts yet");
+        }
+        for batch in &batches {
+            println!(
+                "\n#{} {:?} ({:?}) : {} BYN, submitted {} {}",
+                batch.id,
+                batch.status,
+                batch.timing,
+                *batch.total(),
+                batch.submitted.format("%Y-%m-%d"),
+                batch.reason.as_deref().unwrap_or("")
+            );
+            for payout in &batch.payouts {
+                println!("    {:<16} | {:>8} | {}", payout.login, *payout.amount, payout.description);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct SalaryDeductionsSetAction {}
+
+impl Action for SalaryDeductionsSetAction {
+    fn name(&self) -> &'static str {
+        "SET deductions"
+    }
+
+    fn description(&self) -> &'static str {
+        "Income tax, social fund and voluntary deductions withheld from salary"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let income_tax_percent = u32::input("Income tax, % : ", 0).ok_or("Cancelled")?;
+        let tax_account = if income_tax_percent > 0 {
+            Some(TransactionEndPoint::input("Tax account : \n", 0).ok_or("Cancelled")?)
+        } else {
+            None
+        };
+        let social_fund_percent = u32::input("Social fund, % : ", 0).ok_or("Cancelled")?;
+        let fund_account = if social_fund_percent > 0 {
+            Some(TransactionEndPoint::input("Fund account : \n", 0).ok_or("Cancelled")?)
+        } else {
+            None
+        };
+
+        let mut voluntary = Vec::new();
+        while bool::input("Add voluntary deduction? (y/n) : ", 0).unwrap_or(false) {
+            let employee = select_employee(&ctx)?;
+            let description = String::input("Description : ", 0).ok_or("Cancelled")?;
+            let amount = Money::input("Amount per salary : ", 0).ok_or("Cancelled")?;
+            let account = TransactionEndPoint::input("Pay to : \n", 0).ok_or("Cancelled")?;
+            voluntary.push(VoluntaryDeduction { login: employee.login, description, amount, account });
+        }
+
+        let req = PayrollDeductions {
+            income_tax_percent,
+            tax_account,
+            social_fund_percent,
+            fund_account,
+            voluntary,
+        };
+        let resp = post_with_params(API!("/salary/deductions"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct PayslipsGetAction {}
+
+impl Action for PayslipsGetAction {
+    fn name(&self) -> &'static str {
+        "GET payslips"
+    }
+
+    fn description(&self) -> &'static str {
+        "Payslips of every employee for a month"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let period = input_period()?;
+        let resp = get_with_query(API!("/salary/payslips"), &[("period", period.to_string())], &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let payslips: Vec<Payslip> = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        if payslips.is_empty() {
+            println!("Nobody was paid for {}", period);
+            return Ok(());
+        }
+        print_payslips(&payslips).map_err(ActionError::from)
+    }
+}
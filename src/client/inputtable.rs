@@ -3,18 +3,20 @@ use l1::common::bank::{AccountID, BIK};
 use l1::common::transaction::*;
 use l1::common::user::*;
 use l1::common::deposit::*;
+use l1::common::interest::{Capitalization, DayCount};
 use l1::common::Money;
 use std::convert::TryInto;
 use std::io::Write;
 use std::string::String;
 use chrono::{DateTime, Utc, NaiveDateTime};
+use crate::selector::select_from;
 
 const SPACE_PER_INDENT:  i32= 3;
 
 fn add_indentation(s: &str, n: usize) -> String {
     let indent = str::repeat(" ", n);
-    let ss = indent.to_string() + &String::from(s);
-    ss
+    
+    indent.to_string() + &String::from(s)
 }
 
 pub trait Inputtable {
@@ -40,13 +42,13 @@ where
         println!("Do you want to cancel input?[y/n]");
         let mut s = String::new();
         std::io::stdin().read_line(&mut s).expect("Error");
-        if s.chars().nth(0).unwrap_or('\0') == 'y' {
+        if s.chars().next().unwrap_or('\0') == 'y' {
             return None;
         }
     }
 }
 
-/// ----------- INPUTTABLE for basic types ----------- ///
+// ----------- INPUTTABLE for basic types ----------- //
 
 impl Inputtable for String {
     type InputType = String;
@@ -175,7 +177,7 @@ impl Inputtable for DateTime<Utc> {
 //
 // impl_Inputtable!(for u64);
 
-/// ----------- INPUTTABLE for realm structs ----------- ///
+// ----------- INPUTTABLE for realm structs ----------- //
 
 impl Inputtable for UserPassportData {
     type InputType = UserPassportData;
@@ -239,6 +241,44 @@ impl Inputtable for RegisterUserReq {
     }
 }
 
+impl Inputtable for Enterprise {
+    type InputType = Enterprise;
+    fn input(invitation: &str, level: i32) -> Option<Self::InputType> {
+        Self::print_invitation(invitation, level);
+        Some(Enterprise {
+            enterprise_type: input_until_valid::<String>("Type of enterprise : ", level)?,
+            name: input_until_valid::<String>("Name : ", level)?,
+            unp: input_until_valid::<String>("UNP (9 digits) : ", level)?,
+            address: input_until_valid::<String>("Address : ", level)?,
+        })
+    }
+}
+
+impl Inputtable for RegisterEnterpriseReq {
+    type InputType = RegisterEnterpriseReq;
+    fn input(invitation: &str, level: i32) -> Option<Self::InputType> {
+        Self::print_invitation(invitation, level);
+        Some(RegisterEnterpriseReq {
+            enterprise: input_until_valid::<Enterprise>("Enterprise : \n", level)?,
+            login_data: input_until_valid::<LoginReq>("Credentials of responsible specialist : \n", level)?,
+            full_name: input_until_valid::<UserPersonalName>("Full name of responsible specialist : \n", level)?,
+            position: input_until_valid::<String>("Position : ", level)?,
+        })
+    }
+}
+
+impl Inputtable for AddSpecialistReq {
+    type InputType = AddSpecialistReq;
+    fn input(invitation: &str, level: i32) -> Option<Self::InputType> {
+        Self::print_invitation(invitation, level);
+        Some(AddSpecialistReq {
+            login_data: input_until_valid::<LoginReq>("Credentials (password is temporary) : \n", level)?,
+            full_name: input_until_valid::<UserPersonalName>("Full name : \n", level)?,
+            position: input_until_valid::<String>("Position : ", level)?,
+        })
+    }
+}
+
 impl Inputtable for AcceptRegistrationReq {
     type InputType = AcceptRegistrationReq;
     fn input(invitation: &str, level: i32) -> Option<Self::InputType> {
@@ -261,6 +301,29 @@ impl Inputtable for TransactionEndPoint {
 }
 
 
+impl Inputtable for DayCount {
+    type InputType = DayCount;
+    fn input(invitation: &str, n: i32) -> Option<Self::InputType> {
+        Self::print_invitation(invitation, n);
+        println!();
+        select_from(&[DayCount::Actual365, DayCount::Actual360, DayCount::Thirty360])
+    }
+}
+
+impl Inputtable for Capitalization {
+    type InputType = Capitalization;
+    fn input(invitation: &str, n: i32) -> Option<Self::InputType> {
+        Self::print_invitation(invitation, n);
+        println!();
+        select_from(&[
+            Capitalization::Daily,
+            Capitalization::Monthly,
+            Capitalization::Quarterly,
+            Capitalization::AtMaturity,
+        ])
+    }
+}
+
 impl Inputtable for DepositNewRequest {
     type InputType = DepositNewRequest;
 
@@ -273,7 +336,9 @@ impl Inputtable for DepositNewRequest {
             src_account : u64::input("Source account : ", n)?,
             interest_rate : u8::input("Interest rate : ", n)?,
             months_expires : u32::input("Month expires", n)?,
-            amount : Money(i32::input("Amount of money : ", n)?)
+            amount : Money(i32::input("Amount of money : ", n)?),
+            day_count : DayCount::input("Day count : ", n)?,
+            capitalization : Capitalization::input("Capitalization : ", n)?
         })
 //TMP!!!
 
@@ -1,4 +1,5 @@
 pub mod client;
+pub mod config;
 pub mod inputtable;
 pub mod menu;
 pub mod selector;
@@ -12,6 +13,7 @@ pub mod client_actions;
 pub mod manager_actions;
 pub mod operator_actions;
 pub mod enterprise_action;
+pub mod administrator_actions;
 
 
 const LOGO: &str = r#"
@@ -47,11 +49,19 @@ const BANK_CLI_STR: &str = r#"
 
 
 fn main() {
+    let config = match config::ClientConfig::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+
     print!("{}", LOGO);
-    print!("{}\n", BANK_CLI_STR);
+    println!("{}", BANK_CLI_STR);
     println!("Welcome to FINARUS - Finance system in Rust\n");
 
-    let mut client = client::Client::new();
+    let mut client = client::Client::new(&config);
     client.run();
 
 }
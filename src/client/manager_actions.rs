@@ -1,16 +1,16 @@
 
-use crate::menu::Action;
+use crate::menu::{Action, ActionError};
 use std::sync::{Arc, Mutex};
 use crate::client::ClientContext;
 use crate::utils::*;
 use crate::inputtable::*;
-use l1::common::auth::{GetRegistrationsReq, AcceptRegistrationReq};
-use l1::common::time::TimeAdvanceReq;
+use l1::common::auth::{GetRegistrationsReq, AcceptRegistrationReq, RejectRegistrationReq, PasswordResetReq, PasswordResetResponse};
+use l1::common::time::{ScheduledEvent, ScheduledEventKind, TimeAdvanceReq, TimeEvent, TimeRestoreReq, TimeSnapshot, TimeStatus, TimeStepReq};
 use l1::common::credit::{CreditUnaccepted, CreditAcceptRequest};
 use l1::common::transaction::*;
 use l1::common::Money;
-use crate::selector::{select_idx, select_from};
-use chrono::{DateTime, Utc};
+use crate::selector::select_idx;
+use chrono::DateTime;
 
 
 fn ensure_bank_selected(ctx: &ClientContext) -> Result<(), String> {
@@ -29,7 +29,7 @@ impl Action for AcceptRegistrationRequestsAction {
     fn description(&self) -> &'static str {
         "Accept registration requests"
     }
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         
                 let ctx = ctx_ref.lock().unwrap();
                 let resp = get_with_params(API!("/auth/accept"), &ctx)?;
@@ -52,6 +52,38 @@ impl Action for AcceptRegistrationRequestsAction {
 }
 
 
+pub struct RejectRegistrationRequestsAction {}
+
+impl Action for RejectRegistrationRequestsAction {
+    fn name(&self) -> &'static str {
+        "REJECT REGISTRATION requests"
+    }
+    fn description(&self) -> &'static str {
+        "Reject registration request. The applicant will see the reason"
+    }
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = get_with_params(API!("/auth/accept"), &ctx)?;
+        let response_str = handle_errors(resp)?;
+        let options : Vec<GetRegistrationsReq> = serde_json::from_str(&response_str).map_err(
+            |_| "Server sent wrong response".to_string()
+        )?;
+
+        let reject_login_idx = select_idx(&options).ok_or("Cancelled")?;
+        let reason = String::input("Reason of rejection : ", 0).ok_or("Cancelled")?;
+        let reject = RejectRegistrationReq{login : options[reject_login_idx].login.clone(), reason};
+
+        let resp = post_with_params(
+            API!("/auth/reject"),
+            serde_json::to_string(&reject).unwrap(),
+            &ctx,
+        )?;
+        let _ = handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+
 pub struct AdvanceTimeAction {}
 
 impl Action for AdvanceTimeAction {
@@ -62,7 +94,7 @@ impl Action for AdvanceTimeAction {
         "CHANGE server local TIME"
     }
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
 
         let time = DateTime::input("Input time to advance : ", 0).ok_or("Cancelled")?;
@@ -87,19 +119,211 @@ impl Action for GetTimeAction {
         "Get server local time"
     }
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
 
-        let resp = get_with_params(API!("/time/get"),
+        let resp = get_with_params(API!("/time/status"),
                                     &ctx)?;
         let resp_s = handle_errors(resp)?;
+        let status: TimeStatus = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+
+        println!("Time : \n{}{}", status.time, if status.paused { " (paused)" } else { "" });
+        Ok(())
+    }
+}
+
+
+pub struct TimePauseAction {}
+
+impl Action for TimePauseAction {
+    fn description(&self) -> &'static str {
+        "Stop server local time until it is resumed, steps still move it"
+    }
+    fn name(&self) -> &'static str {
+        "PAUSE time"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = post_with_params(API!("/time/pause"), String::new(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+
+pub struct TimeResumeAction {}
+
+impl Action for TimeResumeAction {
+    fn description(&self) -> &'static str {
+        "Let paused server local time run again"
+    }
+    fn name(&self) -> &'static str {
+        "RESUME time"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = post_with_params(API!("/time/resume"), String::new(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+
+pub struct TimeStepAction {}
+
+impl Action for TimeStepAction {
+    fn description(&self) -> &'static str {
+        "Move time forward, every month on the way is processed in order"
+    }
+    fn name(&self) -> &'static str {
+        "STEP time"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let months = u32::input("Months : ", 0).ok_or("Cancelled")?;
+        let days = u32::input("Days : ", 0).ok_or("Cancelled")?;
+        let req = TimeStepReq { days, months };
+
+        let resp = post_with_params(API!("/time/step"),
+            serde_json::to_string(&req).expect("Unserializable"),
+                        &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        println!("Time : \n{}", resp_s);
+        Ok(())
+    }
+}
+
+
+pub struct TimeSnapshotAction {}
+
+impl Action for TimeSnapshotAction {
+    fn description(&self) -> &'static str {
+        "Save the banks at the current time, to come back to it later"
+    }
+    fn name(&self) -> &'static str {
+        "SNAPSHOT time"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = post_with_params(API!("/time/snapshot"), String::new(), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let snapshot: TimeSnapshot = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        println!("Snapshot {}", snapshot);
+        Ok(())
+    }
+}
+
+
+pub struct TimeRestoreAction {}
+
+impl Action for TimeRestoreAction {
+    fn description(&self) -> &'static str {
+        "Put the banks and time back to a snapshot, time may go back"
+    }
+    fn name(&self) -> &'static str {
+        "RESTORE snapshot"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = get_with_params(API!("/time/snapshot"), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let snapshots: Vec<TimeSnapshot> = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
 
+        let idx = select_idx(&snapshots).ok_or("Cancelled")?;
+        let req = TimeRestoreReq { snapshot: snapshots[idx].id };
+        let resp = post_with_params(API!("/time/restore"),
+            serde_json::to_string(&req).expect("Unserializable"),
+                        &ctx)?;
+        let resp_s = handle_errors(resp)?;
         println!("Time : \n{}", resp_s);
         Ok(())
     }
 }
 
 
+pub struct TimeUpcomingAction {}
+
+impl Action for TimeUpcomingAction {
+    fn description(&self) -> &'static str {
+        "Deposit accruals, credit installments, payrolls and standing orders to come"
+    }
+    fn name(&self) -> &'static str {
+        "GET upcoming events"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let resp = get_with_params(API!("/time/upcoming"), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let events: Vec<ScheduledEvent> = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        if events.is_empty() {
+            println!("Nothing scheduled");
+        }
+        for event in &events {
+            let what = match &event.kind {
+                ScheduledEventKind::DepositAccrual { bik, owner, idx } => format!("Interest on deposit #{} of {} in {}", idx, owner, bik),
+                ScheduledEventKind::CreditAccrual { bik, owner, idx } => format!("Interest on credit #{} of {} in {}", idx, owner, bik),
+                ScheduledEventKind::CreditInstallment { bik, owner, idx } => format!("Credit #{} of {} in {}", idx, owner, bik),
+                ScheduledEventKind::Payroll { enterprise } => format!("Payroll of {}", enterprise),
+                ScheduledEventKind::StandingOrder { id } => format!("Standing order #{}", id),
+            };
+            println!("{} {}", event.due.format("%Y-%m-%d %H:%M"), what);
+        }
+        Ok(())
+    }
+}
+
+
+pub struct TimeEventsAction {}
+
+impl Action for TimeEventsAction {
+    fn description(&self) -> &'static str {
+        "What the runner did at every change of time"
+    }
+    fn name(&self) -> &'static str {
+        "GET time events"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let resp = get_with_params(API!("/time/events"), &ctx)?;
+        let resp_s = handle_errors(resp)?;
+        let events: Vec<TimeEvent> = serde_json::from_str(&resp_s)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        if events.is_empty() {
+            println!("No events yet");
+        }
+        for event in &events {
+            println!("{:>5} {} {:?} : {}", event.id, event.time.format("%Y-%m-%d %H:%M"), event.kind, event.message);
+            for entry in &event.transactions {
+                println!(
+                    "        {} / {} -> {} / {} {:>8} {}",
+                    entry.transaction.src.bik,
+                    entry.transaction.src.account_id,
+                    entry.transaction.dst.bik,
+                    entry.transaction.dst.account_id,
+                    *entry.transaction.amount,
+                    entry.description
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+
 pub struct CreditAcceptAction {}
 
 impl Action for CreditAcceptAction {
@@ -112,7 +336,7 @@ impl Action for CreditAcceptAction {
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
 
         let ctx = ctx_ref.lock().unwrap();
         ensure_bank_selected(&ctx)?;
@@ -147,7 +371,7 @@ impl Action for TransactionUnprotecredAction {
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
           let ctx = ctx_ref.lock().unwrap();
 
           println!("Input transaction details.\n");
@@ -159,7 +383,7 @@ impl Action for TransactionUnprotecredAction {
         };
 
         let transaction_resp = post_with_params(
-            API!("/transaction"),
+            API!("/transaction/unprotected"),
             serde_json::to_string(&transaction_req).unwrap(),
             &ctx,
         )?;
@@ -171,3 +395,34 @@ impl Action for TransactionUnprotecredAction {
     }
 }
 
+
+
+pub struct PasswordResetAction {}
+
+impl Action for PasswordResetAction {
+    fn name(&self) -> &'static str {
+        "RESET user PASSWORD"
+    }
+
+    fn description(&self) -> &'static str {
+        r#"Issue a temporary password for a user who lost theirs.
+Verify the user's identity first. The user will have to change it at the next login"#
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        let login = String::input("Login of the user : ", 0).ok_or("Cancelled")?;
+        let resp = post_with_params(
+            API!("/auth/password/reset"),
+            serde_json::to_string(&PasswordResetReq{login}).expect("Unserializable"),
+            &ctx,
+        )?;
+        let reset : PasswordResetResponse = serde_json::from_str(&handle_errors(resp)?).map_err(
+            |_| "Server sent wrong response".to_string()
+        )?;
+        println!("Temporary password for `{}` : {}", reset.login, reset.temporary_password);
+        println!("Pass it to the user in person. It is valid for a single login.\n");
+        Ok(())
+    }
+}
@@ -1,8 +1,8 @@
-use std::collections::hash_map::{HashMap};
 use std::boxed::Box;
 use std::io::Write;
 
 use crate::client::ClientContext;
+use l1::common::error::{ErrorCode, ErrorResponse};
 use std::sync::{Arc, Mutex};
 
 use colored::Colorize;
@@ -12,10 +12,65 @@ pub fn flush(){
     std::io::stdout().flush().unwrap();
 }
 
+/* Server errors keep their code, so the menus can react on it */
+#[derive(Debug)]
+pub enum ActionError {
+    Message(String),
+    Server(ErrorResponse),
+}
+
+impl ActionError {
+    /* Wrong credentials, or the session is gone, e.g. ended by a password reset */
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, ActionError::Server(err) if err.code == ErrorCode::Unauthorized)
+    }
+
+    fn print(&self) {
+        match self {
+            ActionError::Server(err) if err.code == ErrorCode::ValidationFailed => {
+                println!("\n{} : {}", "INVALID INPUT".red(), err.message);
+                for field in &err.details {
+                    println!("   {} : {}", field.field.yellow(), field.message);
+                }
+                println!("\n");
+            }
+            ActionError::Server(err) => println!("\n{} : {}\n\n", "ERROR".red(), err.message),
+            ActionError::Message(msg) => println!("\n{} : {}\n\n", "ERROR".red(), msg),
+        }
+    }
+}
+
+impl std::fmt::Display for ActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActionError::Message(msg) => write!(f, "{}", msg),
+            ActionError::Server(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<String> for ActionError {
+    fn from(msg: String) -> Self {
+        ActionError::Message(msg)
+    }
+}
+
+impl From<&str> for ActionError {
+    fn from(msg: &str) -> Self {
+        ActionError::Message(msg.to_string())
+    }
+}
+
+impl From<ErrorResponse> for ActionError {
+    fn from(err: ErrorResponse) -> Self {
+        ActionError::Server(err)
+    }
+}
+
 pub trait Action {
     fn name(&self) -> &'static str;
     fn description(&self) -> &'static str;
-    fn exec(&mut self, ctx : Arc<Mutex<ClientContext>>) -> Result<(), String>;
+    fn exec(&mut self, ctx : Arc<Mutex<ClientContext>>) -> Result<(), ActionError>;
 }
 
 pub struct Menu<'a>{
@@ -24,6 +79,12 @@ pub struct Menu<'a>{
     actions : Vec<(u8, Box<dyn Action + 'a>)>
 }
 
+impl<'a> Default for Menu<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> Menu<'a> {
     pub fn new() -> Self {
         Menu{
@@ -43,7 +104,7 @@ impl<'a> Menu<'a> {
     }
 
     pub fn add_action(&mut self, opt : u8, action : Box<dyn Action + 'a>) -> &mut Self{
-        let _ = self.actions.push((opt, action));
+        self.actions.push((opt, action));
         self
     }
 }
@@ -53,7 +114,7 @@ impl<'a> Menu<'a> {
 
 
 impl<'a> Action for Menu<'a> {
-    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref: Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         println!("Select an option:");
         loop {
             let login = ctx_ref.lock().unwrap().login.clone();
@@ -70,16 +131,10 @@ impl<'a> Action for Menu<'a> {
             if inp.len() == 2 {
                 let option = inp.as_bytes()[0];
                 let maybe_menu = self.actions.iter_mut().find(|v| v.0 == option);
-                match maybe_menu{
-                    Some(val) => {
-                        print!("\n{}\n\n", val.1.description());
-                        flush();
-                        return val.1.exec(ctx_ref.clone()).map_err(|err : String|{
-                            println!("\n{} : {}\n\n", "ERROR".red(), err);
-                           err 
-                        });
-                    }
-                    None => ()
+                if let Some(val) = maybe_menu {
+                    print!("\n{}\n\n", val.1.description());
+                    flush();
+                    return val.1.exec(ctx_ref.clone()).inspect_err(ActionError::print);
                 }
             }
 
@@ -133,7 +188,7 @@ impl<'a> Action for Menu<'a> {
 //     fn description(&self) -> &'static str {
 //         self.desc
 //     }
-//     fn exec(&mut self) -> Result<(), String> {
+//     fn exec(&mut self) -> Result<(), ActionError> {
 //         (self.func)()
 //     }
 // }
@@ -1,11 +1,13 @@
-use crate::menu::Action;
+use crate::menu::{Action, ActionError};
 use std::sync::{Arc, Mutex};
 use crate::client::ClientContext;
 use crate::utils::*;
+use crate::inputtable::*;
 use l1::common::transaction::*;
 use crate::selector::*;
 use std::io::Write;
 use l1::common::salary::*;
+use l1::common::auth::Login;
 
 pub fn flush(){
     std::io::stdout().flush().unwrap();
@@ -26,12 +28,12 @@ impl Action for TransactionsGetAction {
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
 
         let resp = get_with_params(API!("/transaction"), &ctx)?;
         let resp_s = handle_errors(resp)?;
-        let yaml = json_to_yaml::<Vec<Transaction>>(resp_s).ok_or(
+        let yaml = json_to_yaml::<Vec<LedgerEntry>>(resp_s).ok_or(
             "Server sent wrong response".to_string()
         )?;
 
@@ -41,6 +43,34 @@ impl Action for TransactionsGetAction {
 }
 
 
+pub struct TransactionsExportAction {}
+
+
+impl Action for TransactionsExportAction {
+    fn name(&self) -> &'static str {
+        "EXPORT transactions"
+    }
+
+
+    fn description(&self) -> &'static str {
+        "Save the transaction log to a file as CSV or JSON"
+    }
+
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+
+        println!("Format : ");
+        let format = select_from(&EXPORT_FORMATS[..2]).ok_or("Cancelled".to_string())?;
+        let path = String::input("File : ", 0).ok_or("Wrong input")?;
+
+        let resp = get_with_query(API!("/transaction"), &[("format", format.to_string())], &ctx)?;
+        let data = handle_errors(resp)?;
+        save_export(&path, &data).map_err(ActionError::from)
+    }
+}
+
+
 pub struct TransactionsRevertAction {}
 
 
@@ -54,7 +84,7 @@ impl Action for TransactionsRevertAction {
     }
 
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         
         print!("Please print `yes` to confirm the revert action.");
@@ -62,7 +92,7 @@ impl Action for TransactionsRevertAction {
         let mut s = String::new();
         std::io::stdin().read_line(&mut s).unwrap();
         if s != "yes\n"{
-            return Err("Cancelled".to_string());
+            return Err("Cancelled".into());
         }
 
 
@@ -90,7 +120,7 @@ impl Action for SalaryAcceptProjAction {
         "Accept salary project"
     }
 
-    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), String> {
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
         let ctx = ctx_ref.lock().unwrap();
         let requests_resp = get_with_params(API!("/salary/accept_proj"), &ctx)?;
         let requests : Vec<SalaryProjectResp> = serde_json::from_str(&handle_errors(requests_resp)?)
@@ -113,3 +143,111 @@ impl Action for SalaryAcceptProjAction {
 
 
 }
+
+fn select_salary_proj(url: &str, ctx: &ClientContext) -> Result<Login, ActionError> {
+    let resp = get_with_params(url, ctx)?;
+    let projects : Vec<SalaryProjectResp> = serde_json::from_str(&handle_errors(resp)?)
+        .map_err(|_| "Server sent wrong response".to_string())?;
+    let idx = select_idx(&projects).ok_or("Cancelled".to_string())?;
+    Ok(projects[idx].enterprise.clone())
+}
+
+pub struct SalaryDeclineProjAction {}
+
+impl Action for SalaryDeclineProjAction {
+    fn name(&self) -> &'static str {
+        "DECLINE salary project"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send a salary project back to the enterprise, with the reason"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let enterprise = select_salary_proj(API!("/salary/accept_proj"), &ctx)?;
+        let reason = String::input("Reason : ", 0).ok_or("Cancelled")?;
+
+        let req = SalaryProjReasonRequest { enterprise, reason };
+        let resp = post_with_params(API!("/salary/decline_proj"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct SalarySuspendProjAction {}
+
+impl Action for SalarySuspendProjAction {
+    fn name(&self) -> &'static str {
+        "SUSPEND salary project"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stop paying salary of an enterprise until the project is resumed"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let enterprise = select_salary_proj(API!("/salary/bank_proj"), &ctx)?;
+        let reason = String::input("Reason : ", 0).ok_or("Cancelled")?;
+
+        let req = SalaryProjReasonRequest { enterprise, reason };
+        let resp = post_with_params(API!("/salary/suspend_proj"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct SalaryResumeProjAction {}
+
+impl Action for SalaryResumeProjAction {
+    fn name(&self) -> &'static str {
+        "RESUME salary project"
+    }
+
+    fn description(&self) -> &'static str {
+        "Resume a suspended salary project, the missed salary is paid"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let enterprise = select_salary_proj(API!("/salary/bank_proj"), &ctx)?;
+
+        let req = SalaryAcceptProjRequest { enterprise };
+        let resp = post_with_params(API!("/salary/resume_proj"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
+
+pub struct PayoutReviewAction {}
+
+impl Action for PayoutReviewAction {
+    fn name(&self) -> &'static str {
+        "REVIEW payouts"
+    }
+
+    fn description(&self) -> &'static str {
+        "Approve or decline payouts of salary projects in the bank"
+    }
+
+    fn exec(&mut self, ctx_ref : Arc<Mutex<ClientContext>>) -> Result<(), ActionError> {
+        let ctx = ctx_ref.lock().unwrap();
+        let resp = get_with_params(API!("/salary/payout/review"), &ctx)?;
+        let batches : Vec<PayoutBatch> = serde_json::from_str(&handle_errors(resp)?)
+            .map_err(|_| "Server sent wrong response".to_string())?;
+        let idx = select_idx(&batches).ok_or("Cancelled".to_string())?;
+
+        let accept = bool::input("Approve? (y/n) : ", 0).ok_or("Cancelled")?;
+        let reason = if accept {
+            String::new()
+        } else {
+            String::input("Reason : ", 0).ok_or("Cancelled")?
+        };
+
+        let req = PayoutReviewRequest { id : batches[idx].id, accept, reason };
+        let resp = post_with_params(API!("/salary/payout/review"), serde_json::to_string(&req).unwrap(), &ctx)?;
+        handle_errors(resp)?;
+        Ok(())
+    }
+}
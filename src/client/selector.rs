@@ -1,12 +1,11 @@
 
-use std::vec::Vec;
 use std::io::Write;
 
-pub fn select_from<T>(options : &Vec<T> ) ->  Option<T>
+pub fn select_from<T>(options : &[T] ) ->  Option<T>
     where T : ToString + Copy
 {
 
-    if options.len() == 0{
+    if options.is_empty(){
         println!("Nothing to select from.");
         return None;
     }
@@ -35,11 +34,11 @@ pub fn select_from<T>(options : &Vec<T> ) ->  Option<T>
 
 }
 
-pub fn select_idx<T>(options : &Vec<T> ) ->  Option<usize>
+pub fn select_idx<T>(options : &[T] ) ->  Option<usize>
     where T : ToString 
 {
 
-    if options.len() == 0{
+    if options.is_empty(){
         println!("Nothing to select from.");
         return None;
     }
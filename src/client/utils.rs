@@ -1,5 +1,8 @@
 
 use crate::client::ClientContext;
+use crate::inputtable::Inputtable;
+use l1::common::error::{ErrorCode, ErrorResponse};
+use l1::common::salary::{PayrollPeriod, Payslip};
 use reqwest::blocking::Response;
 use reqwest::StatusCode;
 
@@ -16,28 +19,42 @@ where
     Some(yaml)
 }
 
+/* Path of an API endpoint, the server url is prepended from `ClientContext` */
 #[macro_export]
 macro_rules! API {
     ($url : literal) => {
-        concat!("http://127.0.0.1:8080/api/v1", $url)
+        concat!("/api/v1", $url)
     };
 }
 
-pub fn handle_errors(response: reqwest::blocking::Response) -> Result<String, String> {
-
-    match response.status() {
-        StatusCode::OK => {
-            println!("{}", "Success".green());
-            Ok(response.text().unwrap())
-        }
-        _ => Err(response.text().unwrap()),
+/* Decodes the error body, servers answer with `ErrorResponse` on every failure */
+pub fn handle_errors(response: reqwest::blocking::Response) -> Result<String, ErrorResponse> {
+    let status = response.status();
+    if status == StatusCode::OK {
+        println!("{}", "Success".green());
+        return Ok(response.text().unwrap());
     }
+    let text = response.text().unwrap_or_default();
+    Err(serde_json::from_str::<ErrorResponse>(&text).unwrap_or(ErrorResponse {
+        code: ErrorCode::from_status_code(status.as_u16()),
+        message: text,
+        details: Vec::new(),
+    }))
 }
 
 pub fn post_with_params(url: &str, body: String, ctx: &ClientContext) -> Result<Response, String> {
+    post_with_query(url, body, &[], ctx)
+}
+
+/* Same as `post_with_params`, with extra query parameters */
+pub fn post_with_query(
+    url: &str,
+    body: String,
+    query: &[(&str, String)],
+    ctx: &ClientContext,
+) -> Result<Response, String> {
     let client = reqwest::blocking::Client::new();
-    // let ctx = ctx_ref.lock().expect("Mutex");
-    let mut post_req = client.post(url);
+    let mut post_req = client.post(format!("{}{}", ctx.server, url)).query(query);
     if let Some(auth) = &ctx.auth_info {
         post_req = post_req.query(&[("token", auth.token.to_string().as_str())]);
     }
@@ -50,10 +67,18 @@ pub fn post_with_params(url: &str, body: String, ctx: &ClientContext) -> Result<
 }
 
 pub fn get_with_params(url: &str, ctx: &ClientContext) -> Result<Response, String> {
+    get_with_query(url, &[], ctx)
+}
+
+/* Same as `get_with_params`, with extra query parameters */
+pub fn get_with_query(
+    url: &str,
+    query: &[(&str, String)],
+    ctx: &ClientContext,
+) -> Result<Response, String> {
     let client = reqwest::blocking::Client::new();
-    // let ctx = ctx_ref.lock().expect("Mutex");
 
-    let mut get_req = client.get(url);
+    let mut get_req = client.get(format!("{}{}", ctx.server, url)).query(query);
     if let Some(auth) = &ctx.auth_info {
         get_req = get_req.query(&[("token", auth.token.to_string().as_str())]);
     }
@@ -63,3 +88,33 @@ pub fn get_with_params(url: &str, ctx: &ClientContext) -> Result<Response, Strin
 
     get_req.send().map_err(|e| e.to_string())
 }
+
+/* Export formats offered by the server, see `format` parameter of statement and transaction routes */
+pub const EXPORT_FORMATS: [&str; 3] = ["csv", "json", "camt053"];
+
+pub fn save_export(path: &str, data: &str) -> Result<(), String> {
+    if path.is_empty() {
+        return Err("File name is required".to_string());
+    }
+    std::fs::write(path, data).map_err(|e| format!("Can't write {} : {}", path, e))?;
+    println!("Saved to {}", path);
+    Ok(())
+}
+
+/* Payroll period as `2024-01` */
+pub fn input_period() -> Result<PayrollPeriod, String> {
+    let period = String::input("Period (2024-01) : ", 0).ok_or("Cancelled")?;
+    period.trim().parse()
+}
+
+/* Prints payslips and saves them to a text file if asked */
+pub fn print_payslips(payslips: &[Payslip]) -> Result<(), String> {
+    let documents: Vec<String> = payslips.iter().map(|payslip| payslip.to_string()).collect();
+    let document = documents.join("\n\n");
+    println!("{}", document);
+    let path = String::input("Save to file (empty to skip) : ", 0).unwrap_or_default();
+    if path.is_empty() {
+        return Ok(());
+    }
+    save_export(&path, &document)
+}
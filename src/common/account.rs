@@ -1,6 +1,10 @@
 
 use serde::{Serialize, Deserialize};
-use crate::common::bank::{Account, AccountID};
+use chrono::{DateTime, Utc};
+use crate::common::bank::{Account, AccountID, BIK};
+use crate::common::transaction::TransactionEndPoint;
+use crate::common::validate::Validate;
+use crate::common::Money;
 
 #[derive(Serialize,  Deserialize)]
 pub struct AccountOpenResp {
@@ -13,8 +17,35 @@ pub struct AccountCloseReq {
 }
 
 
+impl Validate for AccountCloseReq {}
+
+
 #[derive(Serialize, Deserialize)]
 pub struct AccountsGetResp {
     pub accounts : Vec<Account>
 }
 
+
+/* One ledger entry of a statement. `amount` is negative for outgoing money. */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StatementLine {
+    pub entry_id : u64,
+    pub time : DateTime<Utc>,
+    // bik 0 and account 0 stand for the bank itself
+    pub counterparty : TransactionEndPoint,
+    pub description : String,
+    pub amount : Money,
+    pub balance : Money
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Statement {
+    pub bik : BIK,
+    pub account_id : AccountID,
+    // `None` means unbounded
+    pub from : Option<DateTime<Utc>>,
+    pub to : Option<DateTime<Utc>>,
+    pub opening_balance : Money,
+    pub lines : Vec<StatementLine>,
+    pub closing_balance : Money
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AuditEvent {
+    // too many failed logins for one login name
+    LoginLocked,
+    // too many failed logins from one address
+    AddressLocked,
+    PasswordChanged,
+    // temporary password issued by bank staff
+    PasswordReset,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub time : chrono::DateTime<chrono::Utc>,
+    pub event : AuditEvent,
+    // login or address the event is about
+    pub subject : String,
+    pub details : String,
+}
+
+impl std::fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LockoutInfo {
+    pub subject : String,
+    pub failures : u32,
+    pub locked_until : chrono::DateTime<chrono::Utc>,
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use crate::common::user::{Client, UserType};
+use crate::common::user::{Client, Enterprise, Specialist, UserData, UserPersonalName, UserType};
+use crate::common::validate::{Validate, ValidationErrors};
 
 pub type Token = u64;
 pub type Login = String;
@@ -17,6 +18,70 @@ pub struct SessionResponse{
     pub user_type : UserType,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct TotpEnrollResponse {
+    pub secret : String,
+    pub otpauth_uri : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum LoginResponse {
+    Session(SessionResponse),
+    // password accepted, TOTP code (or recovery code) is expected at `/auth/login/2fa`
+    SecondFactorRequired { challenge : Token },
+    // two-factor authentication is mandatory for the user, but was never set up
+    EnrollmentRequired { challenge : Token, enrollment : TotpEnrollResponse },
+    // logged in with a temporary password, new one is expected at `/auth/login/password`
+    PasswordChangeRequired { challenge : Token },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewPasswordReq {
+    pub challenge : Token,
+    pub new_password : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordChangeReq {
+    pub old_password : String,
+    pub new_password : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordResetReq {
+    pub login : Login,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordResetResponse {
+    pub login : Login,
+    // valid for a single login only, has to be replaced right after it
+    pub temporary_password : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SecondFactorReq {
+    pub challenge : Token,
+    pub code : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SecondFactorResponse {
+    pub session : SessionResponse,
+    // non-empty only when the login completed 2FA enrollment
+    pub recovery_codes : Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TotpCodeReq {
+    pub code : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes : Vec<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct UserInfoResonse {
     pub user_type : UserType,
@@ -28,21 +93,183 @@ pub struct RegisterUserReq{
     pub user_data : Client,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RegisterEnterpriseReq{
+    // credentials of the responsible specialist
+    pub login_data : LoginReq,
+    pub enterprise : Enterprise,
+    pub full_name : UserPersonalName,
+    pub position : String,
+}
+
+/* Another specialist added by an already approved one */
+#[derive(Serialize, Deserialize)]
+pub struct AddSpecialistReq{
+    // the password has to be changed by the specialist at the first login
+    pub login_data : LoginReq,
+    pub full_name : UserPersonalName,
+    pub position : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SpecialistResp{
+    pub login : Login,
+    pub specialist : Specialist,
+}
+
+impl std::fmt::Display for SpecialistResp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuthInfoResp{
+    pub login : Login,
+    pub user_type : UserType,
+    pub user_data : UserData,
+    // the enterprise a specialist acts for
+    pub enterprise : Option<Enterprise>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct AcceptRegistrationReq {
     pub login : String
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RejectRegistrationReq {
+    pub login : String,
+    pub reason : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum RegistrationStatus {
+    Pending,
+    Accepted,
+    Rejected { reason : String },
+}
+
+impl std::fmt::Display for RegistrationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistrationStatus::Pending => write!(f, "Pending. Waiting for a manager to review"),
+            RegistrationStatus::Accepted => write!(f, "Accepted. You can log in now"),
+            RegistrationStatus::Rejected { reason } => write!(f, "Rejected : {}", reason),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum RegistrationData {
+    Client(Client),
+    Enterprise { enterprise : Enterprise, specialist : Specialist },
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetRegistrationsReq {
     pub login : String,
-    pub user : Client
+    pub data : RegistrationData
+}
+
+impl std::fmt::Display for GetRegistrationsReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
 }
 
-impl ToString for GetRegistrationsReq{
-    fn to_string(&self) -> String {
-        serde_yaml::to_string(self).unwrap()
+
+
+
+// ----------- validation ----------- //
+
+impl Validate for LoginReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.login, "login");
+        errors.check(!self.password.is_empty(), "password", "Must not be empty");
+        errors.into_result()
     }
 }
 
+impl Validate for SecondFactorReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.code, "code");
+        errors.into_result()
+    }
+}
 
+impl Validate for TotpCodeReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.code, "code");
+        errors.into_result()
+    }
+}
+
+// password strength is a server policy, checked by the auth service
+impl Validate for NewPasswordReq {}
+
+impl Validate for PasswordChangeReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(!self.old_password.is_empty(), "old_password", "Must not be empty");
+        errors.into_result()
+    }
+}
+
+impl Validate for PasswordResetReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.login, "login");
+        errors.into_result()
+    }
+}
+
+impl Validate for RegisterUserReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.nested("login_data", &self.login_data);
+        errors.nested("user_data", &self.user_data);
+        errors.into_result()
+    }
+}
+
+impl Validate for RegisterEnterpriseReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.nested("login_data", &self.login_data);
+        errors.nested("enterprise", &self.enterprise);
+        errors.nested("full_name", &self.full_name);
+        errors.check_not_empty(&self.position, "position");
+        errors.into_result()
+    }
+}
+
+impl Validate for AddSpecialistReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.nested("login_data", &self.login_data);
+        errors.nested("full_name", &self.full_name);
+        errors.check_not_empty(&self.position, "position");
+        errors.into_result()
+    }
+}
+
+impl Validate for AcceptRegistrationReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.login, "login");
+        errors.into_result()
+    }
+}
+
+impl Validate for RejectRegistrationReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.login, "login");
+        errors.check_not_empty(&self.reason, "reason");
+        errors.into_result()
+    }
+}
@@ -3,6 +3,8 @@ use chrono::{DateTime, Utc};
 use crate::common::bank::AccountID;
 use crate::common::Money;
 use crate::common::auth::Login;
+use crate::common::interest::{DayCount, InterestAccrual};
+use crate::common::validate::{Validate, ValidationErrors};
 
 #[repr(u8)]
 #[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
@@ -14,14 +16,14 @@ pub enum CreditTerm {
     MG24(u8)
 }
 
-impl ToString for CreditTerm {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for CreditTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::M3 => "3 months".to_string(),
-            Self::M6 => "6 months".to_string(),
-            Self::M12 => "12 months".to_string(),
-            Self::M24 => "24 months".to_string(),
-            Self::MG24(_) => "custom".to_string()
+            Self::M3 => write!(f, "3 months"),
+            Self::M6 => write!(f, "6 months"),
+            Self::M12 => write!(f, "12 months"),
+            Self::M24 => write!(f, "24 months"),
+            Self::MG24(_) => write!(f, "custom")
         }
     }
 }
@@ -29,9 +31,12 @@ impl ToString for CreditTerm {
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct CreditParams {
     pub src_account : AccountID,
+    // annual, accrued daily on what is left to pay
     pub interest_rate : u8,
     pub term : u8,
     pub amount : Money,
+    #[serde(default)]
+    pub day_count : DayCount,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
@@ -44,12 +49,16 @@ pub struct Credit {
 
     pub first_pay : DateTime<Utc>,
     pub last_pay :  DateTime<Utc>,
+
+    // principal left, interest is paid with every installment
+    pub outstanding : Money,
+    pub accrual : InterestAccrual,
 }
 
 
-impl ToString for CreditUnaccepted{
-    fn to_string(&self) -> String {
-        serde_yaml::to_string(self).unwrap()
+impl std::fmt::Display for CreditUnaccepted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
 }
 
@@ -73,3 +82,42 @@ pub struct CreditNewRequest {
 pub struct CreditAcceptRequest {
     pub idx : usize
 }
+
+// longest credit the banks offer
+const CREDIT_MAX_MONTHS : u8 = 120;
+
+impl Validate for CreditParams {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(*self.amount > 0, "amount", "Must be positive");
+        errors.check(self.interest_rate <= 100, "interest_rate", "Must be at most 100 %");
+        errors.check(
+            (1..=CREDIT_MAX_MONTHS).contains(&self.term),
+            "term",
+            "Must be from 1 to 120 months",
+        );
+        errors.into_result()
+    }
+}
+
+impl Validate for CreditNewRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(*self.amount > 0, "amount", "Must be positive");
+        errors.check(
+            self.interest_rate.is_none_or(|rate| rate <= 100),
+            "interest_rate",
+            "Must be at most 100 %",
+        );
+        if let CreditTerm::MG24(months) = self.term {
+            errors.check(
+                months > 24 && months <= CREDIT_MAX_MONTHS,
+                "term",
+                "Custom term must be from 25 to 120 months",
+            );
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for CreditAcceptRequest {}
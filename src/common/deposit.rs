@@ -2,7 +2,9 @@ use serde::{
 Serialize, Deserialize
 };
 use crate::common::Money;
+use crate::common::validate::{Validate, ValidationErrors};
 use crate::common::auth::Login;
+use crate::common::interest::{Capitalization, DayCount, InterestAccrual};
 
 
 use crate::common::bank::AccountID; 
@@ -13,10 +15,12 @@ pub struct Deposit {
     pub owner : Login,
     pub interest_rate : u8,
     pub start_date : chrono::DateTime<chrono::Utc>,
-    pub last_update : chrono::DateTime<chrono::Utc>,
     pub end_date : chrono::DateTime<chrono::Utc>,
     pub initial_amount : Money,
-    pub current_amount : Money
+    pub current_amount : Money,
+    pub capitalization : Capitalization,
+    // accrued daily, added to `current_amount` on capitalization dates
+    pub accrual : InterestAccrual
 }
 
 #[derive(Serialize, Deserialize)]
@@ -24,7 +28,11 @@ pub struct DepositNewRequest {
     pub src_account : AccountID,
     pub interest_rate : u8,
     pub months_expires : u32,
-    pub amount : Money
+    pub amount : Money,
+    #[serde(default)]
+    pub day_count : DayCount,
+    #[serde(default)]
+    pub capitalization : Capitalization
 }
 
 #[derive(Serialize, Deserialize)]
@@ -45,6 +53,25 @@ pub struct DepositPossibleResponse {
     
 }
 
+// longest deposit the banks offer
+const DEPOSIT_MAX_MONTHS : u32 = 120;
+
+impl Validate for DepositNewRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(*self.amount > 0, "amount", "Must be positive");
+        errors.check(self.interest_rate <= 100, "interest_rate", "Must be at most 100 %");
+        errors.check(
+            (1..=DEPOSIT_MAX_MONTHS).contains(&self.months_expires),
+            "months_expires",
+            "Must be from 1 to 120 months",
+        );
+        errors.into_result()
+    }
+}
+
+impl Validate for DepositWithdrawRequest {}
+
 pub struct DepositParams {
     pub interest_rate : u8,
     pub months_expires : u32,
@@ -53,9 +80,9 @@ pub struct DepositParams {
 }
 
 
-impl ToString for Deposit {
-    fn to_string(&self) -> String {
-        serde_yaml::to_string(&self).unwrap()
+impl std::fmt::Display for Deposit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
 }
 
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use crate::common::validate::FieldError;
+
+/* Machine-readable reason of a failed request. Codes are part of the API and must not be renamed. */
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    // no session, or login credentials are wrong
+    Unauthorized,
+    // authenticated, but not allowed to do this
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    // conflicts with the current state, e.g. duplicate login
+    Conflict,
+    // request fields are invalid, see `details`
+    ValidationFailed,
+    TooManyRequests,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            ErrorCode::BadRequest => 400,
+            ErrorCode::Unauthorized => 401,
+            ErrorCode::Forbidden => 403,
+            ErrorCode::NotFound => 404,
+            ErrorCode::MethodNotAllowed => 405,
+            ErrorCode::Conflict => 409,
+            ErrorCode::ValidationFailed => 422,
+            ErrorCode::TooManyRequests => 429,
+            ErrorCode::InternalError => 500,
+        }
+    }
+
+    /* Best guess for responses without a JSON body */
+    pub fn from_status_code(status: u16) -> Self {
+        match status {
+            401 => ErrorCode::Unauthorized,
+            403 => ErrorCode::Forbidden,
+            404 => ErrorCode::NotFound,
+            405 => ErrorCode::MethodNotAllowed,
+            409 => ErrorCode::Conflict,
+            422 => ErrorCode::ValidationFailed,
+            429 => ErrorCode::TooManyRequests,
+            500..=599 => ErrorCode::InternalError,
+            _ => ErrorCode::BadRequest,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ErrorResponse {
+    pub code : ErrorCode,
+    pub message : String,
+    // per-field problems, filled for `ValidationFailed` only
+    #[serde(default)]
+    pub details : Vec<FieldError>,
+}
+
+impl std::fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        for err in &self.details {
+            write!(f, "\n   {} : {}", err.field, err.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ErrorResponse> for String {
+    fn from(err: ErrorResponse) -> Self {
+        err.to_string()
+    }
+}
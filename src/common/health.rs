@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/* Answer of `/ready`, the server takes requests once everything is up.
+ * The data is built in memory when the server starts, nothing is loaded from storage. */
+#[derive(Serialize, Deserialize)]
+pub struct ReadinessResp {
+    pub ready : bool,
+    pub runner_alive : bool
+}
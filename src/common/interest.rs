@@ -0,0 +1,220 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use crate::common::Money;
+
+// interest is carried in millionths of a cent between accruals
+const FRACTION_SCALE : i128 = 1_000_000;
+
+/* How days between two dates count towards a year of interest */
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DayCount {
+    // actual days, 365 days a year, leap years too
+    #[default]
+    Actual365,
+    Actual360,
+    // every month has 30 days, the 31st counts as the 30th
+    Thirty360,
+}
+
+impl DayCount {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DayCount::Actual365 => "actual/365",
+            DayCount::Actual360 => "actual/360",
+            DayCount::Thirty360 => "30/360",
+        }
+    }
+
+    pub fn days(&self, from : NaiveDate, to : NaiveDate) -> i64 {
+        match self {
+            DayCount::Actual365 | DayCount::Actual360 => (to - from).num_days(),
+            DayCount::Thirty360 => {
+                let day = |date : NaiveDate| date.day().min(30) as i64;
+                360 * (to.year() - from.year()) as i64
+                    + 30 * (to.month() as i64 - from.month() as i64)
+                    + day(to) - day(from)
+            }
+        }
+    }
+
+    pub fn year_days(&self) -> i64 {
+        match self {
+            DayCount::Actual365 => 365,
+            DayCount::Actual360 | DayCount::Thirty360 => 360,
+        }
+    }
+}
+
+impl std::fmt::Display for DayCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/* How often accrued interest is added to a deposit */
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Capitalization {
+    Daily,
+    // on the day of the month the deposit was opened
+    #[default]
+    Monthly,
+    Quarterly,
+    AtMaturity,
+}
+
+impl Capitalization {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capitalization::Daily => "daily",
+            Capitalization::Monthly => "monthly",
+            Capitalization::Quarterly => "quarterly",
+            Capitalization::AtMaturity => "at maturity",
+        }
+    }
+
+    /* Whether interest is added on `date` of a deposit opened on `start` and ending on `end` */
+    pub fn is_due(&self, start : &DateTime<Utc>, end : &DateTime<Utc>, date : &DateTime<Utc>) -> bool {
+        let every = match self {
+            Capitalization::Daily => return true,
+            Capitalization::Monthly => 1,
+            Capitalization::Quarterly => 3,
+            Capitalization::AtMaturity => return date >= end,
+        };
+        if date >= end {
+            return true;
+        }
+        let months = (date.year() - start.year()) * 12 + date.month() as i32 - start.month() as i32;
+        months > 0
+            && months % every == 0
+            // the 31st is paid on the last day of shorter months
+            && (*start + chrono::Months::new(months as u32)).date_naive() == date.date_naive()
+    }
+}
+
+impl std::fmt::Display for Capitalization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/* Interest accrued day by day and not paid out yet.
+ * Shared by deposits and credits. */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InterestAccrual {
+    pub day_count : DayCount,
+    pub accrued : Money,
+    // millionths of a cent below `accrued`
+    pub fraction : i64,
+    pub last_accrual : DateTime<Utc>,
+}
+
+impl InterestAccrual {
+    pub fn new(day_count : DayCount, start : DateTime<Utc>) -> Self {
+        InterestAccrual {
+            day_count,
+            accrued : Money(0),
+            fraction : 0,
+            last_accrual : start,
+        }
+    }
+
+    pub fn next_accrual(&self) -> DateTime<Utc> {
+        self.last_accrual + chrono::Days::new(1)
+    }
+
+    /* Interest on `principal` at an annual `rate` in percents from the last accrual to `to` */
+    pub fn accrue(&mut self, principal : Money, rate : u8, to : DateTime<Utc>) {
+        if to <= self.last_accrual {
+            return;
+        }
+        let days = self.day_count.days(self.last_accrual.date_naive(), to.date_naive()).max(0);
+        let interest = *principal as i128 * rate as i128 * days as i128 * FRACTION_SCALE
+            / (100 * self.day_count.year_days() as i128)
+            + self.fraction as i128;
+        self.accrued += Money((interest / FRACTION_SCALE) as i32);
+        self.fraction = (interest % FRACTION_SCALE) as i64;
+        self.last_accrual = to;
+    }
+
+    /* Whole cents accrued so far, the fraction stays for later */
+    pub fn take(&mut self) -> Money {
+        std::mem::take(&mut self.accrued)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn date(year : i32, month : u32, day : u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn time(year : i32, month : u32, day : u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn day_counts() {
+        assert_eq!(DayCount::Actual365.days(date(2023, 1, 15), date(2023, 3, 15)), 59);
+        assert_eq!(DayCount::Actual360.days(date(2024, 1, 15), date(2024, 3, 15)), 60);
+        assert_eq!(DayCount::Thirty360.days(date(2023, 1, 15), date(2023, 3, 15)), 60);
+        // the 31st counts as the 30th
+        assert_eq!(DayCount::Thirty360.days(date(2023, 1, 31), date(2023, 2, 28)), 28);
+        assert_eq!(DayCount::Thirty360.days(date(2023, 1, 30), date(2023, 3, 31)), 60);
+        assert_eq!(DayCount::Thirty360.days(date(2023, 12, 1), date(2024, 1, 1)), 30);
+    }
+
+    #[test]
+    fn accrues_a_year() {
+        let mut accrual = InterestAccrual::new(DayCount::Actual365, time(2023, 1, 1));
+        accrual.accrue(Money(1000), 5, time(2024, 1, 1));
+        assert_eq!(*accrual.accrued, 50);
+        assert_eq!(accrual.fraction, 0);
+
+        let mut accrual = InterestAccrual::new(DayCount::Actual360, time(2023, 1, 1));
+        accrual.accrue(Money(3600), 10, time(2023, 1, 31));
+        assert_eq!(*accrual.accrued, 30);
+    }
+
+    #[test]
+    fn carries_fractions() {
+        let mut accrual = InterestAccrual::new(DayCount::Actual365, time(2023, 1, 1));
+        // 1.369863... a time
+        accrual.accrue(Money(1000), 5, time(2023, 1, 11));
+        assert_eq!(*accrual.accrued, 1);
+        assert_eq!(accrual.fraction, 369_863);
+        accrual.accrue(Money(1000), 5, time(2023, 1, 21));
+        assert_eq!(*accrual.accrued, 2);
+        assert_eq!(accrual.fraction, 739_726);
+        assert_eq!(*accrual.take(), 2);
+        assert_eq!(*accrual.accrued, 0);
+        accrual.accrue(Money(1000), 5, time(2023, 1, 31));
+        assert_eq!(*accrual.accrued, 2);
+        assert_eq!(accrual.fraction, 109_589);
+    }
+
+    #[test]
+    fn never_accrues_backwards() {
+        let mut accrual = InterestAccrual::new(DayCount::Actual365, time(2023, 1, 10));
+        accrual.accrue(Money(1000), 5, time(2023, 1, 1));
+        assert_eq!(*accrual.accrued, 0);
+        assert_eq!(accrual.last_accrual, time(2023, 1, 10));
+    }
+
+    #[test]
+    fn capitalization_dates() {
+        let start = time(2023, 1, 31);
+        let end = time(2024, 1, 31);
+        assert!(Capitalization::Monthly.is_due(&start, &end, &time(2023, 2, 28)));
+        assert!(!Capitalization::Monthly.is_due(&start, &end, &time(2023, 2, 27)));
+        assert!(!Capitalization::Monthly.is_due(&start, &end, &start));
+        assert!(Capitalization::Quarterly.is_due(&start, &end, &time(2023, 4, 30)));
+        assert!(!Capitalization::Quarterly.is_due(&start, &end, &time(2023, 3, 31)));
+        assert!(!Capitalization::AtMaturity.is_due(&start, &end, &time(2023, 12, 31)));
+        assert!(Capitalization::AtMaturity.is_due(&start, &end, &end));
+    }
+}
@@ -2,7 +2,7 @@ use std::ops::{Deref, Rem, Div, Sub, Add, AddAssign, SubAssign, Mul};
 use serde::{Serialize, Deserialize};
 use std::fmt::Display;
 
-#[derive(Clone, Copy, Serialize,Deserialize, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Default, Serialize,Deserialize, PartialEq, PartialOrd)]
 pub struct Money (pub i32);
 
 impl Deref for Money {
@@ -61,7 +61,8 @@ impl Sub<i32> for Money{
 
 impl Display for Money {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{:02}", *self/100, *self%100)
+        let sign = if self.0 < 0 { "-" } else { "" };
+        write!(f, "{}{}.{:02}", sign, (self.0 / 100).abs(), (self.0 % 100).abs())
     }
 }
 
@@ -73,5 +74,25 @@ pub mod transaction;
 pub mod validate;
 pub mod account;
 pub mod credit;
+pub mod health;
+pub mod interest;
 pub mod time;
 pub mod salary;
+pub mod permission;
+pub mod audit;
+pub mod error;
+pub mod payment;
+pub mod standing_order;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn money_displays_cents() {
+        assert_eq!(Money(1334).to_string(), "13.34");
+        assert_eq!(Money(5).to_string(), "0.05");
+        assert_eq!(Money(-1205).to_string(), "-12.05");
+        assert_eq!(Money(-5).to_string(), "-0.05");
+    }
+}
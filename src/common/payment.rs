@@ -0,0 +1,56 @@
+use crate::common::bank::AccountID;
+use crate::common::transaction::TransactionEndPoint;
+use crate::common::Money;
+use serde::{Serialize, Deserialize};
+
+/* One payment of a bulk batch, `line` is the CSV line or the number of pain.001 transaction */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaymentLine {
+    pub line : usize,
+    // account of the payer in the selected bank
+    pub src_account : AccountID,
+    pub dst : TransactionEndPoint,
+    pub amount : Money,
+    pub reference : String
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchMode {
+    // either every payment is executed or none
+    Atomic,
+    // valid payments are executed, invalid ones are reported
+    Partial
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentStatus {
+    Executed,
+    Failed,
+    // valid, but not executed because other lines of an atomic batch failed
+    Skipped
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaymentLineResult {
+    pub line : usize,
+    pub reference : String,
+    pub status : PaymentStatus,
+    pub error : Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaymentBatchResp {
+    pub mode : BatchMode,
+    pub executed : usize,
+    pub failed : usize,
+    pub total : Money,
+    pub lines : Vec<PaymentLineResult>
+}
+
+impl std::fmt::Display for PaymentBatchResp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
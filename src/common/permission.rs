@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use crate::common::user::*;
+
+/* Every permission once, with the name it has in the API */
+macro_rules! permissions {
+    ($($variant:ident => $name:literal,)*) => {
+        #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum Permission {
+            $(
+                #[serde(rename = $name)]
+                $variant,
+            )*
+        }
+
+        impl Permission {
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Permission::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+permissions! {
+    AuthInfo => "auth.info",
+    TwoFactor => "auth.2fa",
+    PasswordChange => "auth.password",
+    PasswordReset => "auth.password.reset",
+    RegistrationReview => "registration.review",
+    RegistrationApprove => "registration.approve",
+    AuditRead => "audit.read",
+
+    EnterpriseSpecialists => "enterprise.specialists",
+
+    BankRead => "bank.read",
+    AccountRead => "account.read",
+    AccountOpen => "account.open",
+    AccountClose => "account.close",
+
+    DepositRead => "deposit.read",
+    DepositOpen => "deposit.open",
+    DepositWithdraw => "deposit.withdraw",
+
+    CreditRead => "credit.read",
+    CreditRequest => "credit.request",
+    CreditReview => "credit.review",
+    CreditApprove => "credit.approve",
+
+    TxCreate => "tx.create",
+    TxUnprotected => "tx.unprotected",
+    TxRead => "tx.read",
+    TxRevert => "tx.revert",
+    PaymentBatch => "payment.batch",
+
+    OrderRead => "order.read",
+    OrderManage => "order.manage",
+
+    TimeRead => "time.read",
+    TimeAdvance => "time.advance",
+
+    SalaryRequest => "salary.request",
+    SalaryManage => "salary.manage",
+    SalaryApprove => "salary.approve",
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+const CLIENT_PERMISSIONS: &[Permission] = &[
+    Permission::AuthInfo,
+    Permission::TwoFactor,
+    Permission::PasswordChange,
+    Permission::BankRead,
+    Permission::AccountRead,
+    Permission::AccountOpen,
+    Permission::AccountClose,
+    Permission::DepositRead,
+    Permission::DepositOpen,
+    Permission::DepositWithdraw,
+    Permission::CreditRead,
+    Permission::CreditRequest,
+    Permission::TxCreate,
+    Permission::OrderRead,
+    Permission::OrderManage,
+    Permission::SalaryRequest,
+];
+
+const ENTERPRISE_PERMISSIONS: &[Permission] = &[
+    Permission::AuthInfo,
+    Permission::TwoFactor,
+    Permission::PasswordChange,
+    Permission::BankRead,
+    Permission::AccountRead,
+    Permission::AccountOpen,
+    Permission::AccountClose,
+    Permission::TxCreate,
+    Permission::PaymentBatch,
+    Permission::OrderRead,
+    Permission::OrderManage,
+    Permission::SalaryManage,
+    Permission::EnterpriseSpecialists,
+];
+
+const MANAGER_PERMISSIONS: &[Permission] = &[
+    Permission::AuthInfo,
+    Permission::TwoFactor,
+    Permission::PasswordChange,
+    Permission::PasswordReset,
+    Permission::BankRead,
+    Permission::RegistrationReview,
+    Permission::RegistrationApprove,
+    Permission::CreditReview,
+    Permission::CreditApprove,
+    Permission::TxUnprotected,
+    Permission::TimeRead,
+    Permission::TimeAdvance,
+];
+
+const OPERATOR_PERMISSIONS: &[Permission] = &[
+    Permission::AuthInfo,
+    Permission::TwoFactor,
+    Permission::PasswordChange,
+    Permission::BankRead,
+    Permission::TxRead,
+    Permission::TxRevert,
+    Permission::SalaryApprove,
+];
+
+// administrator can do whatever staff can do
+const ADMINISTRATOR_PERMISSIONS: &[Permission] = &[
+    Permission::AuthInfo,
+    Permission::TwoFactor,
+    Permission::PasswordChange,
+    Permission::PasswordReset,
+    Permission::AuditRead,
+    Permission::BankRead,
+    Permission::RegistrationReview,
+    Permission::RegistrationApprove,
+    Permission::CreditReview,
+    Permission::CreditApprove,
+    Permission::TxUnprotected,
+    Permission::TxRead,
+    Permission::TxRevert,
+    Permission::TimeRead,
+    Permission::TimeAdvance,
+    Permission::SalaryApprove,
+];
+
+/* Permissions granted by a single role. `role` must have exactly one bit set. */
+pub fn role_permissions(role: UserType) -> &'static [Permission] {
+    match role {
+        CLIENT => CLIENT_PERMISSIONS,
+        OPERATOR => OPERATOR_PERMISSIONS,
+        MANAGER => MANAGER_PERMISSIONS,
+        ENTERPRISE => ENTERPRISE_PERMISSIONS,
+        ADMINISTRATOR => ADMINISTRATOR_PERMISSIONS,
+        _ => &[],
+    }
+}
+
+pub const ROLES: &[UserType] = &[CLIENT, OPERATOR, MANAGER, ENTERPRISE, ADMINISTRATOR];
+
+/* Union of permissions of every role the user holds. */
+pub fn permissions(user_type: UserType) -> Vec<Permission> {
+    let mut result: Vec<Permission> = Vec::new();
+    for role in ROLES.iter().filter(|role| user_type & **role != 0) {
+        for permission in role_permissions(*role) {
+            if !result.contains(permission) {
+                result.push(*permission);
+            }
+        }
+    }
+    result
+}
+
+pub fn has_permission(user_type: UserType, permission: Permission) -> bool {
+    ROLES
+        .iter()
+        .filter(|role| user_type & **role != 0)
+        .any(|role| role_permissions(*role).contains(&permission))
+}
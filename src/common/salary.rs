@@ -1,7 +1,9 @@
 use crate::common::auth::Login;
 use crate::common::transaction::TransactionEndPoint;
 use crate::common::Money;
+use crate::common::validate::{Validate, ValidationErrors};
 use serde::{Serialize, Deserialize};
+use chrono::Datelike;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SalaryClientRequest {
@@ -18,15 +20,37 @@ pub struct SalaryAcceptRequest {
 }
 
 
-impl ToString for SalaryClientRequest {
-    fn to_string(&self) -> String {
-        serde_yaml::to_string(&self).unwrap()
+impl std::fmt::Display for SalaryClientRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SalaryInitProjRequest {
-    pub account : TransactionEndPoint
+    pub account : TransactionEndPoint,
+    #[serde(default = "default_pay_day")]
+    pub pay_day : u32,
+    #[serde(default)]
+    pub policy : PayrollPolicy
+}
+
+fn default_pay_day() -> u32 {
+    DEFAULT_PAY_DAY
+}
+
+// day of month salary is paid on, unless the enterprise chose another one
+pub const DEFAULT_PAY_DAY : u32 = 25;
+
+/* What to do when the enterprise account can't cover the whole payroll */
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PayrollPolicy {
+    // nobody is paid until the money is there for everyone
+    #[default]
+    AllOrNothing,
+    // employees are paid in order while the money lasts
+    Partial
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -34,11 +58,142 @@ pub struct SalaryAcceptProjRequest {
     pub enterprise : Login 
 }
 
+/* Decline or suspension of a project by an operator */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SalaryProjReasonRequest {
+    pub enterprise : Login,
+    pub reason : String
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SalaryProjectStatus {
+    // set up by the enterprise, or declined by the bank and waiting for changes
+    Draft,
+    // sent to the bank holding the enterprise account
+    Pending,
+    Active,
+    // stopped by the bank, nothing is paid until it is resumed
+    Suspended,
+    // ended by the enterprise, a new project may be set up
+    Closed
+}
+
+impl SalaryProjectStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SalaryProjectStatus::Draft => "draft",
+            SalaryProjectStatus::Pending => "pending",
+            SalaryProjectStatus::Active => "active",
+            SalaryProjectStatus::Suspended => "suspended",
+            SalaryProjectStatus::Closed => "closed",
+        }
+    }
+}
+
+impl std::fmt::Display for SalaryProjectStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SalaryProjectStatusChange {
+    pub time : chrono::DateTime<chrono::Utc>,
+    pub status : SalaryProjectStatus,
+    // enterprise or operator, who made the change
+    pub by : Login,
+    // given when a project is declined or suspended
+    pub reason : Option<String>
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Employee {
     pub login: Login,
     pub account: TransactionEndPoint,
+    // salary at hire, later ones are in `salary_changes`
     pub salary: Money,
+    pub hired: chrono::DateTime<chrono::Utc>,
+    // ordered by effective date
+    pub salary_changes: Vec<SalaryChange>,
+    pub dismissed: Option<chrono::DateTime<chrono::Utc>>,
+    pub history: Vec<EmployeeHistoryEntry>,
+}
+
+impl Employee {
+    pub fn salary_on(&self, time : &chrono::DateTime<chrono::Utc>) -> Money {
+        self.salary_changes
+            .iter()
+            .rev()
+            .find(|change| change.effective <= *time)
+            .map(|change| change.salary)
+            .unwrap_or(self.salary)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.dismissed.is_none()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SalaryChange {
+    pub effective : chrono::DateTime<chrono::Utc>,
+    pub salary : Money
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EmployeeEvent {
+    Hired { salary : Money },
+    SalaryChanged { salary : Money, effective : chrono::DateTime<chrono::Utc> },
+    AccountChanged { account : TransactionEndPoint },
+    // by the enterprise
+    Dismissed { final_payment : Money },
+    // by the employee
+    Left { final_payment : Money }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EmployeeHistoryEntry {
+    pub time : chrono::DateTime<chrono::Utc>,
+    #[serde(flatten)]
+    pub event : EmployeeEvent
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EmployeeSalaryChangeRequest {
+    pub login : Login,
+    pub salary : Money,
+    pub effective : chrono::DateTime<chrono::Utc>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EmployeeAccountChangeRequest {
+    pub login : Login,
+    pub account : TransactionEndPoint
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EmployeeDismissRequest {
+    pub login : Login
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SalaryLeaveRequest {
+    pub enterprise : Login
+}
+
+/* Salary project as seen by one of its employees */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SalaryMembership {
+    pub enterprise : Login,
+    pub employee : Employee
+}
+
+impl std::fmt::Display for SalaryMembership {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -47,9 +202,377 @@ pub struct SalaryProject {
     pub enterprise_accoint: TransactionEndPoint,
     pub last_paid : Option<chrono::DateTime<chrono::Utc>>,
     pub created : chrono::DateTime<chrono::Utc>,
-    pub accepted : bool
+    pub status : SalaryProjectStatus,
+    pub status_history : Vec<SalaryProjectStatusChange>,
+    // 1..=31, clamped to the last day of short months
+    pub pay_day : u32,
+    pub policy : PayrollPolicy,
+    // latest period a payroll run was created for
+    pub last_period : Option<PayrollPeriod>,
+    #[serde(default)]
+    pub deductions : PayrollDeductions
+}
+
+/* Voluntary deduction of an employee, like a credit repayment, withheld from every salary */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VoluntaryDeduction {
+    pub login : Login,
+    pub description : String,
+    pub amount : Money,
+    pub account : TransactionEndPoint
+}
+
+/* Withholdings of a salary project, percentages are of the gross amount */
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PayrollDeductions {
+    pub income_tax_percent : u32,
+    pub tax_account : Option<TransactionEndPoint>,
+    pub social_fund_percent : u32,
+    pub fund_account : Option<TransactionEndPoint>,
+    #[serde(default)]
+    pub voluntary : Vec<VoluntaryDeduction>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DeductionKind {
+    IncomeTax,
+    SocialFund,
+    Voluntary
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayrollDeduction {
+    pub kind : DeductionKind,
+    pub description : String,
+    pub amount : Money,
+    pub account : TransactionEndPoint
+}
+
+impl PayrollDeductions {
+    /* Withholdings from `gross` paid to `login`. Voluntary deductions are taken
+     * from the regular salary only and never exceed what is left after taxes. */
+    pub fn withhold(&self, login : &Login, gross : Money, regular : bool) -> Vec<PayrollDeduction> {
+        let mut result = Vec::new();
+        let percent = |value : u32| Money((*gross as i64 * value as i64 / 100) as i32);
+        if let Some(account) = &self.tax_account {
+            result.push(PayrollDeduction {
+                kind : DeductionKind::IncomeTax,
+                description : format!("Income tax {}%", self.income_tax_percent),
+                amount : percent(self.income_tax_percent),
+                account : account.clone()
+            });
+        }
+        if let Some(account) = &self.fund_account {
+            result.push(PayrollDeduction {
+                kind : DeductionKind::SocialFund,
+                description : format!("Social fund {}%", self.social_fund_percent),
+                amount : percent(self.social_fund_percent),
+                account : account.clone()
+            });
+        }
+        if regular {
+            for deduction in self.voluntary.iter().filter(|deduction| deduction.login == *login) {
+                let left = *gross - result.iter().map(|deduction| *deduction.amount).sum::<i32>();
+                result.push(PayrollDeduction {
+                    kind : DeductionKind::Voluntary,
+                    description : deduction.description.clone(),
+                    amount : Money(left.min(*deduction.amount)),
+                    account : deduction.account.clone()
+                });
+            }
+        }
+        result.retain(|deduction| *deduction.amount > 0);
+        result
+    }
+}
+
+/* Calendar month salary is paid for */
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PayrollPeriod {
+    pub year : i32,
+    pub month : u32
+}
+
+impl PayrollPeriod {
+    pub fn of(time : &chrono::DateTime<chrono::Utc>) -> Self {
+        PayrollPeriod { year : time.year(), month : time.month() }
+    }
+
+    pub fn next(&self) -> Self {
+        if self.month == 12 {
+            PayrollPeriod { year : self.year + 1, month : 1 }
+        } else {
+            PayrollPeriod { year : self.year, month : self.month + 1 }
+        }
+    }
+
+    fn first_day(&self) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(self.year, self.month, 1).expect("Valid period")
+    }
+
+    pub fn days(&self) -> u32 {
+        (self.first_day() + chrono::Months::new(1) - chrono::Days::new(1)).day()
+    }
+
+    /* Part of the monthly `amount` earned in the first `days` days of the period */
+    pub fn prorate(&self, amount : Money, days : u32) -> Money {
+        Money((*amount as i64 * days.min(self.days()) as i64 / self.days() as i64) as i32)
+    }
+
+    /* Start of `pay_day` in this month, the last day if the month is shorter */
+    pub fn pay_date(&self, pay_day : u32) -> chrono::DateTime<chrono::Utc> {
+        self.first_day()
+            .with_day(pay_day.clamp(1, self.days()))
+            .expect("Day is clamped")
+            .and_hms_opt(0, 0, 0)
+            .expect("Midnight exists")
+            .and_utc()
+    }
+}
+
+impl std::str::FromStr for PayrollPeriod {
+    type Err = String;
+
+    /* `YYYY-MM`, as it is displayed */
+    fn from_str(value : &str) -> Result<Self, Self::Err> {
+        let (year, month) = value.split_once('-').ok_or("Period must look like 2024-01")?;
+        let year = year.parse().map_err(|_| "Invalid year".to_string())?;
+        let month = month.parse().map_err(|_| "Invalid month".to_string())?;
+        if !(1..=12).contains(&month) {
+            return Err("Invalid month".to_string());
+        }
+        Ok(PayrollPeriod { year, month })
+    }
+}
+
+impl std::fmt::Display for PayrollPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{:02}", self.year, self.month)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PayrollLineStatus {
+    Pending,
+    Paid
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayrollLine {
+    pub login : Login,
+    pub account : TransactionEndPoint,
+    pub amount : Money,
+    pub status : PayrollLineStatus,
+    // reason of the last failed attempt
+    pub error : Option<String>,
+    pub paid_at : Option<chrono::DateTime<chrono::Utc>>,
+    // one-off payout, paid along with the salary or on its own
+    #[serde(default)]
+    pub payout : Option<PayoutID>,
+    #[serde(default)]
+    pub description : Option<String>,
+    // withheld from `amount`, which is the gross one
+    #[serde(default)]
+    pub deductions : Vec<PayrollDeduction>
+}
+
+impl PayrollLine {
+    /* What the employee gets */
+    pub fn net(&self) -> Money {
+        Money(*self.amount - self.deductions.iter().map(|deduction| *deduction.amount).sum::<i32>())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PayrollRunStatus {
+    // nobody is paid yet, retried on every runner tick
+    Failed,
+    // some employees are paid, the rest are retried
+    Partial,
+    Completed
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PayrollRunKind {
+    // monthly salary, one per period
+    Regular,
+    // prorated pay of a dismissed employee
+    Final,
+    // one-off payouts paid as soon as they are approved
+    Payout
+}
+
+/* Payroll of one enterprise for one period. Lines are fixed when the run is created,
+ * paid lines are never paid again. */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayrollRun {
+    pub enterprise : Login,
+    pub kind : PayrollRunKind,
+    pub period : PayrollPeriod,
+    pub pay_date : chrono::DateTime<chrono::Utc>,
+    pub policy : PayrollPolicy,
+    pub attempts : u32,
+    pub last_attempt : Option<chrono::DateTime<chrono::Utc>>,
+    pub status : PayrollRunStatus,
+    pub lines : Vec<PayrollLine>
+}
+
+impl PayrollRun {
+    pub fn total_paid(&self) -> Money {
+        Money(self.lines.iter()
+            .filter(|line| line.status == PayrollLineStatus::Paid)
+            .map(|line| *line.amount)
+            .sum())
+    }
+}
+
+impl std::fmt::Display for PayrollRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+
+pub type PayoutID = u64;
+
+pub const MAX_PAYOUT_LINES : usize = 1000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutTiming {
+    // paid as soon as the bank approves the batch
+    #[default]
+    Immediate,
+    // paid with the salary of the next payroll run
+    NextRun
+}
+
+/* Bonus, reimbursement or another payment which is not part of the salary */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayoutLine {
+    pub login : Login,
+    pub amount : Money,
+    pub description : String
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayoutBatchRequest {
+    pub payouts : Vec<PayoutLine>,
+    #[serde(default)]
+    pub timing : PayoutTiming
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PayoutBatchResp {
+    pub id : PayoutID
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PayoutBatchStatus {
+    // waits for an operator of the bank
+    Pending,
+    Declined,
+    // waits for the next payroll run
+    Approved,
+    // lines are in a payroll run, see its history
+    Released
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayoutBatch {
+    pub id : PayoutID,
+    pub enterprise : Login,
+    pub timing : PayoutTiming,
+    pub status : PayoutBatchStatus,
+    pub payouts : Vec<PayoutLine>,
+    pub submitted : chrono::DateTime<chrono::Utc>,
+    // operator who approved or declined the batch
+    pub reviewed_by : Option<Login>,
+    pub reason : Option<String>,
+    // period of the payroll run the payouts were released to
+    pub period : Option<PayrollPeriod>
+}
+
+impl PayoutBatch {
+    pub fn total(&self) -> Money {
+        Money(self.payouts.iter().map(|payout| *payout.amount).sum())
+    }
+}
+
+impl std::fmt::Display for PayoutBatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayoutReviewRequest {
+    pub id : PayoutID,
+    pub accept : bool,
+    // required when the batch is declined
+    #[serde(default)]
+    pub reason : String
+}
+
+/* One payment of the period on a payslip */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PayslipEntry {
+    pub kind : PayrollRunKind,
+    pub description : String,
+    pub gross : Money,
+    pub deductions : Vec<PayrollDeduction>,
+    pub net : Money,
+    pub status : PayrollLineStatus,
+    pub paid_at : Option<chrono::DateTime<chrono::Utc>>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Payslip {
+    pub enterprise : Login,
+    pub login : Login,
+    pub period : PayrollPeriod,
+    pub entries : Vec<PayslipEntry>
 }
 
+impl Payslip {
+    pub fn gross(&self) -> Money {
+        Money(self.entries.iter().map(|entry| *entry.gross).sum())
+    }
+
+    pub fn net(&self) -> Money {
+        Money(self.entries.iter().map(|entry| *entry.net).sum())
+    }
+}
+
+/* Payslip as a printable document */
+impl std::fmt::Display for Payslip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "PAYSLIP {}", self.period)?;
+        writeln!(f, "Employer : {}", self.enterprise)?;
+        writeln!(f, "Employee : {}", self.login)?;
+        for entry in &self.entries {
+            writeln!(f)?;
+            writeln!(f, "{:<40} {:>10}", entry.description, *entry.gross)?;
+            for deduction in &entry.deductions {
+                writeln!(f, "  - {:<36} {:>10}", deduction.description, -*deduction.amount)?;
+            }
+            let status = match entry.paid_at {
+                Some(paid_at) => format!("paid {}", paid_at.format("%Y-%m-%d")),
+                None => "not paid yet".to_string(),
+            };
+            writeln!(f, "  {:<38} {:>10} ({})", "Net", *entry.net, status)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:<40} {:>10}", "Total gross", *self.gross())?;
+        writeln!(f, "{:<40} {:>10}", "Total withheld", *self.gross() - *self.net())?;
+        write!(f, "{:<40} {:>10}", "Total net", *self.net())
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SalaryProjectResp{
@@ -59,9 +582,187 @@ pub struct SalaryProjectResp{
 }
 
 
-impl ToString for SalaryProjectResp {
-    fn to_string(&self) -> String {
-        serde_yaml::to_string(&self).unwrap()
+impl std::fmt::Display for SalaryProjectResp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+impl Validate for SalaryClientRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.enterprise_name, "enterprise_name");
+        errors.check_not_empty(&self.client_login, "client_login");
+        errors.into_result()
+    }
+}
+
+impl Validate for SalaryAcceptRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        // salary is ignored when the request is declined
+        errors.check(!self.accept || *self.salary > 0, "salary", "Must be positive");
+        errors.into_result()
+    }
+}
+
+impl Validate for SalaryInitProjRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check((1..=31).contains(&self.pay_day), "pay_day", "Must be from 1 to 31");
+        errors.into_result()
+    }
+}
+
+impl Validate for EmployeeSalaryChangeRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.login, "login");
+        errors.check(*self.salary > 0, "salary", "Must be positive");
+        errors.into_result()
+    }
+}
+
+impl Validate for EmployeeAccountChangeRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.login, "login");
+        errors.into_result()
+    }
+}
+
+impl Validate for EmployeeDismissRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.login, "login");
+        errors.into_result()
+    }
+}
+
+impl Validate for SalaryLeaveRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.enterprise, "enterprise");
+        errors.into_result()
+    }
+}
+
+impl Validate for PayrollDeductions {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(self.income_tax_percent <= 100, "income_tax_percent", "Must be from 0 to 100");
+        errors.check(self.social_fund_percent <= 100, "social_fund_percent", "Must be from 0 to 100");
+        errors.check(
+            self.income_tax_percent
+                .checked_add(self.social_fund_percent)
+                .is_some_and(|total| total <= 100),
+            "social_fund_percent",
+            "Withholdings must not exceed 100% together",
+        );
+        errors.check(
+            self.income_tax_percent == 0 || self.tax_account.is_some(),
+            "tax_account",
+            "Required for income tax",
+        );
+        errors.check(
+            self.social_fund_percent == 0 || self.fund_account.is_some(),
+            "fund_account",
+            "Required for social fund",
+        );
+        for (idx, deduction) in self.voluntary.iter().enumerate() {
+            errors.check_not_empty(&deduction.login, &format!("voluntary[{}].login", idx));
+            errors.check_not_empty(&deduction.description, &format!("voluntary[{}].description", idx));
+            errors.check(*deduction.amount > 0, &format!("voluntary[{}].amount", idx), "Must be positive");
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for PayoutBatchRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(!self.payouts.is_empty(), "payouts", "Must not be empty");
+        errors.check(
+            self.payouts.len() <= MAX_PAYOUT_LINES,
+            "payouts",
+            &format!("At most {} payouts per batch", MAX_PAYOUT_LINES),
+        );
+        for (idx, payout) in self.payouts.iter().enumerate() {
+            errors.check_not_empty(&payout.login, &format!("payouts[{}].login", idx));
+            errors.check(*payout.amount > 0, &format!("payouts[{}].amount", idx), "Must be positive");
+            errors.check_not_empty(&payout.description, &format!("payouts[{}].description", idx));
+            errors.check(
+                payout.description.len() <= 140,
+                &format!("payouts[{}].description", idx),
+                "Must be at most 140 characters",
+            );
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for PayoutReviewRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if !self.accept {
+            errors.check_not_empty(&self.reason, "reason");
+        }
+        errors.check(self.reason.len() <= 500, "reason", "Must be at most 500 characters");
+        errors.into_result()
     }
 }
 
+impl Validate for SalaryProjReasonRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.enterprise, "enterprise");
+        errors.check_not_empty(&self.reason, "reason");
+        errors.check(self.reason.len() <= 500, "reason", "Must be at most 500 characters");
+        errors.into_result()
+    }
+}
+
+impl Validate for SalaryAcceptProjRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.enterprise, "enterprise");
+        errors.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prorates_by_days_of_the_month() {
+        let february = PayrollPeriod { year : 2024, month : 2 };
+        assert_eq!(february.days(), 29);
+        assert_eq!(*february.prorate(Money(2900), 10), 1000);
+        assert_eq!(*february.prorate(Money(2900), 29), 2900);
+        // days past the end of the month do not add up
+        assert_eq!(*february.prorate(Money(2900), 31), 2900);
+
+        let april = PayrollPeriod { year : 2023, month : 4 };
+        assert_eq!(*april.prorate(Money(1000), 1), 33);
+        assert_eq!(*april.prorate(Money(1000), 0), 0);
+    }
+
+    #[test]
+    fn withholdings_do_not_overflow() {
+        let deductions = PayrollDeductions {
+            income_tax_percent : u32::MAX,
+            social_fund_percent : u32::MAX,
+            ..Default::default()
+        };
+        let fields : Vec<String> = deductions
+            .validate()
+            .unwrap_err()
+            .errors
+            .into_iter()
+            .map(|err| err.field)
+            .collect();
+        assert!(fields.contains(&"income_tax_percent".to_string()));
+        assert!(fields.contains(&"social_fund_percent".to_string()));
+    }
+}
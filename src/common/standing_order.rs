@@ -0,0 +1,130 @@
+use crate::common::auth::Login;
+use crate::common::bank::AccountID;
+use crate::common::transaction::TransactionEndPoint;
+use crate::common::validate::{Validate, ValidationErrors};
+use crate::common::Money;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub type OrderID = u64;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    // single transfer on the start date
+    Once,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Active,
+    // every occurrence up to the end date is done
+    Completed,
+    Cancelled,
+    // one-off order, which ran out of attempts
+    Failed,
+}
+
+/* One attempt to execute an occurrence, `error` is `None` for a successful one */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OrderExecution {
+    pub due : DateTime<Utc>,
+    pub time : DateTime<Utc>,
+    pub attempt : u32,
+    pub error : Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StandingOrder {
+    pub id : OrderID,
+    pub owner : Login,
+    pub src : TransactionEndPoint,
+    pub dst : TransactionEndPoint,
+    pub amount : Money,
+    pub frequency : Frequency,
+    pub reference : String,
+    pub start : DateTime<Utc>,
+    pub end : Option<DateTime<Utc>>,
+    // number of the next occurrence, counting from 0 at `start`
+    pub occurrence : u32,
+    // failed attempts of the next occurrence
+    pub attempts : u32,
+    pub retry_at : Option<DateTime<Utc>>,
+    pub status : OrderStatus,
+    pub history : Vec<OrderExecution>,
+}
+
+impl StandingOrder {
+    /* Due date of the n-th occurrence. Monthly ones are counted from the start,
+     * so that Jan 31 is followed by Feb 28 and then Mar 31. */
+    pub fn due_date(&self, occurrence : u32) -> DateTime<Utc> {
+        match self.frequency {
+            Frequency::Once => self.start,
+            Frequency::Weekly => self.start + chrono::Days::new(7 * occurrence as u64),
+            Frequency::Monthly => self.start + chrono::Months::new(occurrence),
+        }
+    }
+
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        if self.status != OrderStatus::Active {
+            return None;
+        }
+        Some(self.due_date(self.occurrence))
+    }
+
+    /* Time of the next attempt, a retry if the last one failed */
+    pub fn next_attempt(&self) -> Option<DateTime<Utc>> {
+        let due = self.next_due()?;
+        Some(self.retry_at.unwrap_or(due))
+    }
+}
+
+impl std::fmt::Display for StandingOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StandingOrderNewRequest {
+    // account of the owner in the selected bank
+    pub src_account : AccountID,
+    pub dst : TransactionEndPoint,
+    pub amount : Money,
+    pub frequency : Frequency,
+    pub start : DateTime<Utc>,
+    pub end : Option<DateTime<Utc>>,
+    pub reference : String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StandingOrderNewResp {
+    pub id : OrderID,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StandingOrderCancelRequest {
+    pub id : OrderID,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StandingOrdersGetResp {
+    pub orders : Vec<StandingOrder>,
+}
+
+impl Validate for StandingOrderNewRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(*self.amount > 0, "amount", "Must be positive");
+        errors.check(self.reference.len() <= 140, "reference", "Must be at most 140 characters");
+        if let Some(end) = self.end {
+            errors.check(end >= self.start, "end", "Must not be before the start");
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for StandingOrderCancelRequest {}
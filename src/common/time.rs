@@ -1,8 +1,122 @@
 use serde::{Deserialize, Serialize};
+use crate::common::auth::Login;
+use crate::common::bank::BIK;
+use crate::common::standing_order::OrderID;
+use crate::common::transaction::LedgerEntry;
+use crate::common::validate::{Validate, ValidationErrors};
 
 #[derive(Deserialize, Serialize)]
 pub struct TimeAdvanceReq {
     pub time : chrono::DateTime<chrono::Utc>
 }
 
+impl Validate for TimeAdvanceReq {}
 
+/* Moves the clock forward, the runner goes through every month on the way */
+#[derive(Deserialize, Serialize)]
+pub struct TimeStepReq {
+    #[serde(default)]
+    pub days : u32,
+    #[serde(default)]
+    pub months : u32
+}
+
+impl Validate for TimeStepReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(self.days > 0 || self.months > 0, "days", "Step must not be empty");
+        errors.check(self.days <= 3660, "days", "Must be at most 3660");
+        errors.check(self.months <= 120, "months", "Must be at most 120");
+        errors.into_result()
+    }
+}
+
+pub type SnapshotID = u64;
+
+/* State of the banks saved at the virtual time `time` */
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TimeSnapshot {
+    pub id : SnapshotID,
+    pub time : chrono::DateTime<chrono::Utc>
+}
+
+/* Puts the banks back as they were in the snapshot, time goes back with them */
+#[derive(Deserialize, Serialize)]
+pub struct TimeRestoreReq {
+    pub snapshot : SnapshotID
+}
+
+impl Validate for TimeRestoreReq {}
+
+impl std::fmt::Display for TimeSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{} {}", self.id, self.time.format("%Y-%m-%d %H:%M"))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TimeStatus {
+    pub time : chrono::DateTime<chrono::Utc>,
+    pub paused : bool
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeEventKind {
+    Paused,
+    Resumed,
+    // clock is set with `/time/advance`
+    Advanced,
+    // one stop of `/time/step`
+    Step,
+    // dynamic update made by the runner
+    Update,
+    Snapshot,
+    // time went back to a snapshot
+    Restored
+}
+
+/* What happened to the simulation clock, `time` is the virtual one */
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TimeEvent {
+    pub id : u64,
+    pub time : chrono::DateTime<chrono::Utc>,
+    pub kind : TimeEventKind,
+    pub message : String,
+    // booked while the runner was updating
+    pub transactions : Vec<LedgerEntry>
+}
+
+impl std::fmt::Display for TimeEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+/* Something the runner has to do at a given virtual time.
+ * Deposits and credits are found by owner and position in the bank. */
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ScheduledEventKind {
+    DepositAccrual { bik : BIK, owner : Login, idx : usize },
+    // accrues before an installment due at the same time
+    CreditAccrual { bik : BIK, owner : Login, idx : usize },
+    CreditInstallment { bik : BIK, owner : Login, idx : usize },
+    // opens the runs of the enterprise which are due and retries unpaid ones
+    Payroll { enterprise : Login },
+    StandingOrder { id : OrderID },
+}
+
+/* Ordered by due time first */
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct ScheduledEvent {
+    pub due : chrono::DateTime<chrono::Utc>,
+    #[serde(flatten)]
+    pub kind : ScheduledEventKind
+}
+
+impl std::fmt::Display for ScheduledEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
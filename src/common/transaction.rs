@@ -1,8 +1,10 @@
 use crate::common::bank::{AccountID, BIK};
 use crate::common::Money;
+use crate::common::validate::{Validate, ValidationErrors};
 use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize, PartialEq)]
 pub struct TransactionEndPoint {
     pub bik : BIK,
     pub account_id : AccountID
@@ -30,5 +32,45 @@ impl Transaction {
             amount: self.amount
         }
     }
+
+    /* Whether either end of the transaction is in the bank `bik` */
+    pub fn involves(&self, bik : BIK) -> bool {
+        self.src.bik == bik || self.dst.bik == bik
+    }
 }
 
+/* Transaction as it was booked. Ids grow by one, starting from 1. */
+#[derive(Clone, Deserialize, Serialize)]
+pub struct LedgerEntry {
+    pub id : u64,
+    pub time : DateTime<Utc>,
+    pub transaction : Transaction,
+    pub description : String
+}
+
+impl LedgerEntry {
+    /* Change of the account balance made by this entry, zero if the account is not involved */
+    pub fn effect_on(&self, account : &TransactionEndPoint) -> i32 {
+        let mut effect = 0;
+        if self.transaction.dst == *account {
+            effect += *self.transaction.amount;
+        }
+        if self.transaction.src == *account {
+            effect -= *self.transaction.amount;
+        }
+        effect
+    }
+}
+
+impl Validate for Transaction {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(*self.amount > 0, "amount", "Must be positive");
+        errors.check(
+            self.src.bik != self.dst.bik || self.src.account_id != self.dst.account_id,
+            "dst",
+            "Must differ from source account",
+        );
+        errors.into_result()
+    }
+}
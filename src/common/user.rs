@@ -1,7 +1,7 @@
 
 use std::string::String;
 use serde::{Serialize, Deserialize};
-use crate::common::validate::Validate;
+use crate::common::validate::{is_valid_email, Validate, ValidationErrors};
 use std::str;
 
 
@@ -20,19 +20,12 @@ pub struct UserPersonalName{
 }
 
 impl Validate for UserPersonalName {
-    fn validate(&self) -> Result<(), &str> {
-        if self.first_name.is_empty(){
-            Err("First name is empty")
-        }
-        else if self.middle_name.is_empty(){
-            Err("Middle name is empty")
-        }
-        else if self.last_name.is_empty(){
-            Err("Last name is empty")
-        }
-        else {
-            Ok(())
-        }
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.first_name, "first_name");
+        errors.check_not_empty(&self.middle_name, "middle_name");
+        errors.check_not_empty(&self.last_name, "last_name");
+        errors.into_result()
     }
 }
 
@@ -47,15 +40,26 @@ pub struct UserPassportData{
 
 impl Validate for UserPassportData
 {
-    fn validate(&self) -> Result<(), &str> {
-        if self.series.len() != 2 || !self.series.chars().all(|c:_|c.is_alphabetic()){
-            Err("Wrong passport series")
-        } else if self.number.len() != 7 || !self.series.chars().all(|c:_|c.is_digit(10)){
-            Err("Wrong passport number")
-        } else {
-            Ok(())
-        }
-        //TODO: check id number
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check(
+            self.series.len() == 2 && self.series.chars().all(|c:_|c.is_ascii_alphabetic()),
+            "series",
+            "Must be 2 letters",
+        );
+        errors.check(
+            self.number.len() == 7 && self.number.chars().all(|c:_|c.is_ascii_digit()),
+            "number",
+            "Must be 7 digits",
+        );
+        // 7 digits, region letter, 3 digits, 2 letters, check digit
+        let re_id_number = regex::Regex::new(r#"^[0-9]{7}[A-Z][0-9]{3}[A-Z]{2}[0-9]$"#).unwrap();
+        errors.check(
+            re_id_number.is_match(&self.id_number.to_uppercase()),
+            "id_number",
+            "Must look like 1234567A123PB1",
+        );
+        errors.into_result()
     }
 }
 
@@ -69,15 +73,14 @@ pub struct Client{
 }
 
 impl Validate for Client{
-    fn validate(&self) -> Result<(), &str> {
-        self.full_name.validate()?;
-        self.passport.validate()?;
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.nested("full_name", &self.full_name);
+        errors.nested("passport", &self.passport);
         let re_phone_number = regex::Regex::new(r#"^[\+]?[(]?[0-9]{3}[)]?[-\s\.]?[0-9]{3}[-\s\.]?[0-9]{4,6}$"#).unwrap();
-        if re_phone_number.is_match(self.phone_number.as_str()){
-            Err("Invalid phone number")
-        } else {
-            Ok(())
-        }
+        errors.check(re_phone_number.is_match(self.phone_number.as_str()), "phone_number", "Invalid phone number");
+        errors.check(is_valid_email(&self.email), "email", "Invalid email");
+        errors.into_result()
     }
 }
 
@@ -90,14 +93,48 @@ pub struct Enterprise{
     pub address : String
 }
 
+// weights of the first 8 digits of UNP, the 9th one is the check digit
+const UNP_WEIGHTS : [u32; 8] = [29, 23, 19, 17, 13, 7, 5, 3];
+
+/* UNP (payer's account number) of a legal entity : 9 digits with checksum */
+pub fn is_valid_unp(unp: &str) -> bool {
+    let digits : Vec<u32> = unp.chars().filter_map(|c:_| c.to_digit(10)).collect();
+    if unp.len() != 9 || digits.len() != 9 {
+        return false;
+    }
+    let checksum = UNP_WEIGHTS.iter().zip(&digits).map(|(w, d)| w * d).sum::<u32>() % 11;
+    checksum != 10 && checksum == digits[8]
+}
+
+impl Validate for Enterprise {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        errors.check_not_empty(&self.enterprise_type, "enterprise_type");
+        errors.check_not_empty(&self.name, "name");
+        errors.check(is_valid_unp(&self.unp), "unp", "Invalid UNP");
+        errors.check_not_empty(&self.address, "address");
+        errors.into_result()
+    }
+}
+
+/* Person acting on behalf of an enterprise. One enterprise may have several. */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Specialist{
+    // name of the enterprise, it owns the accounts and salary project
+    pub enterprise : String,
+    pub full_name : UserPersonalName,
+    pub position : String
+}
+
 pub type UserID = u64;
 
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum UserData {
     None,
     EnterpriseData(Enterprise),
-    ClientData(Client)
+    ClientData(Client),
+    SpecialistData(Specialist)
 }
 
 
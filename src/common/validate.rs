@@ -1,6 +1,83 @@
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    // dotted path to the field, e.g. `user_data.passport.number`
+    pub field : String,
+    pub message : String,
+}
+
+/* Every problem found in a request, one entry per offending field */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ValidationErrors {
+    pub errors : Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        ValidationErrors { errors: Vec::new() }
+    }
+
+    pub fn add(&mut self, field: &str, message: &str) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    /* Adds the error unless `condition` holds */
+    pub fn check(&mut self, condition: bool, field: &str, message: &str) {
+        if !condition {
+            self.add(field, message);
+        }
+    }
+
+    pub fn check_not_empty(&mut self, value: &str, field: &str) {
+        self.check(!value.trim().is_empty(), field, "Must not be empty");
+    }
 
+    /* Validates a nested struct, its fields are reported as `prefix.field` */
+    pub fn nested<T: Validate>(&mut self, prefix: &str, value: &T) {
+        if let Err(nested) = value.validate() {
+            for err in nested.errors {
+                self.errors.push(FieldError {
+                    field: format!("{}.{}", prefix, err.field),
+                    message: err.message,
+                });
+            }
+        }
+    }
 
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for err in &self.errors {
+            writeln!(f, "{} : {}", err.field, err.message)?;
+        }
+        Ok(())
+    }
+}
+
+/* Requests without any constraints keep the default implementation */
 pub trait Validate {
-    fn validate(&self) -> Result<(), &str>;
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
+}
+
+pub fn is_valid_email(email: &str) -> bool {
+    let re_email = regex::Regex::new(r#"^[A-Za-z0-9._%+\-]+@[A-Za-z0-9\-]+(\.[A-Za-z0-9\-]+)*\.[A-Za-z]{2,}$"#).unwrap();
+    re_email.is_match(email)
 }
@@ -1,2 +1 @@
-use l1::common::Money;
 
@@ -1,14 +1,16 @@
 use crate::services::deposit::DepositService;
 use crate::services::credit::CreditService;
-use crate::traits::dynamic::Dynamic;
+use crate::server::ServerError;
 use l1::common::auth::Login;
 use l1::common::bank::*;
 use l1::common::Money;
+use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
 
 
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Bank {
     pub deposit_service: DepositService,
     pub credit_service : CreditService,
@@ -28,23 +30,23 @@ impl Bank {
         }
     }
 
-    fn add_client_if_not_exist(&mut self, login: &Login) {
+    pub fn add_client_if_not_exist(&mut self, login: &Login) {
         if !self.clients.contains_key(login) {
             self.add_client(login);
         }
     }
 
-    pub fn add_client(&mut self, client: &String) {
-        self.clients.insert(client.clone(), Vec::new());
-        self.deposit_service.add_client(client.clone());
-        self.credit_service.add_client(client.clone());
+    pub fn add_client(&mut self, client: &str) {
+        self.clients.insert(client.to_string(), Vec::new());
+        self.deposit_service.add_client(client.to_string());
+        self.credit_service.add_client(client.to_string());
     }
 
-    pub fn validate_account_identity(&self, acc: AccountID, login: &Login) -> Result<(), &str> {
+    pub fn validate_account_identity(&self, acc: AccountID, login: &Login) -> Result<(), ServerError> {
         let maybe_acc_id = self
             .clients
             .get(login)
-            .ok_or("Client not found. Open at least one ACCOUNT in the bank")?
+            .ok_or(ServerError::NotFound("Client not found. Open at least one ACCOUNT in the bank".to_string()))?
             .iter()
             .find(|v: _| **v == acc);
         if let Some(acc_id) = maybe_acc_id {
@@ -53,24 +55,34 @@ impl Bank {
                 .get(acc_id)
                 .expect("Accounts are not synced with clients");
             match acc.status {
-                AccountStatus::Frozen => Err("Account frozen"),
-                AccountStatus::Blocked => Err("Account blocked"),
+                AccountStatus::Frozen => Err(ServerError::Forbidden("Account frozen".to_string())),
+                AccountStatus::Blocked => Err(ServerError::Forbidden("Account blocked".to_string())),
                 AccountStatus::Normal => Ok(()),
             }
         } else {
-            Err("Account not found")
+            // accounts of other users are reported the same way
+            Err(ServerError::NotFound("Account not found".to_string()))
         }
     }
 
-    pub fn account_new(&mut self, login: &Login) -> Result<AccountID, &str> {
+    /* Unlike `validate_account_identity`, frozen and blocked accounts count too */
+    pub fn owns_account(&self, login: &Login, acc: AccountID) -> bool {
+        self.clients
+            .get(login)
+            .is_some_and(|accounts| accounts.contains(&acc))
+    }
+
+    pub fn account_new(&mut self, login: &Login) -> Result<AccountID, ServerError> {
         self.add_client_if_not_exist(login);
-        let client_accounts = self.clients.get_mut(login).ok_or("Client not found")?;
+        let client_accounts = self
+            .clients
+            .get_mut(login)
+            .ok_or(ServerError::NotFound("Client not found".to_string()))?;
         let new_acc_id = (self.accounts.len() + 1) as u64;
 
-        // TODO : remove 1334
         let new_acc = Account {
-            balance: Money(1334),
-            id: new_acc_id as u64,
+            balance: Money(0),
+            id: new_acc_id,
             status: AccountStatus::Normal,
         };
         let mb_old_acc = self.accounts.insert(new_acc_id, new_acc);
@@ -82,16 +94,17 @@ impl Bank {
         Ok(new_acc_id)
     }
 
-    pub fn account_close(&mut self, login: &Login, id: AccountID) -> Result<(), &str> {
-        self.validate_account_identity(id, login)
-            .map_err(|_| "Account does not exist")?;
+    pub fn account_close(&mut self, login: &Login, id: AccountID) -> Result<(), ServerError> {
+        self.validate_account_identity(id, login)?;
         let client_accounts = self
             .clients
             .get_mut(login)
             .expect("Account identidy validation did't work?");
         // TODO : check for deposits and credits open
 
-        self.accounts.remove(&id).ok_or("Invalid account ID")?;
+        self.accounts
+            .remove(&id)
+            .ok_or(ServerError::NotFound("Invalid account ID".to_string()))?;
 
         let remove_pos = client_accounts
             .iter()
@@ -101,9 +114,12 @@ impl Bank {
         Ok(())
     }
 
-    pub fn accounts_get(&self, login: &Login) -> Result<Vec<Account>, &str> {
-        let client_accounts = self.clients.get(login).ok_or("Client not found")?;
-        Ok(client_accounts
+    /* Accounts of the client in this bank, empty if there are none */
+    pub fn accounts_get(&self, login: &Login) -> Vec<Account> {
+        let Some(client_accounts) = self.clients.get(login) else {
+            return Vec::new();
+        };
+        client_accounts
             .iter()
             .map(|acc_id| {
                 self.accounts
@@ -111,7 +127,7 @@ impl Bank {
                     .expect("Client has unexisting account!")
                     .clone()
             })
-            .collect())
+            .collect()
     }
 
     //     /* Performs account replenishment without checking authentification.*/
@@ -145,9 +161,3 @@ impl Bank {
     //         }
     //     }
 }
-
-impl Dynamic for Bank {
-    fn update(&mut self, time: &chrono::DateTime<chrono::Utc>) {
-        self.deposit_service.update(time);
-    }
-}
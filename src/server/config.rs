@@ -0,0 +1,186 @@
+/*
+ * Server settings. Read from the YAML file given by `--config`,
+ * then overridden by `BANK_*` environment variables, then by flags.
+ * Every field is optional, missing ones keep the defaults below.
+ */
+
+use l1::common::bank::BankPublicInfo;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub listen: String,
+    // where `StorageService` keeps its files
+    pub data_dir: PathBuf,
+    // seconds between dynamic updates of deposits, credits and salaries
+    pub runner_interval: u64,
+    // seconds between storage dumps
+    pub storage_interval: u64,
+    // `env_logger` filter, `RUST_LOG` still takes precedence
+    pub log_level: String,
+    // YAML with demo users, banks, accounts, deposits and credits, see `fixture.rs`
+    pub fixture: Option<PathBuf>,
+    pub banks: Vec<BankPublicInfo>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen: "127.0.0.1:8080".to_string(),
+            data_dir: PathBuf::from("server"),
+            runner_interval: 24 * 60 * 60,
+            storage_interval: 5 * 60,
+            log_level: "info".to_string(),
+            fixture: None,
+            banks: vec![
+                BankPublicInfo {
+                    name: "BelarusBank".to_string(),
+                    bik: 1003004,
+                    address: "Nezalezhnasci pr, 4".to_string(),
+                },
+                BankPublicInfo {
+                    name: "AlphaBank".to_string(),
+                    bik: 1003005,
+                    address: "Pershamaiski peravulak, 111".to_string(),
+                },
+                BankPublicInfo {
+                    name: "BelinvestBank".to_string(),
+                    bik: 1003006,
+                    address: "Masherava pr., 142".to_string(),
+                },
+            ],
+        }
+    }
+}
+
+const USAGE: &str = "Usage : bank-server [--config <file.yaml>] [--listen <addr:port>] [--data-dir <dir>]
+       [--runner-interval <seconds>] [--storage-interval <seconds>] [--log-level <filter>]
+       [--fixture <file.yaml>]
+An empty --fixture turns off the fixture of the config.";
+
+// flag and environment variable of every setting that can be overridden
+const OVERRIDES: &[(&str, &str)] = &[
+    ("--listen", "BANK_LISTEN"),
+    ("--data-dir", "BANK_DATA_DIR"),
+    ("--runner-interval", "BANK_RUNNER_INTERVAL"),
+    ("--storage-interval", "BANK_STORAGE_INTERVAL"),
+    ("--log-level", "BANK_LOG_LEVEL"),
+    ("--fixture", "BANK_FIXTURE"),
+];
+
+fn parse<T: std::str::FromStr>(source: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value of {} : {}", source, value))
+}
+
+impl ServerConfig {
+    /* Config for the command line of `bank-server` */
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut path = None;
+        let mut flags = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => path = Some(args.next().ok_or(USAGE)?),
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                flag => match OVERRIDES.iter().find(|(name, _)| *name == flag) {
+                    Some((name, env)) => flags.push((*name, *env, args.next().ok_or(USAGE)?)),
+                    None => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+                },
+            }
+        }
+
+        let mut config = match path {
+            Some(path) => Self::load(&path)?,
+            None => ServerConfig::default(),
+        };
+        config.apply_env()?;
+        for (flag, env, value) in flags {
+            config.set(env, flag, &value)?;
+        }
+        config.check()?;
+        Ok(config)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Can't read config {} : {}", path, e))?;
+        serde_yaml::from_str(&text).map_err(|e| format!("Invalid config {} : {}", path, e))
+    }
+
+    pub fn apply_env(&mut self) -> Result<(), String> {
+        for (_, env) in OVERRIDES {
+            if let Ok(value) = std::env::var(env) {
+                self.set(env, env, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /* Setting of the `env` variable, `source` names it in errors */
+    fn set(&mut self, env: &str, source: &str, value: &str) -> Result<(), String> {
+        match env {
+            "BANK_LISTEN" => self.listen = value.to_string(),
+            "BANK_DATA_DIR" => self.data_dir = PathBuf::from(value),
+            "BANK_RUNNER_INTERVAL" => self.runner_interval = parse(source, value)?,
+            "BANK_STORAGE_INTERVAL" => self.storage_interval = parse(source, value)?,
+            "BANK_LOG_LEVEL" => self.log_level = value.to_string(),
+            // empty value turns the fixture off
+            "BANK_FIXTURE" => self.fixture = Some(PathBuf::from(value)).filter(|path| !path.as_os_str().is_empty()),
+            _ => unreachable!("{} is not in OVERRIDES", env),
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.runner_interval == 0 || self.storage_interval == 0 {
+            return Err("Intervals must be positive".to_string());
+        }
+        if self.banks.is_empty() {
+            return Err("At least one bank must be configured".to_string());
+        }
+        let mut biks: Vec<_> = self.banks.iter().map(|bank| bank.bik).collect();
+        biks.sort();
+        biks.dedup();
+        if biks.len() != self.banks.len() {
+            return Err("Bank BIKs must be unique".to_string());
+        }
+        // BIK 0 stands for the bank itself in transactions
+        if biks.contains(&0) {
+            return Err("BIK 0 is reserved".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split(' ').map(str::to_string)
+    }
+
+    #[test]
+    fn flags_override_settings() {
+        let config = ServerConfig::from_args(args(
+            "--listen 0.0.0.0:9000 --data-dir /tmp/bank --runner-interval 60 --fixture config/fixture.yaml",
+        ))
+        .unwrap();
+        assert_eq!(config.listen, "0.0.0.0:9000");
+        assert_eq!(config.data_dir, PathBuf::from("/tmp/bank"));
+        assert_eq!(config.runner_interval, 60);
+        assert_eq!(config.fixture, Some(PathBuf::from("config/fixture.yaml")));
+    }
+
+    #[test]
+    fn bad_flags() {
+        let err = ServerConfig::from_args(args("--runner-interval soon")).err().unwrap();
+        assert_eq!(err, "Invalid value of --runner-interval : soon");
+        assert!(ServerConfig::from_args(args("--storage-interval 0")).is_err());
+        assert!(ServerConfig::from_args(args("--port 80")).is_err());
+        assert!(ServerConfig::from_args(args("--listen")).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+/*
+ * Statement and transaction log exports for accounting.
+ * The format is taken from the `format` parameter, then from the `Accept` header.
+ */
+
+use crate::server::ServerError;
+use chrono::{DateTime, Utc};
+use l1::common::account::Statement;
+use l1::common::transaction::{LedgerEntry, TransactionEndPoint};
+use l1::common::Money;
+use rouille::{Request, Response};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    // ISO 20022 Bank-to-Customer Statement
+    Camt053,
+}
+
+impl ExportFormat {
+    pub fn from_request(req: &Request) -> Result<Self, ServerError> {
+        if let Some(format) = req.get_param("format").filter(|format| !format.is_empty()) {
+            return match format.to_lowercase().as_str() {
+                "json" => Ok(ExportFormat::Json),
+                "csv" => Ok(ExportFormat::Csv),
+                "camt053" | "camt.053" | "xml" => Ok(ExportFormat::Camt053),
+                _ => Err(ServerError::field("format", "Must be one of json, csv, camt053")),
+            };
+        }
+
+        // the first known media type wins, quality values are not taken into account
+        let accept = req.header("Accept").unwrap_or("");
+        for media_type in accept.split(',') {
+            match media_type.split(';').next().unwrap_or("").trim() {
+                "application/json" => return Ok(ExportFormat::Json),
+                "text/csv" => return Ok(ExportFormat::Csv),
+                "application/xml" | "text/xml" => return Ok(ExportFormat::Camt053),
+                _ => {}
+            }
+        }
+        Ok(ExportFormat::Json)
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn endpoint_id(endpoint: &TransactionEndPoint) -> String {
+    format!("{}/{}", endpoint.bik, endpoint.account_id)
+}
+
+fn csv_response(csv: String, file_name: &str) -> Response {
+    Response::from_data("text/csv; charset=utf-8", csv).with_additional_header(
+        "Content-Disposition",
+        format!("attachment; filename=\"{}\"", file_name),
+    )
+}
+
+pub fn statement_csv(statement: &Statement) -> String {
+    let mut csv = String::from("entry_id,time,counterparty_bik,counterparty_account,description,amount,balance\n");
+    for line in &statement.lines {
+        csv += &format!(
+            "{},{},{},{},{},{},{}\n",
+            line.entry_id,
+            line.time.to_rfc3339(),
+            line.counterparty.bik,
+            line.counterparty.account_id,
+            csv_field(&line.description),
+            *line.amount,
+            *line.balance
+        );
+    }
+    csv
+}
+
+fn camt_balance(code: &str, amount: i32, date: Option<DateTime<Utc>>) -> String {
+    let indicator = if amount < 0 { "DBIT" } else { "CRDT" };
+    let date = date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default();
+    format!(
+        r#"      <Bal>
+        <Tp><CdOrPrtry><Cd>{}</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="BYN">{}</Amt>
+        <CdtDbtInd>{}</CdtDbtInd>
+        <Dt><Dt>{}</Dt></Dt>
+      </Bal>
+"#,
+        code,
+        Money(amount.abs()),
+        indicator,
+        date
+    )
+}
+
+/* camt.053.001.02 document. Accounts have no IBAN yet, so they are identified as `bik/account`. */
+pub fn statement_camt053(statement: &Statement, created: DateTime<Utc>) -> String {
+    let account = TransactionEndPoint {
+        bik: statement.bik,
+        account_id: statement.account_id,
+    };
+    let statement_id = format!(
+        "STMT-{}-{}-{}",
+        statement.bik,
+        statement.account_id,
+        created.format("%Y%m%d%H%M%S")
+    );
+    let first_entry = statement.lines.first().map(|line| line.time);
+    let last_entry = statement.lines.last().map(|line| line.time);
+    let from = statement.from.or(first_entry).unwrap_or(created);
+    let to = statement.to.or(last_entry).unwrap_or(created);
+
+    let mut xml = String::new();
+    xml += r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+"#;
+    xml += &format!(
+        r#"    <GrpHdr>
+      <MsgId>{id}</MsgId>
+      <CreDtTm>{created}</CreDtTm>
+    </GrpHdr>
+    <Stmt>
+      <Id>{id}</Id>
+      <CreDtTm>{created}</CreDtTm>
+      <FrToDt>
+        <FrDtTm>{from}</FrDtTm>
+        <ToDtTm>{to}</ToDtTm>
+      </FrToDt>
+      <Acct>
+        <Id><Othr><Id>{account}</Id></Othr></Id>
+        <Ccy>BYN</Ccy>
+        <Svcr><FinInstnId><Othr><Id>{bik}</Id></Othr></FinInstnId></Svcr>
+      </Acct>
+"#,
+        id = statement_id,
+        created = created.format("%Y-%m-%dT%H:%M:%S"),
+        from = from.format("%Y-%m-%dT%H:%M:%S"),
+        to = to.format("%Y-%m-%dT%H:%M:%S"),
+        account = endpoint_id(&account),
+        bik = statement.bik
+    );
+    xml += &camt_balance("OPBD", *statement.opening_balance, Some(from));
+    xml += &camt_balance("CLBD", *statement.closing_balance, Some(to));
+
+    for line in &statement.lines {
+        let amount = *line.amount;
+        // issued or received credit transfer
+        let (indicator, family, party) = if amount < 0 {
+            ("DBIT", "ICDT", "Cdtr")
+        } else {
+            ("CRDT", "RCDT", "Dbtr")
+        };
+        xml += &format!(
+            r#"      <Ntry>
+        <NtryRef>{entry_id}</NtryRef>
+        <Amt Ccy="BYN">{amount}</Amt>
+        <CdtDbtInd>{indicator}</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><DtTm>{time}</DtTm></BookgDt>
+        <ValDt><Dt>{date}</Dt></ValDt>
+        <BkTxCd><Domn><Cd>PMNT</Cd><Fmly><Cd>{family}</Cd><SubFmlyCd>OTHR</SubFmlyCd></Fmly></Domn></BkTxCd>
+        <NtryDtls>
+          <TxDtls>
+            <Refs><EndToEndId>{entry_id}</EndToEndId></Refs>
+            <RltdPties>
+              <{party}Acct><Id><Othr><Id>{counterparty}</Id></Othr></Id></{party}Acct>
+            </RltdPties>
+            <RmtInf><Ustrd>{description}</Ustrd></RmtInf>
+          </TxDtls>
+        </NtryDtls>
+        <AddtlNtryInf>{description}</AddtlNtryInf>
+      </Ntry>
+"#,
+            entry_id = line.entry_id,
+            amount = Money(amount.abs()),
+            indicator = indicator,
+            family = family,
+            time = line.time.format("%Y-%m-%dT%H:%M:%S"),
+            date = line.time.format("%Y-%m-%d"),
+            description = xml_escape(&line.description),
+            party = party,
+            counterparty = endpoint_id(&line.counterparty)
+        );
+    }
+
+    xml += r#"    </Stmt>
+  </BkToCstmrStmt>
+</Document>
+"#;
+    xml
+}
+
+pub fn statement_response(
+    statement: &Statement,
+    format: ExportFormat,
+    created: DateTime<Utc>,
+) -> Response {
+    let file_name = format!("statement-{}-{}", statement.bik, statement.account_id);
+    match format {
+        ExportFormat::Json => Response::json(statement),
+        ExportFormat::Csv => csv_response(statement_csv(statement), &format!("{}.csv", file_name)),
+        ExportFormat::Camt053 => {
+            Response::from_data("application/xml; charset=utf-8", statement_camt053(statement, created))
+                .with_additional_header(
+                    "Content-Disposition",
+                    format!("attachment; filename=\"{}.xml\"", file_name),
+                )
+        }
+    }
+}
+
+pub fn ledger_csv(entries: &[LedgerEntry]) -> String {
+    let mut csv =
+        String::from("id,time,src_bik,src_account,dst_bik,dst_account,amount,description\n");
+    for entry in entries {
+        let transaction = &entry.transaction;
+        csv += &format!(
+            "{},{},{},{},{},{},{},{}\n",
+            entry.id,
+            entry.time.to_rfc3339(),
+            transaction.src.bik,
+            transaction.src.account_id,
+            transaction.dst.bik,
+            transaction.dst.account_id,
+            *transaction.amount,
+            csv_field(&entry.description)
+        );
+    }
+    csv
+}
+
+/* Transaction log, camt.053 is a per-account format and is not offered here */
+pub fn ledger_response(entries: &[LedgerEntry], format: ExportFormat) -> Result<Response, ServerError> {
+    match format {
+        ExportFormat::Json => Ok(Response::json(&entries)),
+        ExportFormat::Csv => Ok(csv_response(ledger_csv(entries), "transactions.csv")),
+        ExportFormat::Camt053 => Err(ServerError::field(
+            "format",
+            "Transaction log can be exported as json or csv only",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use l1::common::account::StatementLine;
+
+    fn statement() -> Statement {
+        let time = Utc.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap();
+        Statement {
+            bik: 1003004,
+            account_id: 1,
+            from: None,
+            to: None,
+            opening_balance: Money(1334),
+            lines: vec![StatementLine {
+                entry_id: 7,
+                time,
+                counterparty: TransactionEndPoint { bik: 1003005, account_id: 2 },
+                description: "Rent & fee".to_string(),
+                amount: Money(-1205),
+                balance: Money(129),
+            }],
+            closing_balance: Money(129),
+        }
+    }
+
+    #[test]
+    fn camt053_amounts_are_in_byn() {
+        let created = Utc.with_ymd_and_hms(2024, 3, 6, 0, 0, 0).unwrap();
+        let xml = statement_camt053(&statement(), created);
+        let amounts: Vec<&str> = xml
+            .match_indices("<Amt Ccy=\"BYN\">")
+            .map(|(at, tag)| {
+                let value = &xml[at + tag.len()..];
+                &value[..value.find('<').unwrap()]
+            })
+            .collect();
+        assert_eq!(amounts, vec!["13.34", "1.29", "12.05"]);
+    }
+
+    #[test]
+    fn camt053_keeps_description_out_of_the_transaction_code() {
+        let created = Utc.with_ymd_and_hms(2024, 3, 6, 0, 0, 0).unwrap();
+        let xml = statement_camt053(&statement(), created);
+        assert!(xml.contains("<Fmly><Cd>ICDT</Cd><SubFmlyCd>OTHR</SubFmlyCd></Fmly>"));
+        assert!(xml.contains("<AddtlNtryInf>Rent &amp; fee</AddtlNtryInf>"));
+        assert!(xml.contains("<RmtInf><Ustrd>Rent &amp; fee</Ustrd></RmtInf>"));
+        assert!(!xml.contains("<Prtry>"));
+    }
+}
@@ -0,0 +1,423 @@
+/*
+ * Demo data loader. Reads a YAML fixture with users, enterprises, banks,
+ * accounts, deposits and credits and puts it into the services on start.
+ * Random clients are generated from `rng_seed`, so the same fixture
+ * always gives the same logins and passports.
+ */
+
+use crate::services::auth::AuthService;
+use crate::services::bank::{credit_monthly_pay, BankService};
+use crate::server::ServerError;
+
+use l1::common::bank::{AccountID, BankPublicInfo, BIK};
+use l1::common::credit::{Credit, CreditParams};
+use l1::common::deposit::Deposit;
+use l1::common::interest::{Capitalization, DayCount, InterestAccrual};
+use l1::common::user::*;
+use l1::common::validate::Validate;
+use l1::common::Money;
+
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum FixtureRole {
+    Client,
+    Operator,
+    Manager,
+    Enterprise,
+    Administrator,
+}
+
+impl From<FixtureRole> for UserType {
+    fn from(role: FixtureRole) -> Self {
+        match role {
+            FixtureRole::Client => CLIENT,
+            FixtureRole::Operator => OPERATOR,
+            FixtureRole::Manager => MANAGER,
+            FixtureRole::Enterprise => ENTERPRISE,
+            FixtureRole::Administrator => ADMINISTRATOR,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FixtureUser {
+    pub login: String,
+    pub password: String,
+    pub role: FixtureRole,
+    // required for `client` role
+    pub client: Option<Client>,
+    // required for `enterprise` role
+    pub specialist: Option<Specialist>,
+    #[serde(default)]
+    pub password_change_required: bool,
+    // required for `operator` and `manager` roles
+    pub bank: Option<BIK>,
+}
+
+#[derive(Deserialize)]
+pub struct FixtureAccount {
+    pub owner: String,
+    pub bik: BIK,
+    #[serde(default)]
+    pub balance: Money,
+}
+
+#[derive(Deserialize)]
+pub struct FixtureDeposit {
+    pub owner: String,
+    pub bik: BIK,
+    pub amount: Money,
+    pub months: u32,
+    pub interest_rate: u8,
+    #[serde(default)]
+    pub day_count: DayCount,
+    #[serde(default)]
+    pub capitalization: Capitalization,
+}
+
+#[derive(Deserialize)]
+pub struct FixtureCredit {
+    pub owner: String,
+    pub bik: BIK,
+    // ids are given out from 1 in every bank, in the order of `accounts`
+    pub account: AccountID,
+    pub amount: Money,
+    pub term: u8,
+    pub interest_rate: u8,
+    #[serde(default)]
+    pub day_count: DayCount,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Fixture {
+    pub rng_seed: u64,
+    // clients with random logins, password "123" to be changed at the first login
+    pub random_clients: usize,
+    // added to the banks of the config
+    pub banks: Vec<BankPublicInfo>,
+    pub enterprises: Vec<Enterprise>,
+    pub users: Vec<FixtureUser>,
+    pub accounts: Vec<FixtureAccount>,
+    pub deposits: Vec<FixtureDeposit>,
+    pub credits: Vec<FixtureCredit>,
+}
+
+const NAMES : &[&str]= &[
+  "Kostya",
+  "Max",
+  "Dima",
+  "Artsiom",
+  "Xena",
+  "Diana",
+  "Pavel",
+  "Ivan",
+  "Misha",
+  "Stas",
+  "Andrey",
+  "Sergey",
+  "Petr",
+  "Nikita",
+  "Vladimir",
+  "Oleg",
+  "Denis",
+  "Arseniy",
+  "Egor",
+  "Daniil",
+  "Yaroslav",
+];
+const SURNAMES  : &[&str]= &[
+  "Byteable",
+  "Verible",
+  "Cromable",
+  "Clangly",
+  "Rustable",
+  "Sandable",
+  "Nandy",
+  "Nory",
+  "Pony",
+  "Sandman",
+  "Silverman",
+  "Goldman",
+  "Platinuman",
+  "Rubyman",
+  "Sapphireman",
+  "Cosmer",
+  "Earther",
+  "Deuterer",
+  "Curable",
+  "Fermenter",
+  "Milkman",
+  "Waterman",
+  "Mailer",
+  "Cretor",
+  "Mentor",
+  "Supitar",
+  "Onkir",
+];
+
+fn describe(err: ServerError) -> String {
+    err.into_response().to_string()
+}
+
+fn random_client(rng: &mut StdRng, login: &str) -> Client {
+    Client {
+        full_name: UserPersonalName {
+            first_name: NAMES[rng.random_range(0..NAMES.len())].to_string(),
+            middle_name: NAMES[rng.random_range(0..NAMES.len())].to_string(),
+            last_name: SURNAMES[rng.random_range(0..SURNAMES.len())].to_string(),
+        },
+        passport: UserPassportData {
+            series: "MP".to_string(),
+            number: format!("{:07}", rng.random_range(0..10_000_000)),
+            id_number: format!(
+                "{:07}A{:03}PB{}",
+                rng.random_range(0..10_000_000),
+                rng.random_range(0..1000),
+                rng.random_range(0..10)
+            ),
+        },
+        phone_number: format!("+375 29{:07}", rng.random_range(0..10_000_000)),
+        email: format!("{}@finarus.by", login.to_lowercase()),
+    }
+}
+
+impl Fixture {
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Can't read fixture {} : {}", path.display(), e))?;
+        serde_yaml::from_str(&text)
+            .map_err(|e| format!("Invalid fixture {} : {}", path.display(), e))
+    }
+
+    /* Must run before the services are shared, banks can't be added later */
+    pub fn apply(
+        &self,
+        auth: &mut AuthService,
+        banks: &mut BankService,
+        now: DateTime<Utc>,
+    ) -> Result<(), String> {
+        for bank in &self.banks {
+            banks.add_bank(bank.clone()).map_err(describe)?;
+        }
+        for enterprise in &self.enterprises {
+            enterprise
+                .validate()
+                .map_err(|e| format!("Enterprise {} :\n{}", enterprise.name, e))?;
+            auth.add_enterprise(enterprise.clone()).map_err(describe)?;
+        }
+        self.apply_users(auth, banks)?;
+        self.apply_random_clients(auth)?;
+        self.apply_accounts(auth, banks)?;
+        self.apply_deposits(banks, now)?;
+        self.apply_credits(banks, now)
+    }
+
+    fn apply_users(&self, auth: &mut AuthService, banks: &BankService) -> Result<(), String> {
+        for user in &self.users {
+            let bank = match (user.role, user.bank) {
+                (FixtureRole::Operator | FixtureRole::Manager, Some(bik)) if !banks.has_bank(bik) => {
+                    return Err(format!("User {} : bank {} not found", user.login, bik))
+                }
+                (FixtureRole::Operator | FixtureRole::Manager, None) => {
+                    return Err(format!("User {} : `bank` is required", user.login))
+                }
+                (FixtureRole::Operator | FixtureRole::Manager, bank) => bank,
+                _ => None,
+            };
+            let public_user = match (user.role, &user.client, &user.specialist) {
+                (FixtureRole::Client, Some(client), _) => {
+                    client
+                        .validate()
+                        .map_err(|e| format!("User {} :\n{}", user.login, e))?;
+                    UserData::ClientData(client.clone())
+                }
+                (FixtureRole::Client, None, _) => {
+                    return Err(format!("User {} : `client` is required", user.login))
+                }
+                (FixtureRole::Enterprise, _, Some(specialist)) => {
+                    UserData::SpecialistData(specialist.clone())
+                }
+                (FixtureRole::Enterprise, _, None) => {
+                    return Err(format!("User {} : `specialist` is required", user.login))
+                }
+                _ => UserData::None,
+            };
+            auth.add_user(
+                &user.login,
+                &user.password,
+                user.role.into(),
+                public_user,
+                user.password_change_required,
+                bank,
+            )
+            .map_err(describe)?;
+        }
+        Ok(())
+    }
+
+    /* Logins and data of the random clients, the same for the same seed. `taken` logins are skipped. */
+    fn random_clients(&self, taken: impl Fn(&str) -> bool) -> Vec<(String, Client)> {
+        let mut rng = StdRng::seed_from_u64(self.rng_seed);
+        let mut clients: Vec<(String, Client)> = Vec::new();
+        while clients.len() < self.random_clients {
+            let login: String = (&mut rng)
+                .sample_iter(&rand::distr::Alphanumeric)
+                .take(4)
+                .map(char::from)
+                .collect();
+            let client = random_client(&mut rng, &login);
+            if taken(&login) || clients.iter().any(|(other, _)| *other == login) {
+                continue;
+            }
+            clients.push((login, client));
+        }
+        clients
+    }
+
+    fn apply_random_clients(&self, auth: &mut AuthService) -> Result<(), String> {
+        for (login, client) in self.random_clients(|login| auth.user_exists(login)) {
+            auth.add_user(&login, "123", CLIENT, UserData::ClientData(client), true, None)
+                .map_err(describe)?;
+            log::info!("User with login {} created", login);
+        }
+        Ok(())
+    }
+
+    fn apply_accounts(&self, auth: &AuthService, banks: &BankService) -> Result<(), String> {
+        for account in &self.accounts {
+            if !auth.user_exists(&account.owner) {
+                return Err(format!("Account owner {} not found", account.owner));
+            }
+            let mut bank = banks.lock_bank(account.bik).map_err(describe)?;
+            let id = bank.account_new(&account.owner).map_err(describe)?;
+            bank.accounts
+                .get_mut(&id)
+                .expect("Account was just opened")
+                .balance = account.balance;
+        }
+        Ok(())
+    }
+
+    fn apply_deposits(&self, banks: &BankService, now: DateTime<Utc>) -> Result<(), String> {
+        for deposit in &self.deposits {
+            let mut bank = banks.lock_bank(deposit.bik).map_err(describe)?;
+            bank.add_client_if_not_exist(&deposit.owner);
+            bank.deposit_service.add_deposit(
+                deposit.owner.clone(),
+                Deposit {
+                    owner: deposit.owner.clone(),
+                    interest_rate: deposit.interest_rate,
+                    start_date: now,
+                    end_date: now + chrono::Months::new(deposit.months),
+                    initial_amount: deposit.amount,
+                    current_amount: deposit.amount,
+                    capitalization: deposit.capitalization,
+                    accrual: InterestAccrual::new(deposit.day_count, now),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn apply_credits(&self, banks: &BankService, now: DateTime<Utc>) -> Result<(), String> {
+        for credit in &self.credits {
+            let params = CreditParams {
+                src_account: credit.account,
+                interest_rate: credit.interest_rate,
+                term: credit.term,
+                amount: credit.amount,
+                day_count: credit.day_count,
+            };
+            params
+                .validate()
+                .map_err(|e| format!("Credit of {} :\n{}", credit.owner, e))?;
+            let mut bank = banks.lock_bank(credit.bik).map_err(describe)?;
+            bank.validate_account_identity(credit.account, &credit.owner)
+                .map_err(|e| format!("Credit of {} : {}", credit.owner, describe(e)))?;
+            bank.credit_service
+                .accepted_credits
+                .entry(credit.owner.clone())
+                .or_default()
+                .push(Credit {
+                    owner: credit.owner.clone(),
+                    monthly_pay: credit_monthly_pay(&params),
+                    outstanding: params.amount,
+                    accrual: InterestAccrual::new(params.day_count, now),
+                    params,
+                    first_pay: now + chrono::Months::new(1),
+                    last_pay: now + chrono::Months::new(1),
+                });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::audit::AuditService;
+    use crate::services::time::TimeService;
+    use std::sync::{Arc, Mutex};
+
+    fn bank_service() -> BankService {
+        let audit = Arc::new(Mutex::new(AuditService::new()));
+        let auth = Arc::new(Mutex::new(AuthService::new(audit)));
+        let time = Arc::new(Mutex::new(TimeService::new()));
+        BankService::new(auth, time, &[])
+    }
+
+    #[test]
+    fn refuses_invalid_credits() {
+        let fixture = Fixture {
+            credits: vec![FixtureCredit {
+                owner: "ivan".to_string(),
+                bik: 1003004,
+                account: 1,
+                amount: Money(1000),
+                term: 0,
+                interest_rate: 0,
+                day_count: DayCount::default(),
+            }],
+            ..Default::default()
+        };
+        let err = fixture.apply_credits(&bank_service(), Utc::now()).unwrap_err();
+        assert!(err.starts_with("Credit of ivan :"), "{}", err);
+        assert!(err.contains("term"), "{}", err);
+    }
+
+    fn generated(fixture: &Fixture, taken: &[&str]) -> Vec<(String, String)> {
+        fixture
+            .random_clients(|login| taken.contains(&login))
+            .into_iter()
+            .map(|(login, client)| (login, serde_json::to_string(&client).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_clients() {
+        let fixture = Fixture { rng_seed: 42, random_clients: 20, ..Default::default() };
+        let first = generated(&fixture, &[]);
+        assert_eq!(first.len(), 20);
+        assert_eq!(first, generated(&fixture, &[]));
+
+        let other_seed = Fixture { rng_seed: 43, ..fixture };
+        assert_ne!(first, generated(&other_seed, &[]));
+    }
+
+    #[test]
+    fn skips_taken_logins() {
+        let fixture = Fixture { rng_seed: 42, random_clients: 3, ..Default::default() };
+        let first = generated(&fixture, &[]);
+        let taken = first[0].0.clone();
+        let clients = generated(&fixture, &[&taken]);
+        assert_eq!(clients.len(), 3);
+        assert!(clients.iter().all(|(login, _)| *login != taken));
+        assert_eq!(clients[..2], first[1..]);
+    }
+}
@@ -0,0 +1,371 @@
+/*
+ * Bulk payment files : ISO 20022 pain.001 (Customer Credit Transfer Initiation)
+ * and a CSV template. Accounts are written as `bik/account`, the way
+ * `export.rs` writes them, or as a bare account id in the payer's bank.
+ */
+
+use crate::server::ServerError;
+use l1::common::bank::{AccountID, BIK};
+use l1::common::payment::PaymentLine;
+use l1::common::transaction::TransactionEndPoint;
+use l1::common::validate::ValidationErrors;
+use l1::common::Money;
+use roxmltree::{Document, Node};
+use rouille::Request;
+
+pub const MAX_BATCH_LINES: usize = 1000;
+
+pub const CSV_TEMPLATE: &str = "src_account,dst_bik,dst_account,amount,reference";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    Pain001,
+}
+
+impl ImportFormat {
+    /* `format` parameter first, then `Content-Type`. CSV is the default. */
+    pub fn from_request(req: &Request) -> Result<Self, ServerError> {
+        if let Some(format) = req.get_param("format").filter(|format| !format.is_empty()) {
+            return match format.to_lowercase().as_str() {
+                "csv" => Ok(ImportFormat::Csv),
+                "pain001" | "pain.001" | "xml" => Ok(ImportFormat::Pain001),
+                _ => Err(ServerError::field("format", "Must be one of csv, pain001")),
+            };
+        }
+        let content_type = req.header("Content-Type").unwrap_or("");
+        match content_type.split(';').next().unwrap_or("").trim() {
+            "application/xml" | "text/xml" => Ok(ImportFormat::Pain001),
+            _ => Ok(ImportFormat::Csv),
+        }
+    }
+}
+
+/* Decimal amount in BYN, kept in cents */
+fn parse_amount(value: &str) -> Result<Money, String> {
+    let value = value.trim();
+    let invalid = || format!("Invalid amount {}", value);
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if whole.is_empty() || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > 2 {
+        return Err("Amount must have at most two decimal places".to_string());
+    }
+    let cents = format!("{:0<2}", fraction).parse::<i32>().map_err(|_| invalid())?;
+    match whole.parse::<i32>().ok().and_then(|whole| whole.checked_mul(100)?.checked_add(cents)) {
+        Some(amount) if amount > 0 => Ok(Money(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+/* `bik/account` or a bare account id, which is looked up in `default_bik` */
+fn parse_account(value: &str, default_bik: BIK) -> Result<TransactionEndPoint, String> {
+    let value = value.trim();
+    let (bik, account) = match value.split_once('/') {
+        Some((bik, account)) => (bik.parse::<BIK>().map_err(|_| format!("Invalid BIK {}", bik))?, account),
+        None => (default_bik, value),
+    };
+    let account_id = account
+        .parse::<AccountID>()
+        .map_err(|_| format!("Invalid account {}", value))?;
+    Ok(TransactionEndPoint { bik, account_id })
+}
+
+fn into_result(lines: Vec<PaymentLine>, errors: ValidationErrors) -> Result<Vec<PaymentLine>, ServerError> {
+    errors.into_result().map_err(ServerError::Unprocessable)?;
+    if lines.is_empty() {
+        return Err(ServerError::field("batch", "No payments in the file"));
+    }
+    if lines.len() > MAX_BATCH_LINES {
+        return Err(ServerError::field(
+            "batch",
+            &format!("At most {} payments per batch", MAX_BATCH_LINES),
+        ));
+    }
+    Ok(lines)
+}
+
+/* Header line is optional. Empty lines and lines starting with `#` are skipped.
+ * Payer accounts are in the bank the batch is sent to. */
+pub fn parse_csv(text: &str) -> Result<Vec<PaymentLine>, ServerError> {
+    let mut lines = Vec::new();
+    let mut errors = ValidationErrors::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("src_account") {
+            continue;
+        }
+        let field = format!("line {}", line_no);
+        let columns: Vec<&str> = line.splitn(5, ',').map(|column| column.trim()).collect();
+        if columns.len() < 4 {
+            errors.add(&field, &format!("Expected {}", CSV_TEMPLATE));
+            continue;
+        }
+
+        let parsed = (|| {
+            let src_account = columns[0]
+                .parse::<AccountID>()
+                .map_err(|_| format!("Invalid source account {}", columns[0]))?;
+            let dst = TransactionEndPoint {
+                bik: columns[1]
+                    .parse()
+                    .map_err(|_| format!("Invalid BIK {}", columns[1]))?,
+                account_id: columns[2]
+                    .parse()
+                    .map_err(|_| format!("Invalid account {}", columns[2]))?,
+            };
+            let amount = parse_amount(columns[3])?;
+            let reference = columns.get(4).unwrap_or(&"").trim_matches('"').to_string();
+            Ok::<_, String>(PaymentLine {
+                line: line_no,
+                src_account,
+                dst,
+                amount,
+                reference,
+            })
+        })();
+        match parsed {
+            Ok(payment) => lines.push(payment),
+            Err(message) => errors.add(&field, &message),
+        }
+    }
+    into_result(lines, errors)
+}
+
+/* Elements named `tag` below `node`, namespace prefixes are ignored */
+fn descendants<'a, 'input>(
+    node: Node<'a, 'input>,
+    tag: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.descendants()
+        .skip(1)
+        .filter(move |child| child.is_element() && child.tag_name().name() == tag)
+}
+
+/* First element along `path`, like `CdtrAcct/Id/Othr/Id` */
+fn element<'a, 'input>(node: Node<'a, 'input>, path: &[&'static str]) -> Option<Node<'a, 'input>> {
+    let mut current = node;
+    for tag in path {
+        current = descendants(current, tag).next()?;
+    }
+    Some(current)
+}
+
+fn text<'a>(node: Node<'a, '_>, path: &[&'static str]) -> Option<&'a str> {
+    element(node, path)?.text().map(str::trim)
+}
+
+/* Reads `PmtInf/DbtrAcct` and every `CdtTrfTxInf` in it.
+ * Payer accounts must belong to the bank `bik` the batch is sent to. */
+pub fn parse_pain001(xml: &str, bik: BIK) -> Result<Vec<PaymentLine>, ServerError> {
+    let document = Document::parse(xml)
+        .map_err(|err| ServerError::field("batch", &format!("Invalid XML : {}", err)))?;
+    let root = document.root();
+    if descendants(root, "CstmrCdtTrfInitn").next().is_none() {
+        return Err(ServerError::field("batch", "Not a pain.001 document"));
+    }
+    let mut lines = Vec::new();
+    let mut errors = ValidationErrors::new();
+    let mut line_no = 0;
+
+    for (info_idx, payment_info) in descendants(root, "PmtInf").enumerate() {
+        let src = text(payment_info, &["DbtrAcct", "Id", "Othr", "Id"])
+            .ok_or("Missing DbtrAcct".to_string())
+            .and_then(|account| parse_account(account, bik));
+        let src = match src {
+            Ok(src) if src.bik == bik => src,
+            Ok(_) => {
+                errors.add(&format!("PmtInf {}", info_idx + 1), "Debtor account must be in the selected bank");
+                continue;
+            }
+            Err(message) => {
+                errors.add(&format!("PmtInf {}", info_idx + 1), &message);
+                continue;
+            }
+        };
+
+        for transfer in descendants(payment_info, "CdtTrfTxInf") {
+            line_no += 1;
+            let parsed = (|| {
+                let amount = element(transfer, &["Amt", "InstdAmt"]).ok_or("Missing InstdAmt".to_string())?;
+                if amount.attribute("Ccy").is_some_and(|currency| currency != "BYN") {
+                    return Err("Only BYN payments are supported".to_string());
+                }
+                let amount = parse_amount(amount.text().unwrap_or(""))?;
+                // creditor bank defaults to the payer's one
+                let dst_bik = match text(transfer, &["CdtrAgt", "FinInstnId", "Othr", "Id"]) {
+                    Some(agent) => agent.parse::<BIK>().map_err(|_| format!("Invalid BIK {}", agent))?,
+                    None => bik,
+                };
+                let dst = text(transfer, &["CdtrAcct", "Id", "Othr", "Id"])
+                    .ok_or("Missing CdtrAcct".to_string())
+                    .and_then(|account| parse_account(account, dst_bik))?;
+                let reference = text(transfer, &["RmtInf", "Ustrd"])
+                    .or(text(transfer, &["PmtId", "EndToEndId"]))
+                    .unwrap_or_default()
+                    .to_string();
+                Ok(PaymentLine {
+                    line: line_no,
+                    src_account: src.account_id,
+                    dst,
+                    amount,
+                    reference,
+                })
+            })();
+            match parsed {
+                Ok(payment) => lines.push(payment),
+                Err(message) => errors.add(&format!("line {}", line_no), &message),
+            }
+        }
+    }
+    into_result(lines, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_errors(result: Result<Vec<PaymentLine>, ServerError>) -> Vec<(String, String)> {
+        match result {
+            Err(ServerError::Unprocessable(errors)) => errors
+                .errors
+                .into_iter()
+                .map(|err| (err.field, err.message))
+                .collect(),
+            Err(err) => panic!("Unexpected error {:?}", err),
+            Ok(_) => panic!("Payments were accepted"),
+        }
+    }
+
+    #[test]
+    fn csv_lines() {
+        let text = "src_account,dst_bik,dst_account,amount,reference\n\
+                    # rent\n\
+                    \n\
+                    1, 1003005, 2, 100, \"Rent, March\"\n\
+                    3,1003004,4,25.00\n\
+                    3,1003004,4,10.5\n";
+        let lines = parse_csv(text).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].line, 4);
+        assert_eq!(lines[0].src_account, 1);
+        assert_eq!((lines[0].dst.bik, lines[0].dst.account_id), (1003005, 2));
+        assert_eq!(*lines[0].amount, 10000);
+        assert_eq!(lines[0].reference, "Rent, March");
+        assert_eq!(lines[1].line, 5);
+        assert_eq!(*lines[1].amount, 2500);
+        assert_eq!(lines[1].reference, "");
+        assert_eq!(*lines[2].amount, 1050);
+    }
+
+    #[test]
+    fn csv_errors_name_the_line() {
+        let errors = field_errors(parse_csv("1,1003005,2,10.505\n1,x,2,10\n1,1003005\n1,1003005,2,-3\n"));
+        assert_eq!(
+            errors,
+            vec![
+                ("line 1".to_string(), "Amount must have at most two decimal places".to_string()),
+                ("line 2".to_string(), "Invalid BIK x".to_string()),
+                ("line 3".to_string(), format!("Expected {}", CSV_TEMPLATE)),
+                ("line 4".to_string(), "Invalid amount -3".to_string()),
+            ]
+        );
+        assert_eq!(field_errors(parse_csv("# nothing\n"))[0].0, "batch");
+    }
+
+    const PAIN001: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.03">
+  <CstmrCdtTrfInitn>
+    <GrpHdr><MsgId>1</MsgId><NbOfTxs>2</NbOfTxs></GrpHdr>
+    <PmtInf>
+      <PmtInfId>1</PmtInfId>
+      <DbtrAcct><Id><Othr><Id>1003004/7</Id></Othr></Id></DbtrAcct>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>E2E-1</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="BYN">150.00</InstdAmt></Amt>
+        <CdtrAgt><FinInstnId><Othr><Id>1003005</Id></Othr></FinInstnId></CdtrAgt>
+        <CdtrAcct><Id><Othr><Id>2</Id></Othr></Id></CdtrAcct>
+        <RmtInf><Ustrd>Invoice &amp; fee</Ustrd></RmtInf>
+      </CdtTrfTxInf>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>E2E-2</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="BYN">20</InstdAmt></Amt>
+        <CdtrAcct><Id><Othr><Id>9</Id></Othr></Id></CdtrAcct>
+      </CdtTrfTxInf>
+    </PmtInf>
+  </CstmrCdtTrfInitn>
+</Document>"#;
+
+    #[test]
+    fn pain001_transfers() {
+        let lines = parse_pain001(PAIN001, 1003004).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].line, 1);
+        assert_eq!(lines[0].src_account, 7);
+        assert_eq!((lines[0].dst.bik, lines[0].dst.account_id), (1003005, 2));
+        assert_eq!(*lines[0].amount, 15000);
+        assert_eq!(*lines[1].amount, 2000);
+        assert_eq!(lines[0].reference, "Invoice & fee");
+        // creditor bank defaults to the payer's one, reference to the end-to-end id
+        assert_eq!((lines[1].dst.bik, lines[1].dst.account_id), (1003004, 9));
+        assert_eq!(lines[1].reference, "E2E-2");
+    }
+
+    #[test]
+    fn pain001_errors() {
+        let errors = field_errors(parse_pain001(PAIN001, 1003006));
+        assert_eq!(
+            errors,
+            vec![("PmtInf 1".to_string(), "Debtor account must be in the selected bank".to_string())]
+        );
+
+        let foreign = PAIN001.replace("Ccy=\"BYN\">20", "Ccy=\"EUR\">20");
+        let errors = field_errors(parse_pain001(&foreign, 1003004));
+        assert_eq!(errors, vec![("line 2".to_string(), "Only BYN payments are supported".to_string())]);
+
+        let errors = field_errors(parse_pain001("<Document></Document>", 1003004));
+        assert_eq!(errors, vec![("batch".to_string(), "Not a pain.001 document".to_string())]);
+    }
+
+    #[test]
+    fn amounts_in_cents() {
+        assert_eq!(*parse_amount("150.00").unwrap(), 15000);
+        assert_eq!(*parse_amount(" 0.05 ").unwrap(), 5);
+        assert_eq!(*parse_amount("7.1").unwrap(), 710);
+        assert_eq!(*parse_amount("3.500").unwrap(), 350);
+        assert!(parse_amount("0.00").is_err());
+        assert!(parse_amount(".5").is_err());
+        assert!(parse_amount("1.2.3").is_err());
+        assert!(parse_amount("1e3").is_err());
+        assert!(parse_amount("99999999").is_err());
+    }
+
+    #[test]
+    fn pain001_with_prefixes_and_whitespace() {
+        let xml = "<?xml version=\"1.0\"?>\n\
+            <p:Document xmlns:p=\"urn:iso:std:iso:20022:tech:xsd:pain.001.001.03\">\n\
+            <p:CstmrCdtTrfInitn><p:PmtInf>\n\
+              <p:DbtrAcct><p:Id><p:Othr><p:Id>7</p:Id></p:Othr></p:Id></p:DbtrAcct>\n\
+              <p:CdtTrfTxInf\n>\n\
+                <p:Amt><p:InstdAmt\n\tCcy=\"BYN\">12.34</p:InstdAmt></p:Amt>\n\
+                <p:CdtrAcct><p:Id><p:Othr><p:Id>\n\t1003005/2\n</p:Id></p:Othr></p:Id></p:CdtrAcct>\n\
+              </p:CdtTrfTxInf>\n\
+            </p:PmtInf></p:CstmrCdtTrfInitn></p:Document>";
+        let lines = parse_pain001(xml, 1003004).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].src_account, 7);
+        assert_eq!((lines[0].dst.bik, lines[0].dst.account_id), (1003005, 2));
+        assert_eq!(*lines[0].amount, 1234);
+
+        let foreign = xml.replace("Ccy=\"BYN\"", "Ccy=\"EUR\"");
+        let errors = field_errors(parse_pain001(&foreign, 1003004));
+        assert_eq!(errors, vec![("line 1".to_string(), "Only BYN payments are supported".to_string())]);
+
+        let errors = field_errors(parse_pain001("<Document>", 1003004));
+        assert_eq!(errors[0].0, "batch");
+    }
+}
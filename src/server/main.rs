@@ -1,6 +1,12 @@
 pub mod account;
 pub mod bank;
+pub mod config;
+pub mod export;
+pub mod fixture;
+pub mod import;
+pub mod router;
 pub mod runner;
+pub mod scheduler;
 pub mod server;
 pub mod services;
 pub mod traits;
@@ -9,17 +15,73 @@ pub mod user;
 
 
 use log::*;
+use services::storage::StorageService;
+use traits::storable::Storable;
+use std::sync::mpsc;
+use std::time::Duration;
 
-const IP: &str = "127.0.0.1:8080";
+// how soon a stop signal is noticed
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 fn main() {
-    env_logger::init();
-    let bank_server = server::Server::new();
+    let config = match config::ServerConfig::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&config.log_level))
+        .init();
 
+    let bank_server = match server::Server::new(&config) {
+        Ok(server) => server,
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let mut storage = StorageService::new(&bank_server, &config);
+    storage.run();
 
-    info!("Starting HTTP server...");
-    rouille::start_server(IP, move |req| {
-        bank_server.lock().unwrap().handle_request(req)
-    });
+    // SIGINT and SIGTERM
+    let (stop_sender, stop_receiver) = mpsc::channel();
+    if let Err(err) = ctrlc::set_handler(move || {
+        let _ = stop_sender.send(());
+    }) {
+        error!("Unable to handle signals : {}", err);
+        std::process::exit(1);
+    }
 
+    info!("Starting HTTP server on {}...", config.listen);
+    let handler_server = bank_server.clone();
+    let http_server = match rouille::Server::new(config.listen.as_str(), move |req| {
+        handler_server.handle_request(req)
+    }) {
+        Ok(server) => server,
+        Err(err) => {
+            error!("Unable to listen on {} : {}", config.listen, err);
+            std::process::exit(1);
+        }
+    };
+    while stop_receiver.try_recv().is_err() {
+        http_server.poll_timeout(POLL_INTERVAL);
+    }
+
+    info!("Shutting down...");
+    // nothing is polled any more, so no new request is taken. Requests in
+    // progress are finished, then the listening socket is closed.
+    http_server.join();
+    drop(http_server);
+
+    // the state is stored last, once nothing changes it any more
+    let mut code = 0;
+    for result in [bank_server.shutdown(), storage.stop(), bank_server.store(&config.data_dir)] {
+        if let Err(err) = result {
+            error!("{}", err);
+            code = 1;
+        }
+    }
+    info!("Server stopped");
+    std::process::exit(code);
 }
@@ -0,0 +1,109 @@
+/*
+ * Router maps (method, url) of a request to the handler and
+ * the permission the caller must hold to reach it.
+ * Url segments like `{id}` match any value, it is passed in `RequestParams::path`.
+ */
+
+use crate::server::{RequestParams, Server, ServerError};
+use l1::common::permission::Permission;
+use rouille::{Request, Response};
+use std::collections::HashMap;
+
+pub type Handler = fn(&Server, &Request, &RequestParams) -> Result<Response, ServerError>;
+
+#[derive(Clone, Copy)]
+pub struct Route {
+    pub method: &'static str,
+    pub url: &'static str,
+    // `None` means the route is public and does not require a session
+    pub permission: Option<Permission>,
+    pub handler: Handler,
+}
+
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router { routes: Vec::new() }
+    }
+
+    pub fn add(
+        &mut self,
+        method: &'static str,
+        url: &'static str,
+        permission: Option<Permission>,
+        handler: Handler,
+    ) -> &mut Self {
+        self.routes.push(Route {
+            method,
+            url,
+            permission,
+            handler,
+        });
+        self
+    }
+
+    pub fn get(&mut self, url: &'static str, permission: Permission, handler: Handler) -> &mut Self {
+        self.add("GET", url, Some(permission), handler)
+    }
+
+    pub fn post(&mut self, url: &'static str, permission: Permission, handler: Handler) -> &mut Self {
+        self.add("POST", url, Some(permission), handler)
+    }
+
+    pub fn get_public(&mut self, url: &'static str, handler: Handler) -> &mut Self {
+        self.add("GET", url, None, handler)
+    }
+
+    pub fn post_public(&mut self, url: &'static str, handler: Handler) -> &mut Self {
+        self.add("POST", url, None, handler)
+    }
+
+    /* Returns the route and values of its `{name}` segments */
+    pub fn find(
+        &self,
+        method: &str,
+        url: &str,
+    ) -> Result<(Route, HashMap<&'static str, String>), ServerError> {
+        let mut url_matched = false;
+        for route in &self.routes {
+            let Some(path) = match_url(route.url, url) else {
+                continue;
+            };
+            url_matched = true;
+            if route.method == method {
+                return Ok((*route, path));
+            }
+        }
+        if url_matched {
+            Err(ServerError::MethodNotAllowed("Method not allowed".to_string()))
+        } else {
+            Err(ServerError::NotFound(String::new()))
+        }
+    }
+}
+
+fn match_url(pattern: &'static str, url: &str) -> Option<HashMap<&'static str, String>> {
+    let mut path = HashMap::new();
+    let mut pattern_segments = pattern.split('/');
+    let mut url_segments = url.split('/');
+    loop {
+        match (pattern_segments.next(), url_segments.next()) {
+            (None, None) => return Some(path),
+            (Some(expected), Some(segment)) => {
+                if let Some(name) = expected.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                    if segment.is_empty() {
+                        return None;
+                    }
+                    path.insert(name, segment.to_string());
+                } else if expected != segment {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+    }
+}
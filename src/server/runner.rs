@@ -1,58 +1,107 @@
 /*
  * Server runner updates dynamic properties of bank system,
- * such as credits, loans, deposits.
+ * such as credits, loans, deposits. It sleeps until the next scheduled
+ * event is due, but no longer than the configured interval.
  */
 
 use crate::server::Server;
-use crate::traits::dynamic::Dynamic;
-use chrono::{DateTime, Utc};
 use log::*;
-use std::option::Option;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
-use crate::services::time;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// an event that keeps being due must not make the runner spin
+const MIN_SLEEP: Duration = Duration::from_secs(1);
+
+enum RunnerSignal {
+    // time was changed, the next event may be due sooner
+    Wakeup,
+    Shutdown,
+}
 
 pub struct ServerRunner {
-    thread_handle: Option<std::thread::Thread>,
+    signals: Mutex<Option<Sender<RunnerSignal>>>,
+    thread_handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Default for ServerRunner {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ServerRunner {
     pub fn new() -> Self {
         ServerRunner {
-            thread_handle: None,
+            signals: Mutex::new(None),
+            thread_handle: Mutex::new(None),
         }
     }
 
-    pub fn run(&mut self, serv: &Arc<Mutex<Server>>, timeout: std::time::Duration) {
+    pub fn run(&self, serv: &Arc<Server>, timeout: Duration) {
+        let mut thread_handle = self.thread_handle.lock().unwrap();
+        if thread_handle.is_some() {
+            error!("Server runner is already running");
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
         let weak_server = Arc::downgrade(serv);
-        self.thread_handle = Some(
-            std::thread::spawn(move || loop {
-                info!("Server runner woke up. Good morning!");
-                let maybe_server = weak_server.upgrade();
-                match maybe_server {
-                    None => {
-                        info!("Server is dead, and so am I. Good bye!");
-                        return;
-                    }
-                    Some(mtx_server) => {
-                        info!("Server is alive. Performing dynamic update...");
-                        let mut server = mtx_server.lock().expect("Mutex");
-                        server.update();
-                    }
+        let handle = std::thread::spawn(move || loop {
+            info!("Server runner woke up. Good morning!");
+            let maybe_server = weak_server.upgrade();
+            let sleep = match maybe_server {
+                None => {
+                    info!("Server is dead, and so am I. Good bye!");
+                    return;
                 }
-                info!(
-                    "Dynamic update performed successfully. Going asleep for {}s. Good night.",
-                    timeout.as_secs()
-                );
-                std::thread::park_timeout(timeout);
-            })
-            .thread()
-            .clone(),
-        )
+                Some(server) => {
+                    info!("Server is alive. Performing dynamic update...");
+                    server
+                        .update()
+                        .map_or(timeout, |next| next.clamp(MIN_SLEEP, timeout))
+                }
+            };
+            info!(
+                "Dynamic update performed successfully. Going asleep for {}s. Good night.",
+                sleep.as_secs()
+            );
+            match receiver.recv_timeout(sleep) {
+                Ok(RunnerSignal::Wakeup) | Err(RecvTimeoutError::Timeout) => {}
+                Ok(RunnerSignal::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                    info!("Server runner stopped. Good bye!");
+                    return;
+                }
+            }
+        });
+        *self.signals.lock().unwrap() = Some(sender);
+        *thread_handle = Some(handle);
     }
 
     pub fn force_wakeup(&self) {
-        let handle = self.thread_handle.clone().expect("Not running");
-        handle.unpark();
+        if let Some(signals) = &*self.signals.lock().unwrap() {
+            let _ = signals.send(RunnerSignal::Wakeup);
+        }
     }
 
+    pub fn is_alive(&self) -> bool {
+        self.thread_handle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    /* Waits for the update in progress, if any, to finish */
+    pub fn stop(&self) -> Result<(), String> {
+        if let Some(signals) = self.signals.lock().unwrap().take() {
+            let _ = signals.send(RunnerSignal::Shutdown);
+        }
+        let Some(handle) = self.thread_handle.lock().unwrap().take() else {
+            return Ok(());
+        };
+        handle
+            .join()
+            .map_err(|_| "Server runner panicked".to_string())
+    }
 }
@@ -0,0 +1,51 @@
+/*
+ * Due events of the bank system ordered by virtual time. The runner fires
+ * every event that is due, in order, each at its own due time. Handling an
+ * event moves its item on, so the item's next event is a later one.
+ */
+
+use chrono::{DateTime, Utc};
+use l1::common::time::ScheduledEvent;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+#[derive(Default)]
+pub struct Scheduler {
+    queue: BinaryHeap<Reverse<ScheduledEvent>>,
+}
+
+impl Scheduler {
+    pub fn new(events: Vec<ScheduledEvent>) -> Self {
+        Scheduler {
+            queue: events.into_iter().map(Reverse).collect(),
+        }
+    }
+
+    pub fn push(&mut self, event: ScheduledEvent) {
+        self.queue.push(Reverse(event));
+    }
+
+    /* The earliest event, if it is due at `now` */
+    pub fn pop_due(&mut self, now: &DateTime<Utc>) -> Option<ScheduledEvent> {
+        if self.next_due()? > *now {
+            return None;
+        }
+        self.queue.pop().map(|Reverse(event)| event)
+    }
+
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.queue.peek().map(|Reverse(event)| event.due)
+    }
+
+    /* First `limit` events, earliest first */
+    pub fn upcoming(mut self, limit: usize) -> Vec<ScheduledEvent> {
+        let mut result = Vec::new();
+        while result.len() < limit {
+            let Some(Reverse(event)) = self.queue.pop() else {
+                break;
+            };
+            result.push(event);
+        }
+        result
+    }
+}
@@ -2,57 +2,181 @@ use l1::common::account::*;
 use l1::common::auth::*;
 use l1::common::bank::BIK;
 use l1::common::credit::*;
-use l1::common::deposit::{DepositNewRequest, DepositWithdrawRequest, DepositWithdrawResponse};
+use l1::common::deposit::{DepositNewRequest, DepositWithdrawRequest};
 use l1::common::salary::*;
-use l1::common::time::TimeAdvanceReq;
+use l1::common::standing_order::{StandingOrderCancelRequest, StandingOrderNewRequest};
+use l1::common::time::{SnapshotID, TimeAdvanceReq, TimeEventKind, TimeRestoreReq, TimeSnapshot, TimeStatus, TimeStepReq};
 use l1::common::transaction::Transaction;
-use l1::common::user::UserData;
-use l1::common::user::*;
+use l1::common::payment::BatchMode;
+use l1::common::permission::Permission;
+use l1::common::error::{ErrorCode, ErrorResponse};
+use l1::common::health::ReadinessResp;
+use l1::common::validate::{Validate, ValidationErrors};
+use l1::common::audit::AuditEntry;
 
+use crate::config::ServerConfig;
+use crate::export::{ledger_response, statement_response, ExportFormat};
+use crate::fixture::Fixture;
+use crate::import::{parse_csv, parse_pain001, ImportFormat};
+use crate::router::{Route, Router};
 use crate::runner::ServerRunner;
-use crate::services::auth::AuthService;
-use crate::services::bank::BankService;
-use crate::services::time::TimeService;
-use crate::traits::dynamic::Dynamic;
+use crate::services::audit::AuditService;
+use crate::services::auth::{AuthService, AuthState};
+use crate::services::bank::{BankService, BankState};
+use crate::services::metrics::{MetricsService, MetricsWriter};
+use crate::services::snapshot::SnapshotService;
+use crate::services::time::{TimeService, TimeState};
 use crate::traits::storable::Storable;
 
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use chrono::{DateTime, Utc};
-
-use log::*;
+// events shown by `/time/upcoming` unless asked otherwise
+const DEFAULT_UPCOMING_LIMIT: usize = 20;
+// route label of requests no route matched
+const UNMATCHED_ROUTE: &str = "unmatched";
+// file of `Storable for Server` in the data directory
+const STATE_FILE: &str = "state.json";
 
 use std::sync::{Arc, Mutex};
 
-use log::error;
+use log::{error, info, warn};
 use rouille::{Request, Response};
+use serde::{Deserialize, Serialize};
 
+#[derive(Debug)]
 pub enum ServerError {
     BadRequest(String),
+    // no session or wrong credentials
+    Unauthorized(String),
     Forbidden(String),
     InternalError(String),
     NotFound(String),
     MethodNotAllowed(String),
+    Conflict(String),
+    TooManyRequests(String),
+    // request is well-formed, but some fields have invalid values
+    Unprocessable(ValidationErrors),
 }
 
-const RUNNER_SLEEP_TIME: u64 = 24 * 60 * 60; // 24 hours
+impl ServerError {
+    /* Single field error, for checks that need server state */
+    pub fn field(field: &str, message: &str) -> Self {
+        let mut errors = ValidationErrors::new();
+        errors.add(field, message);
+        ServerError::Unprocessable(errors)
+    }
+
+    /* Short human-readable text, the first field error for `Unprocessable` */
+    pub fn message(self) -> String {
+        let response = self.into_response();
+        match response.details.into_iter().next() {
+            Some(detail) => detail.message,
+            None => response.message,
+        }
+    }
 
+    pub fn into_response(self) -> ErrorResponse {
+        let (code, message, details) = match self {
+            ServerError::BadRequest(s) => (ErrorCode::BadRequest, s, Vec::new()),
+            ServerError::Unauthorized(s) => (ErrorCode::Unauthorized, s, Vec::new()),
+            ServerError::Forbidden(s) => (ErrorCode::Forbidden, s, Vec::new()),
+            ServerError::InternalError(s) => (ErrorCode::InternalError, s, Vec::new()),
+            ServerError::NotFound(s) => (ErrorCode::NotFound, s, Vec::new()),
+            ServerError::MethodNotAllowed(s) => (ErrorCode::MethodNotAllowed, s, Vec::new()),
+            ServerError::Conflict(s) => (ErrorCode::Conflict, s, Vec::new()),
+            ServerError::TooManyRequests(s) => (ErrorCode::TooManyRequests, s, Vec::new()),
+            ServerError::Unprocessable(errors) => (
+                ErrorCode::ValidationFailed,
+                "Invalid request fields".to_string(),
+                errors.errors,
+            ),
+        };
+        ErrorResponse {
+            code,
+            message,
+            details,
+        }
+    }
+}
+
+/* Parses the request body and checks its fields through `Validate` */
 pub fn deserialize_request<T>(body: &Request) -> Result<T, ServerError>
 where
-    T: serde::de::DeserializeOwned,
+    T: serde::de::DeserializeOwned + Validate,
 {
     let body = body.data().expect("Body already retrieved. Server error.");
 
-    serde_json::from_reader(body).map_err(|err: serde_json::Error| {
+    let request: T = serde_json::from_reader(body).map_err(|err: serde_json::Error| {
         error!("Unable to deserialize request : {}", err);
         ServerError::BadRequest("Unable to deserialize request".to_string())
-    })
+    })?;
+    request.validate().map_err(ServerError::Unprocessable)?;
+    Ok(request)
 }
 
 pub struct RequestParams {
     pub token: Option<Token>,
     pub bik: Option<BIK>,
+    // `{name}` segments of the route url
+    pub path: HashMap<&'static str, String>,
+    // session owner, set by the router once the route permission is checked
+    pub login: Option<Login>,
+}
+
+impl RequestParams {
+    pub fn login(&self) -> Result<&Login, ServerError> {
+        self.login
+            .as_ref()
+            .ok_or(ServerError::Unauthorized("No session".to_string()))
+    }
+
+    pub fn path_param<T: FromStr>(&self, name: &str) -> Result<T, ServerError> {
+        self.path
+            .get(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or(ServerError::BadRequest(format!("Invalid {} in url", name)))
+    }
+}
+
+/* Accepts RFC 3339 time or a plain date. A plain date is the start of the day,
+ * or its very end when `end_of_day` is set, so that date ranges are inclusive. */
+pub fn parse_time_param(
+    req: &Request,
+    name: &str,
+    end_of_day: bool,
+) -> Result<Option<DateTime<Utc>>, ServerError> {
+    let Some(value) = req.get_param(name).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    if let Ok(time) = DateTime::parse_from_rfc3339(&value) {
+        return Ok(Some(time.with_timezone(&Utc)));
+    }
+    let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d").map_err(|_| {
+        ServerError::field(name, "Must be a date (2024-01-31) or RFC 3339 time")
+    })?;
+    let time = if end_of_day {
+        date.and_hms_milli_opt(23, 59, 59, 999)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.map(|time| time.and_utc()))
+}
+
+/* Required `YYYY-MM` parameter */
+fn parse_period_param(req: &Request, name: &str) -> Result<PayrollPeriod, ServerError> {
+    req.get_param(name)
+        .ok_or(ServerError::field(name, "Must not be empty"))?
+        .parse()
+        .map_err(|message: String| ServerError::field(name, &message))
+}
+
+fn str_labels<'a>(labels: &'a [(&'a str, String)]) -> Vec<(&'a str, &'a str)> {
+    labels.iter().map(|(label, value)| (*label, value.as_str())).collect()
 }
 
 fn parse_param<T>(param: &str, req: &Request) -> Option<T>
@@ -68,6 +192,8 @@ impl From<&Request> for RequestParams {
         RequestParams {
             token: parse_param("token", req),
             bik: parse_param("bank", req),
+            path: HashMap::new(),
+            login: None,
         }
     }
 }
@@ -81,533 +207,896 @@ macro_rules! APIV1 {
 fn map_err_to_response(opt_response: Result<Response, ServerError>) -> Response {
     match opt_response {
         Ok(response) => response,
-        Err(ServerError::Forbidden(s)) => {
-            error!("Forbidden access.");
-            Response::text(format!("Forbidden : {}", s)).with_status_code(403)
-        }
-        Err(ServerError::BadRequest(s)) => {
-            error!("Bad request.");
-            Response::text(format!("Bad request : {}", s)).with_status_code(400)
-        }
-        Err(ServerError::InternalError(s)) => {
-            error!("Internal server error.");
-            Response::text(format!("Server error : {}", s)).with_status_code(500)
-        }
-        Err(ServerError::NotFound(s)) => {
-            error!("Not found");
-            Response::text(format!("Not found : {}", s)).with_status_code(404)
-        }
-        Err(ServerError::MethodNotAllowed(s)) => {
-            error!("Method not allowed");
-            Response::text(format!("Not allowed : {}", s)).with_status_code(405)
+        Err(err) => {
+            let err = err.into_response();
+            error!("Request failed : {:?} {}", err.code, err.message);
+            let status = err.code.status_code();
+            Response::json(&err).with_status_code(status)
         }
     }
 }
 
 pub struct Server {
+    audit: Arc<Mutex<AuditService>>,
     auth: Arc<Mutex<AuthService>>,
-    banks: Mutex<BankService>,
+    banks: BankService,
     time: Arc<Mutex<TimeService>>,
+    snapshots: Mutex<SnapshotService>,
     dynamic_runner: ServerRunner,
+    router: Router,
+    metrics: Mutex<MetricsService>,
+    // real time the runner finished its last update
+    runner_last_run: Mutex<Option<DateTime<Utc>>>,
+    // held by the runner update and by time steps, so due events fire once
+    update_lock: Mutex<()>,
 }
 
 impl Server {
-    pub fn new() -> Arc<Mutex<Self>> {
-        let auth = Arc::new(Mutex::new(AuthService::new()));
+    pub fn new(config: &ServerConfig) -> Result<Arc<Self>, String> {
+        let audit = Arc::new(Mutex::new(AuditService::new()));
+        let auth = Arc::new(Mutex::new(AuthService::new(audit.clone())));
         let time = Arc::new(Mutex::new(TimeService::new()));
-        let banks = Mutex::new(BankService::new(auth.clone(), time.clone()));
-        let server = Arc::new(Mutex::new(Server {
+        let banks = BankService::new(auth.clone(), time.clone(), &config.banks);
+        let mut server = Server {
+            audit,
             auth,
             banks,
             time,
+            snapshots: Mutex::new(SnapshotService::default()),
             dynamic_runner: ServerRunner::new(),
-        }));
+            router: Self::build_router(),
+            metrics: Mutex::new(MetricsService::new()),
+            runner_last_run: Mutex::new(None),
+            update_lock: Mutex::new(()),
+        };
 
+        // the fixture only seeds a fresh data directory
+        if state_path(&config.data_dir).exists() {
+            server.load(&config.data_dir)?;
+            info!("State loaded from {}", config.data_dir.display());
+        } else if let Some(path) = &config.fixture {
+            let now = server.time.lock().unwrap().get_time();
+            let mut auth = server.auth.lock().expect("Mutex");
+            Fixture::load(path)?.apply(&mut auth, &mut server.banks, now)?;
+            info!("Fixture {} loaded", path.display());
+        }
+        if let Some((login, password)) = server.auth.lock().expect("Mutex").bootstrap_administrator() {
+            warn!("No administrator found. Created `{}`, its password is printed to stderr", login);
+            // not logged, so it does not stay in the log files
+            eprintln!("Temporary password of `{}` : {}", login, password);
+        }
+
+        let server = Arc::new(server);
         server
-            .lock()
-            .expect("Mutex")
             .dynamic_runner
-            .run(&server, Duration::from_secs(RUNNER_SLEEP_TIME));
-        server.clone()
+            .run(&server, Duration::from_secs(config.runner_interval));
+        Ok(server)
     }
 
-    pub fn handle_request(&mut self, req: &Request) -> Response {
-        map_err_to_response(self.handle_request_or_error(req))
+    /* Stops the runner once requests are no longer accepted */
+    pub fn shutdown(&self) -> Result<(), String> {
+        self.dynamic_runner.stop()
     }
 
-    pub fn handle_request_or_error(&mut self, req: &Request) -> Result<Response, ServerError> {
-        let params = RequestParams::from(req);
-        let maybe_token = params.token;
-
-        match maybe_token {
-            // if no token, user can only authentificate or register
-            None => self.handle_no_user(req, &params),
-            Some(_) => self.handle_user(req, &params),
-        }
+    pub fn handle_request(&self, req: &Request) -> Response {
+        let started = Instant::now();
+        let found = self.router.find(req.method(), req.url().as_str());
+        let route = found.as_ref().map_or(UNMATCHED_ROUTE, |(route, _)| route.url);
+        let response = map_err_to_response(found.and_then(|(route, path)| self.handle_route(route, path, req)));
+        self.metrics
+            .lock()
+            .unwrap()
+            .record_request(req.method(), route, response.status_code, started.elapsed());
+        response
     }
 
-    pub fn handle_no_user(
-        &mut self,
+    fn handle_route(
+        &self,
+        route: Route,
+        path: HashMap<&'static str, String>,
         req: &Request,
-        _params: &RequestParams,
     ) -> Result<Response, ServerError> {
-        let mut auth = self.auth.lock().expect("Mutex error");
-
-        if req.method() == "POST" {
-            match req.url().as_str() {
-                APIV1!("/auth/login") => {
-                    let login_data: LoginReq = deserialize_request(&req)?;
-                    let session_info = auth
-                        .init_session(login_data)
-                        .map_err(|err: &str| ServerError::Forbidden(err.to_string()))?;
-                    Ok(Response::json(&session_info))
-                }
-
-                APIV1!("/auth/register") => {
-                    let register_data: RegisterUserReq = deserialize_request(&req)?;
-                    auth.request_add_user(register_data)
-                        .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
-                    Ok(Response::text("Ok").with_status_code(200))
-                }
-
-                _ => Err(ServerError::NotFound(String::new())),
-            }
-        } else {
-            Err(ServerError::NotFound(String::new()))
+        let mut params = RequestParams::from(req);
+        params.path = path;
+
+        if let Some(permission) = route.permission {
+            let token = params
+                .token
+                .ok_or(ServerError::Unauthorized("No token".to_string()))?;
+            let login = self
+                .auth
+                .lock()
+                .expect("Mutex error")
+                .validate_permission(token, permission)?;
+            params.login = Some(login);
         }
+
+        (route.handler)(self, req, &params)
     }
 
-    pub fn handle_user(
-        &mut self,
-        req: &Request,
-        params: &RequestParams,
-    ) -> Result<Response, ServerError> {
-        let token = params.token.expect("`handle_user` called without user");
-        let usr_type = {
+    fn build_router() -> Router {
+        let mut router = Router::new();
+
+        // -------- authentification -------- //
+
+        router.post_public(APIV1!("/auth/login"), |s, req, _| {
+            let login_data: LoginReq = deserialize_request(req)?;
+            let session_info = s
+                .auth
+                .lock()
+                .expect("Mutex error")
+                .init_session(login_data, req.remote_addr().ip())?;
+            Ok(Response::json(&session_info))
+        });
+
+        router.post_public(APIV1!("/auth/login/2fa"), |s, req, _| {
+            let second_factor: SecondFactorReq = deserialize_request(req)?;
+            let session_info = s
+                .auth
+                .lock()
+                .expect("Mutex error")
+                .complete_second_factor(second_factor, req.remote_addr().ip())?;
+            Ok(Response::json(&session_info))
+        });
+
+        router.post_public(APIV1!("/auth/login/password"), |s, req, _| {
+            let new_password: NewPasswordReq = deserialize_request(req)?;
+            let login_response = s
+                .auth
+                .lock()
+                .expect("Mutex error")
+                .complete_password_change(new_password)?;
+            Ok(Response::json(&login_response))
+        });
+
+        router.post(APIV1!("/auth/password"), Permission::PasswordChange, |s, req, params| {
+            let change_req: PasswordChangeReq = deserialize_request(req)?;
+            let mut auth = s.auth.lock().expect("Mutex error");
+            let login = params.login()?;
+            auth.change_password(login, change_req, req.remote_addr().ip())?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/auth/password/reset"), Permission::PasswordReset, |s, req, params| {
+            let reset_req: PasswordResetReq = deserialize_request(req)?;
+            let mut auth = s.auth.lock().expect("Mutex error");
+            let login = params.login()?;
+            let reset = auth
+                .reset_password(login, reset_req)?;
+            Ok(Response::json(&reset))
+        });
+
+        router.post(APIV1!("/auth/2fa/enroll"), Permission::TwoFactor, |s, _, params| {
+            let mut auth = s.auth.lock().expect("Mutex error");
+            let login = params.login()?;
+            let enrollment = auth
+                .totp_enroll(login)?;
+            Ok(Response::json(&enrollment))
+        });
+
+        router.post(APIV1!("/auth/2fa/confirm"), Permission::TwoFactor, |s, req, params| {
+            let code_req: TotpCodeReq = deserialize_request(req)?;
+            let mut auth = s.auth.lock().expect("Mutex error");
+            let login = params.login()?;
+            let codes = auth
+                .totp_confirm(login, &code_req.code)?;
+            Ok(Response::json(&codes))
+        });
+
+        router.post(APIV1!("/auth/2fa/disable"), Permission::TwoFactor, |s, req, params| {
+            let code_req: TotpCodeReq = deserialize_request(req)?;
+            let mut auth = s.auth.lock().expect("Mutex error");
+            let login = params.login()?;
+            auth.totp_disable(login, &code_req.code)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/auth/2fa/recovery"), Permission::TwoFactor, |s, req, params| {
+            let code_req: TotpCodeReq = deserialize_request(req)?;
+            let mut auth = s.auth.lock().expect("Mutex error");
+            let login = params.login()?;
+            let codes = auth
+                .totp_recovery_codes(login, &code_req.code)?;
+            Ok(Response::json(&codes))
+        });
+
+        router.post_public(APIV1!("/auth/register"), |s, req, _| {
+            let register_data: RegisterUserReq = deserialize_request(req)?;
+            s.auth
+                .lock()
+                .expect("Mutex error")
+                .request_add_user(register_data)?;
+            Ok(Response::text("Ok").with_status_code(200))
+        });
+
+        router.post_public(APIV1!("/auth/register/status"), |s, req, _| {
+            let login_data: LoginReq = deserialize_request(req)?;
+            let status = s
+                .auth
+                .lock()
+                .expect("Mutex error")
+                .registration_status(login_data, req.remote_addr().ip())?;
+            Ok(Response::json(&status))
+        });
+
+        router.post_public(APIV1!("/auth/register/enterprise"), |s, req, _| {
+            let register_data: RegisterEnterpriseReq = deserialize_request(req)?;
+            s.auth
+                .lock()
+                .expect("Mutex error")
+                .request_add_enterprise(register_data)?;
+            Ok(Response::text("Ok").with_status_code(200))
+        });
+
+        router.get(APIV1!("/auth"), Permission::AuthInfo, |s, _, params| {
+            let auth = s.auth.lock().expect("Mutex error");
+            let usr_info = auth
+                .auth_info(params.token.expect("Permission checked without token"))
+                .ok_or(ServerError::Unauthorized("Bad token".to_string()))?;
+            Ok(Response::json(&usr_info))
+        });
+
+        router.get(APIV1!("/auth/accept"), Permission::RegistrationReview, |s, _, _| {
+            let auth = s.auth.lock().expect("Mutex error");
+            let registration_requests = auth.get_registration_requests();
+            Ok(Response::json(&registration_requests))
+        });
+
+        router.post(APIV1!("/auth/accept"), Permission::RegistrationApprove, |s, req, _| {
+            let accept_registration: AcceptRegistrationReq = deserialize_request(req)?;
+            s.auth
+                .lock()
+                .expect("Mutex error")
+                .accept_registration_request(&accept_registration)?;
+            Ok(Response::text("Ok").with_status_code(200))
+        });
+
+        router.post(APIV1!("/auth/reject"), Permission::RegistrationApprove, |s, req, _| {
+            let reject_registration: RejectRegistrationReq = deserialize_request(req)?;
+            s.auth
+                .lock()
+                .expect("Mutex error")
+                .reject_registration_request(reject_registration)?;
+            Ok(Response::text("Ok").with_status_code(200))
+        });
+
+        // -------- enterprise -------- //
+
+        router.get(APIV1!("/enterprise/specialists"), Permission::EnterpriseSpecialists, |s, _, params| {
+            let auth = s.auth.lock().expect("Mutex error");
+            let login = params.login()?;
+            Ok(Response::json(&auth.specialists(login)))
+        });
+
+        router.post(APIV1!("/enterprise/specialists"), Permission::EnterpriseSpecialists, |s, req, params| {
+            let add_req: AddSpecialistReq = deserialize_request(req)?;
+            let mut auth = s.auth.lock().expect("Mutex error");
+            let login = params.login()?;
+            auth.add_specialist(login, add_req)?;
+            Ok(Response::text("Ok"))
+        });
+
+        // -------- banks and accounts -------- //
+
+        router.get(APIV1!("/banks"), Permission::BankRead, |s, _, _| {
+            let banks_service = &s.banks;
+            let resp = banks_service.banks_get();
+            Ok(Response::json(&resp))
+        });
+
+        router.get(APIV1!("/account"), Permission::AccountRead, |s, _, params| {
+            let banks_service = &s.banks;
+            let accounts_resp = banks_service.accounts_get(params)?;
+            Ok(Response::json(&accounts_resp))
+        });
+
+        router.get(APIV1!("/account/{id}/statement"), Permission::AccountRead, |s, req, params| {
+            let account_id = params.path_param("id")?;
+            let from = parse_time_param(req, "from", false)?;
+            let to = parse_time_param(req, "to", true)?;
+            let format = ExportFormat::from_request(req)?;
+            let statement = s.banks.account_statement(account_id, from, to, params)?;
+            let now = s.time.lock().unwrap().get_time();
+            Ok(statement_response(&statement, format, now))
+        });
+
+        router.post(APIV1!("/account/open"), Permission::AccountOpen, |s, _, params| {
+            let banks_service = &s.banks;
+            let resp = banks_service.account_open(params)?;
+            Ok(Response::json(&resp))
+        });
+
+        router.post(APIV1!("/account/close"), Permission::AccountClose, |s, req, params| {
+            let banks_service = &s.banks;
+            let close_req: AccountCloseReq = deserialize_request(req)?;
+            banks_service.account_close(close_req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        // -------- deposits -------- //
+
+        router.get(APIV1!("/deposit"), Permission::DepositRead, |s, _, params| {
+            let banks_service = &s.banks;
+            let deposits = banks_service.deposits_get(params)?;
+            Ok(Response::json(&deposits))
+        });
+
+        router.post(APIV1!("/deposit/new"), Permission::DepositOpen, |s, req, params| {
+            let banks_service = &s.banks;
+            let deposit_new_req: DepositNewRequest = deserialize_request(req)?;
+            banks_service.deposit_new(deposit_new_req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/deposit/withdraw"), Permission::DepositWithdraw, |s, req, params| {
+            let banks_service = &s.banks;
+            let deposit_withdraw_req: DepositWithdrawRequest = deserialize_request(req)?;
+            banks_service.deposit_withdraw(deposit_withdraw_req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        // -------- credits -------- //
+
+        router.get(APIV1!("/credit"), Permission::CreditRead, |s, _, params| {
+            let banks_service = &s.banks;
+            let credits = banks_service.credit_get(params)?;
+            Ok(Response::json(&credits))
+        });
+
+        router.post(APIV1!("/credit/new"), Permission::CreditRequest, |s, req, params| {
+            let banks_service = &s.banks;
+            let new_req: CreditParams = deserialize_request(req)?;
+            banks_service.credit_new(new_req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.get(APIV1!("/credit/accept"), Permission::CreditReview, |s, _, params| {
+            let banks_service = &s.banks;
+            let credits = banks_service.credit_get_unaccepted(params)?;
+            Ok(Response::json(&credits))
+        });
+
+        router.post(APIV1!("/credit/accept"), Permission::CreditApprove, |s, req, params| {
+            let banks_service = &s.banks;
+            let accept_req: CreditAcceptRequest = deserialize_request(req)?;
+            banks_service.credit_accept(accept_req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        // -------- transactions -------- //
+
+        router.get(APIV1!("/transaction"), Permission::TxRead, |s, req, _| {
+            let format = ExportFormat::from_request(req)?;
+            let transactions = s.banks.transactions_get();
+            ledger_response(&transactions, format)
+        });
+
+        router.post(APIV1!("/transaction"), Permission::TxCreate, |s, req, params| {
+            let banks_service = &s.banks;
+            let transaction: Transaction = deserialize_request::<Transaction>(req)?;
+            banks_service.transaction(transaction, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/transaction/unprotected"), Permission::TxUnprotected, |s, req, params| {
+            let banks_service = &s.banks;
+            let transaction: Transaction = deserialize_request::<Transaction>(req)?;
+            banks_service.transaction_unprotected(transaction, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/transaction/revert"), Permission::TxRevert, |s, _, params| {
+            let banks = &s.banks;
+            banks.transaction_revert(params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        // -------- payments -------- //
+
+        router.post(APIV1!("/payment/batch"), Permission::PaymentBatch, |s, req, params| {
+            let format = ImportFormat::from_request(req)?;
+            let mode = match req.get_param("mode").as_deref() {
+                None | Some("") | Some("atomic") => BatchMode::Atomic,
+                Some("partial") => BatchMode::Partial,
+                Some(_) => return Err(ServerError::field("mode", "Must be atomic or partial")),
+            };
+            let mut text = String::new();
+            req.data()
+                .expect("Body already retrieved. Server error.")
+                .read_to_string(&mut text)
+                .map_err(|_| ServerError::BadRequest("Batch must be UTF-8 text".to_string()))?;
+            let bik = params.bik.ok_or(ServerError::BadRequest("No bank".to_string()))?;
+            let payments = match format {
+                ImportFormat::Csv => parse_csv(&text)?,
+                ImportFormat::Pain001 => parse_pain001(&text, bik)?,
+            };
+            let resp = s.banks.payment_batch(payments, mode, params)?;
+            Ok(Response::json(&resp))
+        });
+
+        // -------- standing orders -------- //
+
+        router.get(APIV1!("/order"), Permission::OrderRead, |s, _, params| {
+            let resp = s.banks.standing_orders_get(params)?;
+            Ok(Response::json(&resp))
+        });
+
+        router.post(APIV1!("/order/new"), Permission::OrderManage, |s, req, params| {
+            let order_req: StandingOrderNewRequest = deserialize_request(req)?;
+            let resp = s.banks.standing_order_new(order_req, params)?;
+            Ok(Response::json(&resp))
+        });
+
+        router.post(APIV1!("/order/cancel"), Permission::OrderManage, |s, req, params| {
+            let cancel_req: StandingOrderCancelRequest = deserialize_request(req)?;
+            s.banks.standing_order_cancel(cancel_req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        // -------- time -------- //
+
+        router.get(APIV1!("/time/get"), Permission::TimeRead, |s, _, _| {
+            let time = s.time.lock().unwrap().get_time();
+            Ok(Response::json(&time))
+        });
+
+        router.get(APIV1!("/time/status"), Permission::TimeRead, |s, _, _| {
+            let time = s.time.lock().unwrap();
+            Ok(Response::json(&TimeStatus { time: time.get_time(), paused: time.is_paused() }))
+        });
+
+        router.get(APIV1!("/time/events"), Permission::TimeRead, |s, req, _| {
+            let since = parse_param("since", req).unwrap_or(0);
+            Ok(Response::json(&s.time.lock().unwrap().events(since)))
+        });
+
+        router.post(APIV1!("/time/advance"), Permission::TimeAdvance, |s, req, _| {
+            let advance_req: TimeAdvanceReq = deserialize_request(req)?;
+            {
+                let mut time = s.time.lock().unwrap();
+                time.set_time(&advance_req.time)?;
+                let message = format!("Time set to {}", advance_req.time.to_rfc3339());
+                time.record(TimeEventKind::Advanced, message, Vec::new());
+            }
+            s.dynamic_runner.force_wakeup();
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/time/step"), Permission::TimeAdvance, |s, req, _| {
+            let step_req: TimeStepReq = deserialize_request(req)?;
+            Ok(Response::json(&s.step(&step_req)?))
+        });
+
+        router.post(APIV1!("/time/pause"), Permission::TimeAdvance, |s, _, _| {
+            s.time.lock().unwrap().pause()?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/time/resume"), Permission::TimeAdvance, |s, _, _| {
+            s.time.lock().unwrap().resume()?;
+            // the runner does not wait for events while time is paused
+            s.dynamic_runner.force_wakeup();
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/time/snapshot"), Permission::TimeAdvance, |s, _, _| {
+            Ok(Response::json(&s.take_snapshot()))
+        });
+
+        router.get(APIV1!("/time/snapshot"), Permission::TimeRead, |s, _, _| {
+            Ok(Response::json(&s.snapshots.lock().unwrap().list()))
+        });
+
+        router.post(APIV1!("/time/restore"), Permission::TimeAdvance, |s, req, _| {
+            let restore_req: TimeRestoreReq = deserialize_request(req)?;
+            let time = s.restore_snapshot(restore_req.snapshot)?;
+            // events of the restored state are due at other times
+            s.dynamic_runner.force_wakeup();
+            Ok(Response::json(&time))
+        });
+
+        router.get(APIV1!("/time/upcoming"), Permission::TimeRead, |s, req, _| {
+            let limit = parse_param("limit", req).unwrap_or(DEFAULT_UPCOMING_LIMIT);
+            Ok(Response::json(&s.banks.upcoming(limit)))
+        });
+
+        // -------- salary -------- //
+
+        router.post(APIV1!("/salary/request"), Permission::SalaryRequest, |s, req, params| {
+            let banks_service = &s.banks;
+            let req: SalaryClientRequest = deserialize_request(req)?;
+            banks_service.salary_request(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.get(APIV1!("/salary/accept"), Permission::SalaryManage, |s, _, params| {
+            let bank = &s.banks;
+            let resp = bank.salary_accept_decline_get(params)?;
+            Ok(Response::json(&resp))
+        });
+
+        router.post(APIV1!("/salary/accept"), Permission::SalaryManage, |s, req, params| {
+            let bank = &s.banks;
+            let req: SalaryAcceptRequest = deserialize_request(req)?;
+            bank.salary_accept_decline(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.get(APIV1!("/salary/proj"), Permission::SalaryManage, |s, _, params| {
+            let bank = &s.banks;
+            let resp = bank.get_salary_proj(params.login()?.clone())?;
+            Ok(Response::json(&resp))
+        });
+
+        router.post(APIV1!("/salary/new"), Permission::SalaryManage, |s, req, params| {
+            let bank = &s.banks;
+            let req: SalaryInitProjRequest = deserialize_request(req)?;
+            bank.init_salary_proj(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.get(APIV1!("/salary/runs"), Permission::SalaryManage, |s, _, params| {
+            let runs = s.banks.payroll_runs_get(params)?;
+            Ok(Response::json(&runs))
+        });
+
+        router.post(APIV1!("/salary/employee/salary"), Permission::SalaryManage, |s, req, params| {
+            let req: EmployeeSalaryChangeRequest = deserialize_request(req)?;
+            s.banks.employee_salary_change(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/salary/employee/account"), Permission::SalaryManage, |s, req, params| {
+            let req: EmployeeAccountChangeRequest = deserialize_request(req)?;
+            s.banks.employee_account_change(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/salary/employee/dismiss"), Permission::SalaryManage, |s, req, params| {
+            let req: EmployeeDismissRequest = deserialize_request(req)?;
+            s.banks.employee_dismiss(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.get(APIV1!("/salary/memberships"), Permission::SalaryRequest, |s, _, params| {
+            let memberships = s.banks.salary_memberships(params)?;
+            Ok(Response::json(&memberships))
+        });
+
+        router.post(APIV1!("/salary/leave"), Permission::SalaryRequest, |s, req, params| {
+            let req: SalaryLeaveRequest = deserialize_request(req)?;
+            s.banks.salary_leave(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/salary/submit"), Permission::SalaryManage, |s, _, params| {
+            s.banks.salary_proj_submit(params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/salary/close"), Permission::SalaryManage, |s, _, params| {
+            s.banks.salary_proj_close(params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/salary/deductions"), Permission::SalaryManage, |s, req, params| {
+            let req: PayrollDeductions = deserialize_request(req)?;
+            s.banks.salary_deductions_set(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.get(APIV1!("/salary/payslips"), Permission::SalaryManage, |s, req, params| {
+            let period = parse_period_param(req, "period")?;
+            Ok(Response::json(&s.banks.payslips_get(period, params)?))
+        });
+
+        router.get(APIV1!("/salary/payslip"), Permission::SalaryRequest, |s, req, params| {
+            let enterprise = req
+                .get_param("enterprise")
+                .ok_or(ServerError::field("enterprise", "Must not be empty"))?;
+            let period = parse_period_param(req, "period")?;
+            Ok(Response::json(&s.banks.payslip_get(&enterprise, period, params)?))
+        });
+
+        router.get(APIV1!("/salary/payout"), Permission::SalaryManage, |s, _, params| {
+            Ok(Response::json(&s.banks.payouts_get(params)?))
+        });
+
+        router.post(APIV1!("/salary/payout"), Permission::SalaryManage, |s, req, params| {
+            let req: PayoutBatchRequest = deserialize_request(req)?;
+            Ok(Response::json(&s.banks.payout_submit(req, params)?))
+        });
+
+        router.get(APIV1!("/salary/payout/review"), Permission::SalaryApprove, |s, _, params| {
+            Ok(Response::json(&s.banks.payouts_pending_get(params)?))
+        });
+
+        router.post(APIV1!("/salary/payout/review"), Permission::SalaryApprove, |s, req, params| {
+            let req: PayoutReviewRequest = deserialize_request(req)?;
+            s.banks.payout_review(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.get(APIV1!("/salary/accept_proj"), Permission::SalaryApprove, |s, _, params| {
+            let banks = &s.banks;
+            Ok(Response::json(&banks.get_bank_salary_projs(&[SalaryProjectStatus::Pending], params)?))
+        });
+
+        router.post(APIV1!("/salary/accept_proj"), Permission::SalaryApprove, |s, req, params| {
+            let banks = &s.banks;
+            let req: SalaryAcceptProjRequest = deserialize_request(req)?;
+            banks.accept_salary_proj(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/salary/decline_proj"), Permission::SalaryApprove, |s, req, params| {
+            let req: SalaryProjReasonRequest = deserialize_request(req)?;
+            s.banks.decline_salary_proj(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.get(APIV1!("/salary/bank_proj"), Permission::SalaryApprove, |s, _, params| {
+            let statuses = [SalaryProjectStatus::Active, SalaryProjectStatus::Suspended];
+            Ok(Response::json(&s.banks.get_bank_salary_projs(&statuses, params)?))
+        });
+
+        router.post(APIV1!("/salary/suspend_proj"), Permission::SalaryApprove, |s, req, params| {
+            let req: SalaryProjReasonRequest = deserialize_request(req)?;
+            s.banks.suspend_salary_proj(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        router.post(APIV1!("/salary/resume_proj"), Permission::SalaryApprove, |s, req, params| {
+            let req: SalaryAcceptProjRequest = deserialize_request(req)?;
+            s.banks.resume_salary_proj(req, params)?;
+            Ok(Response::text("Ok"))
+        });
+
+        // -------- audit -------- //
+
+        router.get(APIV1!("/audit"), Permission::AuditRead, |s, _, _| {
+            let audit = s.audit.lock().expect("Mutex error");
+            Ok(Response::json(audit.entries()))
+        });
+
+        router.get(APIV1!("/auth/lockouts"), Permission::AuditRead, |s, _, _| {
+            let auth = s.auth.lock().expect("Mutex error");
+            Ok(Response::json(&auth.lockouts()))
+        });
+
+        // -------- monitoring -------- //
+
+        router.get_public("/health", |_, _, _| Ok(Response::text("Ok")));
+
+        router.get_public("/ready", |s, _, _| {
+            let runner_alive = s.dynamic_runner.is_alive();
+            let ready = runner_alive;
+            let resp = ReadinessResp { ready, runner_alive };
+            Ok(Response::json(&resp).with_status_code(if ready { 200 } else { 503 }))
+        });
+
+        router.get_public("/metrics", |s, _, _| {
+            Ok(Response::from_data("text/plain; version=0.0.4", s.metrics_text()))
+        });
+
+        router
+    }
+
+    fn metrics_text(&self) -> String {
+        let mut out = MetricsWriter::new();
+        self.metrics.lock().unwrap().write_requests(&mut out);
+
+        let stats = self.banks.transaction_stats();
+        out.header("bank_transactions_total", "counter", "Booked transactions by bank and direction");
+        for ((bik, direction), (count, _)) in &stats {
+            let labels = [("bank", bik.to_string()), ("direction", direction.to_string())];
+            out.sample("bank_transactions_total", &str_labels(&labels), *count as f64);
+        }
+        out.header("bank_transaction_volume_total", "counter", "Money moved by bank and direction, in cents");
+        for ((bik, direction), (_, volume)) in &stats {
+            let labels = [("bank", bik.to_string()), ("direction", direction.to_string())];
+            out.sample("bank_transaction_volume_total", &str_labels(&labels), *volume as f64);
+        }
+
+        out.header("bank_credit_applications_pending", "gauge", "Credit applications waiting for review");
+        for (bik, count) in self.banks.pending_credit_counts() {
+            let labels = [("bank", bik.to_string())];
+            out.sample("bank_credit_applications_pending", &str_labels(&labels), count as f64);
+        }
+
+        let (sessions, (users, enterprises)) = {
             let auth = self.auth.lock().expect("Mutex error");
-            let usr = auth
-                .get_user_by_token(token)
-                .ok_or(ServerError::Forbidden("Bad token".to_string()))?;
-            usr.user_type
+            (auth.session_count(), auth.pending_registration_counts())
         };
+        out.gauge("bank_active_sessions", "Sessions that were not revoked", sessions as f64);
+        out.header("bank_registration_requests_pending", "gauge", "Registrations waiting for approval");
+        out.sample("bank_registration_requests_pending", &[("kind", "user")], users as f64);
+        out.sample("bank_registration_requests_pending", &[("kind", "enterprise")], enterprises as f64);
 
-        match usr_type {
-            CLIENT => self.handle_client(req, params),
-            MANAGER => self.handle_manager(req, params),
-            ENTERPRISE => self.handle_enterprise_specialist(req, params),
-            OPERATOR => self.handle_operator(req, params),
-            _ => Err(ServerError::BadRequest("Bad user".to_string())),
-        }
+        let last_run = *self.runner_last_run.lock().unwrap();
+        out.gauge(
+            "bank_runner_last_run_timestamp_seconds",
+            "Real time the runner finished its last update, 0 if it did not yet",
+            last_run.map_or(0.0, |time| time.timestamp() as f64),
+        );
+        out.gauge(
+            "bank_runner_alive",
+            "Whether the runner thread is running",
+            if self.dynamic_runner.is_alive() { 1.0 } else { 0.0 },
+        );
+        out.finish()
     }
 
-    pub fn handle_client(
-        &mut self,
-        req: &Request,
-        params: &RequestParams,
-    ) -> Result<Response, ServerError> {
-        let token = params.token.expect("`client` with no token");
-
-        match req.method() {
-            "GET" => match req.url().as_str() {
-                APIV1!("/auth") => {
-                    let auth = self.auth.lock().expect("Mutex error");
-                    let usr_info = &auth
-                        .get_user_by_token(token)
-                        .ok_or(ServerError::BadRequest("Bad token".to_string()))?
-                        .public_user;
-
-                    if let UserData::ClientData(client) = usr_info {
-                        Ok(Response::json(&client))
-                    } else {
-                        Err(ServerError::InternalError("No client data".to_string()))
-                    }
-                }
-                APIV1!("/banks") => {
-                    let banks_service = self.banks.lock().expect("Mutex");
-                    let resp = banks_service.banks_get();
-                    Ok(Response::json(&resp))
-                }
-
-                APIV1!("/account") => {
-                    let banks_service = self.banks.lock().expect("Mutex");
-                    let accounts_resp = banks_service.accounts_get(params)?;
-                    Ok(Response::json(&accounts_resp))
-                }
-
-                APIV1!("/deposit") => {
-                    let banks_service = self.banks.lock().expect("Mutex");
-                    let deposits = banks_service.deposits_get(params)?;
-                    Ok(Response::json(&deposits))
-                }
-
-                APIV1!("/credit") => {
-                    let banks_service = self.banks.lock().expect("Mutex");
-                    let credits = banks_service.credit_get(params)?;
-                    Ok(Response::json(&credits))
-                }
-
-                _ => Err(ServerError::NotFound("".to_string())),
-            },
-
-            "POST" => match req.url().as_str() {
-                APIV1!("/account/open") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let resp = banks_service.account_open(params)?;
-                    Ok(Response::json(&resp))
-                }
-                APIV1!("/account/close") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let close_req: AccountCloseReq = deserialize_request(req)?;
-                    banks_service.account_close(close_req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/deposit/new") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let deposit_new_req: DepositNewRequest = deserialize_request(req)?;
-                    banks_service.deposit_new(deposit_new_req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/deposit/withdraw") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let deposit_withdraw_req: DepositWithdrawRequest = deserialize_request(req)?;
-                    banks_service.deposit_withdraw(deposit_withdraw_req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/deposit/possible") => {
-                    unimplemented!()
-                }
-                APIV1!("/transaction") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let transaction: Transaction = deserialize_request::<Transaction>(req)?;
-                    banks_service.transaction(transaction, params)?;
-                    Ok(Response::text("Ok"))
-                }
-
-                APIV1!("/credit/new") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let new_req: CreditParams = deserialize_request(req)?;
-                    banks_service.credit_new(new_req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/credit/clear") => {
-                    // unimplemented!();
-                    Ok(Response::text("unimplemented"))
-                }
-
-                APIV1!("/salary/request") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let req: SalaryClientRequest = deserialize_request(req)?;
-                    banks_service.salary_request(req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-
-                _ => Err(ServerError::NotFound("".to_string())),
-            },
-
-            _ => Ok(Response::text("Method not allowed").with_status_code(405)),
+    /* Fires what is due. Returns how long until the next event, none while time is paused. */
+    pub fn update(&self) -> Option<Duration> {
+        {
+            let _update = self.update_lock.lock().unwrap();
+            let time = self.time.lock().unwrap().get_time();
+            self.update_at(&time, false);
+        }
+        *self.runner_last_run.lock().unwrap() = Some(Utc::now());
+        if self.time.lock().unwrap().is_paused() {
+            return None;
         }
+        let next = self.banks.next_event_due()?;
+        // virtual time runs as fast as real time
+        let now = self.time.lock().unwrap().get_time();
+        Some((next - now).to_std().unwrap_or(Duration::ZERO))
     }
 
-    pub fn handle_manager(
-        &mut self,
-        req: &Request,
-        params: &RequestParams,
-    ) -> Result<Response, ServerError> {
-        let url = req.url();
-
-        match req.method() {
-            "GET" => match url.as_str() {
-                APIV1!("/auth/accept") => {
-                    let auth = self.auth.lock().expect("Mutex error");
-                    let registration_requests = auth.get_registration_requests();
-                    Ok(Response::json(&registration_requests))
-                }
-                APIV1!("/banks") => {
-                    let banks_service = self.banks.lock().expect("Mutex");
-                    let resp = banks_service.banks_get();
-                    Ok(Response::json(&resp))
-                }
-                APIV1!("/time/get") => {
-                    let time = self.time.lock().unwrap().get_time();
-                    Ok(Response::json(&time))
-                }
-                APIV1!("/credit/accept") => {
-                    let banks_service = self.banks.lock().expect("Mutex");
-                    let credits = banks_service.credit_get_unaccepted(params)?;
-                    Ok(Response::json(&credits))
-                }
-                _ => Err(ServerError::NotFound("".to_string())),
-            },
-
-            "POST" => match url.as_str() {
-                APIV1!("/time/advance") => {
-                    let advance_req: TimeAdvanceReq = deserialize_request(req)?;
-                    self.time.lock().unwrap().set_time(&advance_req.time);
-                    self.dynamic_runner.force_wakeup();
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/transaction") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let transaction: Transaction = deserialize_request::<Transaction>(req)?;
-                    banks_service.transaction_unprotected(transaction, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/auth/accept") => {
-                    let mut auth = self.auth.lock().expect("Mutex error");
-                    let accept_registration: AcceptRegistrationReq = deserialize_request(&req)?;
-                    auth.accept_registration_request(&accept_registration)
-                        .map_err(|err: _| ServerError::BadRequest(err.to_string()))?;
-                    Ok(Response::text("Ok").with_status_code(200))
-                }
-                APIV1!("/credit/accept") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let accept_req: CreditAcceptRequest = deserialize_request(req)?;
-                    banks_service.credit_accept(accept_req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                _ => Err(ServerError::NotFound("".to_string())),
-            },
-
-            _ => Err(ServerError::MethodNotAllowed(
-                "Method not allowed".to_string(),
-            )),
+    /* Idle updates of the runner are not recorded, steps always are */
+    fn update_at(&self, time: &DateTime<Utc>, record_idle: bool) {
+        let first = self.banks.ledger_len();
+        let fired = self.banks.update(time);
+        let transactions = self.banks.ledger_since(first);
+        if record_idle || !fired.is_empty() || !transactions.is_empty() {
+            let message = format!(
+                "Update at {}, {} event(s), {} transaction(s)",
+                time.to_rfc3339(),
+                fired.len(),
+                transactions.len()
+            );
+            self.time.lock().unwrap().record(TimeEventKind::Update, message, transactions);
         }
     }
 
-    pub fn handle_operator(
-        &mut self,
-        req: &Request,
-        params: &RequestParams,
-    ) -> Result<Response, ServerError> {
-        let url = req.url();
-
-        match req.method() {
-            "GET" => match url.as_str() {
-                APIV1!("/transaction") => {
-                    let banks = self.banks.lock().expect("Mutex");
-                    let transactions = banks.transactions_get();
-                    Ok(Response::json(transactions))
-                }
-                APIV1!("/salary/accept_proj") => {
-                    let mut banks = self.banks.lock().expect("Mutex");
-                    Ok(Response::json(&banks.get_accept_salary_proj()?))
-                }
-                _ => Err(ServerError::NotFound("".into())),
-            },
-
-            "POST" => match url.as_str() {
-                APIV1!("/transaction/revert") => {
-                    let mut banks = self.banks.lock().expect("Mutex");
-                    banks.transaction_revert(params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/salary/accept_proj") => {
-                    let mut banks = self.banks.lock().expect("Mutex");
-                    let req: SalaryAcceptProjRequest = deserialize_request(req)?;
-                    banks.accept_salary_proj(req)?;
-                    Ok(Response::text("Ok"))
-                }
-                _ => Err(ServerError::NotFound("".into())),
-            },
-            _ => Err(ServerError::MethodNotAllowed("".into())),
+    /* Moves time forward a month at a time, updating at every stop,
+     * so month-end processing happens in order. Returns the new time. */
+    pub fn step(&self, req: &TimeStepReq) -> Result<DateTime<Utc>, ServerError> {
+        let _update = self.update_lock.lock().unwrap();
+        let start = self.time.lock().unwrap().get_time();
+        let target = start + chrono::Months::new(req.months) + chrono::Days::new(req.days as u64);
+        let mut month = 1;
+        loop {
+            let stop = (start + chrono::Months::new(month)).min(target);
+            {
+                let mut time = self.time.lock().unwrap();
+                time.set_time(&stop)?;
+                time.record(TimeEventKind::Step, format!("Step to {}", stop.to_rfc3339()), Vec::new());
+            }
+            self.update_at(&stop, true);
+            if stop == target {
+                return Ok(stop);
+            }
+            month += 1;
         }
     }
 
-    pub fn update(&mut self) {
-        let mut banks = self.banks.lock().expect("Mutex");
-        let time_service = self.time.lock().unwrap();
-        let time = time_service.get_time();
-        banks.update(&time);
+    /* Saves the bank system at the current time. Nothing fires meanwhile,
+     * so the state matches the time. */
+    pub fn take_snapshot(&self) -> TimeSnapshot {
+        let _update = self.update_lock.lock().unwrap();
+        let time = self.time.lock().unwrap().get_time();
+        let state = self.banks.snapshot();
+        let snapshot = self.snapshots.lock().unwrap().add(time, state);
+        let message = format!("Snapshot #{} at {}", snapshot.id, time.to_rfc3339());
+        self.time.lock().unwrap().record(TimeEventKind::Snapshot, message, Vec::new());
+        snapshot
     }
 
-    pub fn handle_enterprise_specialist(
-        &mut self,
-        req: &Request,
-        params: &RequestParams,
-    ) -> Result<Response, ServerError> {
-        let url = req.url();
-
-        match req.method() {
-            "GET" => match url.as_str() {
-                APIV1!("/banks") => {
-                    let banks_service = self.banks.lock().expect("Mutex");
-                    let resp = banks_service.banks_get();
-                    Ok(Response::json(&resp))
-                }
-                APIV1!("/account") => {
-                    let banks_service = self.banks.lock().expect("Mutex");
-                    let accounts_resp = banks_service.accounts_get(params)?;
-                    Ok(Response::json(&accounts_resp))
-                }
-                APIV1!("/salary/accept") => {
-                    let bank = self.banks.lock().unwrap();
-                    let resp = bank.salary_accept_decline_get(params)?;
-                    Ok(Response::json(resp))
-                }
-                APIV1!("/salary/proj") => {
-                    let bank = self.banks.lock().unwrap();
-                    let enterprise = self
-                        .auth
-                        .lock()
-                        .unwrap()
-                        .validate_authentification(params.token.unwrap(), ENTERPRISE)
-                        .map_err(|e| ServerError::Forbidden(e.to_string()))?;
-                    let resp = bank.get_salary_proj(enterprise)?;
-                    Ok(Response::json(&resp))
-                }
-                _ => Err(ServerError::NotFound("".into())),
-            },
-
-            "POST" => match url.as_str() {
-                APIV1!("/account/open") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let resp = banks_service.account_open(params)?;
-                    Ok(Response::json(&resp))
-                }
-                APIV1!("/account/close") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let close_req: AccountCloseReq = deserialize_request(req)?;
-                    banks_service.account_close(close_req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/salary/new") => {
-                    let mut bank = self.banks.lock().unwrap();
-                    let req: SalaryInitProjRequest = deserialize_request(req)?;
-                    bank.init_salary_proj(req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/salary/accept") => {
-                    let mut bank = self.banks.lock().unwrap();
-                    let req: SalaryAcceptRequest = deserialize_request(req)?;
-                    bank.salary_accept_decline(req, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                APIV1!("/transaction") => {
-                    let mut banks_service = self.banks.lock().expect("Mutex");
-                    let transaction: Transaction = deserialize_request::<Transaction>(req)?;
-                    banks_service.transaction_unprotected(transaction, params)?;
-                    Ok(Response::text("Ok"))
-                }
-                _ => Err(ServerError::NotFound("".into())),
-            },
-
-            _ => Err(ServerError::MethodNotAllowed(
-                "Method not allowed".to_string(),
-            )),
-        }
+    /* Puts the bank system and time back to a snapshot. Users, sessions and
+     * the event feed are kept. Returns the restored time. */
+    pub fn restore_snapshot(&self, id: SnapshotID) -> Result<DateTime<Utc>, ServerError> {
+        let _update = self.update_lock.lock().unwrap();
+        let (snapshot, state) = self.snapshots.lock().unwrap().get(id)?;
+        self.banks.restore(state);
+        let mut time = self.time.lock().unwrap();
+        time.restore(&snapshot.time);
+        let message = format!("Restored snapshot #{}, time is {}", snapshot.id, snapshot.time.to_rfc3339());
+        time.record(TimeEventKind::Restored, message, Vec::new());
+        Ok(snapshot.time)
     }
+}
 
-    // fn handle_get(&mut self, req: &Request) -> Result<Response, ServerError> {
-    //     let req_params = RequestParams::from(req);
-    //     let auth = self.auth.lock().expect("Mutex error");
-    //     match req.url().as_str() {
-    //
-    //         APIV1!("/auth") => {
-    //             // TODO : auth data for not clients
-    //             let token = req_params
-    //                 .token
-    //                 .ok_or(ServerError::BadRequest("No token".to_string()))?;
-    //             let resp_data = auth
-    //                 .get_user_by_token(token)
-    //                 .ok_or(ServerError::BadRequest("Bad token".to_string()))?;
-    //             Ok(Response::json(&resp_data.public_user))
-    //         }
-    //
-    //         "/auth/accept" => {
-    //             let token = req_params
-    //                 .token
-    //                 .ok_or(ServerError::BadRequest("No token".to_string()))?;
-    //             auth.validate_authentification(token, UserType::Manager)
-    //                 .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
-    //             let registration_requests = &self.auth.get_registration_requests();
-    //             Ok(Response::json(registration_requests))
-    //         }
-    //
-    //         "/deposit" => {
-    //             let token = req_params
-    //                 .token
-    //                 .ok_or(ServerError::BadRequest("No token".to_string()))?;
-    //             auth.validate_authentification(token, UserType::Client)
-    //                 .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
-    //
-    //             let bank_service = self.banks.lock().expect("Mutex error");
-    //             unimplemented!()
-    //         }
-    //         _ => Err(ServerError::NotFound("".to_string())),
-    //     }
-    // }
-    //
-    // fn handle_post(&mut self, req: &Request) -> Result<Response, ServerError> {
-    //     let req_params = RequestParams::from(req);
-    //     if req.url().starts_with("/auth") {
-    //         let mut auth = self.auth.lock().expect("Mutex error");
-    //
-    //         match req.url().as_str() {
-    //             "/auth/register" => {
-    //                 let register_data: RegisterUserReq =
-    //                     deserialize_request(&req).map_err(|_: &str| {
-    //                         ServerError::BadRequest("Invalid registration data".to_string())
-    //                     })?;
-    //                 auth.request_add_user(register_data)
-    //                     .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
-    //                 Ok(Response::text("Ok").with_status_code(200))
-    //             }
-    //
-    //             "/auth/login" => {
-    //                 let login_data: LoginReq = deserialize_request(&req)
-    //                     .map_err(|_: &str| ServerError::BadRequest("Bad request".to_string()))?;
-    //                 let session_info = auth
-    //                     .init_session(login_data)
-    //                     .map_err(|err: &str| ServerError::Forbidden(err.to_string()))?;
-    //                 Ok(Response::json(&session_info))
-    //             }
-    //             "/auth/accept" => {
-    //                 let token = req_params
-    //                     .token
-    //                     .ok_or(ServerError::BadRequest("No token".to_string()))?;
-    //                 auth.validate_authentification(token, UserType::Manager)
-    //                     .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
-    //                 let accept_registration: AcceptRegistrationReq = deserialize_request(&req)
-    //                     .map_err(|_| ServerError::BadRequest("Bad request".to_string()))?;
-    //                 auth.accept_registration_request(&accept_registration)
-    //                     .map_err(|err: _| ServerError::BadRequest(err.to_string()))?;
-    //                 Ok(Response::text("Ok").with_status_code(200))
-    //             }
-    //
-    //             _ => Err(ServerError::NotFound("".to_string())),
-    //         }
-    //     } else {
-    //         let mut bank_service = self.banks.lock().expect("Mutex error");
-    //         let token = req_params
-    //             .token
-    //             .ok_or(ServerError::BadRequest("No token".to_string()))?;
-    //         let auth = self.auth.lock().expect("Mutex error");
-    //
-    //         match req.url().as_str() {
-    //             "/deposit/new" => {
-    //                 auth.validate_authentification(token, UserType::Client)
-    //                     .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
-    //                 let deposit_new_req: DepositNewRequest = deserialize_request(req)
-    //                     .map_err(|_| ServerError::BadRequest("Bad depositr request".to_string()))?;
-    //                 bank_service.deposit_new(deposit_new_req, &req_params)?;
-    //                 Ok(Response::text("Ok"))
-    //             }
-    //
-    //             "/deposit/withdraw" => {
-    //                 unimplemented!()
-    //             }
-    //
-    //             _ => Err(ServerError::NotFound("".to_string())),
-    //         }
-    //     }
-    // }
+/* Everything the server keeps across restarts */
+#[derive(Serialize, Deserialize)]
+struct ServerState {
+    auth: AuthState,
+    banks: BankState,
+    time: TimeState,
+    audit: Vec<AuditEntry>,
+}
+
+fn state_path(dir: &Path) -> PathBuf {
+    dir.join(STATE_FILE)
 }
 
 impl Storable for Server {
-    fn load(&mut self, _dir: &std::path::Path) {
-        ()
+    fn load(&mut self, dir: &Path) -> Result<(), String> {
+        let path = state_path(dir);
+        let file = std::fs::File::open(&path)
+            .map_err(|err| format!("Unable to open {} : {}", path.display(), err))?;
+        let state: ServerState = serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|err| format!("Invalid state in {} : {}", path.display(), err))?;
+        self.auth.lock().expect("Mutex").restore(state.auth);
+        self.banks.load(state.banks);
+        self.time.lock().unwrap().restore_state(state.time);
+        self.audit.lock().unwrap().restore(state.audit);
+        Ok(())
+    }
+
+    /* Written to a temporary file first, so a crash never leaves half a state behind */
+    fn store(&self, dir: &Path) -> Result<(), String> {
+        let state = {
+            // nothing fires meanwhile, so the banks match the time
+            let _update = self.update_lock.lock().unwrap();
+            let auth = self.auth.lock().expect("Mutex").state();
+            ServerState {
+                auth,
+                banks: self.banks.snapshot(),
+                time: self.time.lock().unwrap().state(),
+                audit: self.audit.lock().unwrap().entries().clone(),
+            }
+        };
+        std::fs::create_dir_all(dir)
+            .map_err(|err| format!("Unable to create {} : {}", dir.display(), err))?;
+        let path = state_path(dir);
+        let temporary = path.with_extension("json.tmp");
+        let data = serde_json::to_vec(&state).map_err(|err| format!("Unable to serialize state : {}", err))?;
+        std::fs::write(&temporary, data)
+            .and_then(|_| std::fs::rename(&temporary, &path))
+            .map_err(|err| format!("Unable to write {} : {}", path.display(), err))
     }
-    fn store(&self, _dir: &std::path::Path) {
-        ()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use l1::common::bank::BankPublicInfo;
+
+    fn config(dir: &Path) -> ServerConfig {
+        ServerConfig {
+            data_dir: dir.to_path_buf(),
+            banks: vec![BankPublicInfo { bik: 1, address: String::new(), name: "Bank".to_string() }],
+            ..ServerConfig::default()
+        }
+    }
+
+    #[test]
+    fn stored_state_is_loaded_on_start() {
+        let dir = std::env::temp_dir().join(format!("bank-state-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let server = Server::new(&config(&dir)).unwrap();
+        {
+            let mut bank = server.banks.lock_bank(1).unwrap();
+            let account_id = bank.account_new(&"ivan".to_string()).unwrap();
+            bank.accounts.get_mut(&account_id).unwrap().balance = l1::common::Money(4200);
+        }
+        server.time.lock().unwrap().pause().unwrap();
+        let time = server.time.lock().unwrap().get_time();
+        server.store(&dir).unwrap();
+        server.shutdown().unwrap();
+
+        let loaded = Server::new(&config(&dir)).unwrap();
+        loaded.shutdown().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(loaded.auth.lock().unwrap().user_exists("adm"));
+        let bank = loaded.banks.lock_bank(1).unwrap();
+        assert_eq!(*bank.accounts_get(&"ivan".to_string())[0].balance, 4200);
+        let loaded_time = loaded.time.lock().unwrap();
+        assert!(loaded_time.is_paused());
+        assert_eq!(loaded_time.get_time(), time);
     }
 }
@@ -0,0 +1,33 @@
+use l1::common::audit::*;
+
+/* Append-only log of security relevant events, readable by administrators. */
+#[derive(Default)]
+pub struct AuditService {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditService {
+    pub fn new() -> Self {
+        AuditService {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, event: AuditEvent, subject: &str, details: String) {
+        log::warn!("AUDIT {:?} `{}` : {}", event, subject, details);
+        self.entries.push(AuditEntry {
+            time: chrono::Utc::now(),
+            event,
+            subject: subject.to_string(),
+            details,
+        });
+    }
+
+    pub fn entries(&self) -> &Vec<AuditEntry> {
+        &self.entries
+    }
+
+    pub fn restore(&mut self, entries: Vec<AuditEntry>) {
+        self.entries = entries;
+    }
+}
@@ -1,12 +1,22 @@
 use std::collections::{hash_map::Entry, HashMap};
 
-use crate::user::InternalUser;
+use crate::server::ServerError;
+use crate::services::audit::AuditService;
+use crate::services::throttle::LoginThrottle;
+use crate::services::totp;
+use crate::user::{InternalUser, TwoFactor};
+use l1::common::audit::{AuditEvent, LockoutInfo};
 use l1::common::auth::*;
+use l1::common::bank::BIK;
 use l1::common::user::*;
+use l1::common::permission::{has_permission, Permission};
 use rand::prelude::Rng;
+use serde::{Deserialize, Serialize};
 
 use sha2::Digest;
+use std::net::IpAddr;
 use std::option::Option;
+use std::sync::{Arc, Mutex};
 use std::convert::Into;
 
 
@@ -14,10 +24,136 @@ use log::{error, info};
 
 use std::string::ToString;
 
+const TOTP_ISSUER: &str = "FINARUS";
+const SECOND_FACTOR_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+// roles that can not log in without a second factor
+const TWO_FACTOR_MANDATORY: UserType = MANAGER | OPERATOR | ADMINISTRATOR;
+
+fn two_factor_mandatory(user_type: UserType) -> bool {
+    user_type & TWO_FACTOR_MANDATORY != 0
+}
+
+/* Current TOTP code, each one is accepted once */
+fn verify_totp(two_factor: &mut TwoFactor, code: &str) -> bool {
+    match totp::verify(&two_factor.secret, code, two_factor.last_step) {
+        Some(step) => {
+            two_factor.last_step = Some(step);
+            true
+        }
+        None => false,
+    }
+}
+
+/* Accepts either current TOTP code or one of unused recovery codes.
+ * Used recovery code is burnt. */
+fn verify_second_factor(two_factor: &mut TwoFactor, code: &str) -> bool {
+    if verify_totp(two_factor, code) {
+        return true;
+    }
+    let hash = totp::hash_recovery_code(code);
+    match two_factor.recovery_codes.iter().position(|h| *h == hash) {
+        Some(pos) => {
+            two_factor.recovery_codes.swap_remove(pos);
+            true
+        }
+        None => false,
+    }
+}
+
+/* Replaces recovery codes with new ones. Returns them in plain text,
+ * only their hashes are kept. */
+fn reset_recovery_codes(two_factor: &mut TwoFactor) -> Vec<String> {
+    let codes = totp::generate_recovery_codes();
+    two_factor.recovery_codes = codes.iter().map(|c| totp::hash_recovery_code(c)).collect();
+    codes
+}
+
+const MIN_PASSWORD_LEN: usize = 8;
+const TEMPORARY_PASSWORD_LEN: usize = 12;
+// password of these users may be reset by administrator only
+const STAFF: UserType = MANAGER | OPERATOR | ADMINISTRATOR;
+
+fn hash_password(password: &str) -> String {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(password);
+    format!("{:x}", hasher.finalize())
+}
+
+/* Password policy : long enough, has both letters and digits, is not the login itself */
+fn check_password_strength(login: &str, password: &str, field: &str) -> Result<(), ServerError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(ServerError::field(field, "Password must be at least 8 characters long"));
+    }
+    if !password.chars().any(|c| c.is_alphabetic()) || !password.chars().any(|c| c.is_ascii_digit()) {
+        return Err(ServerError::field(field, "Password must contain both letters and digits"));
+    }
+    if password.to_lowercase().contains(&login.to_lowercase()) {
+        return Err(ServerError::field(field, "Password must not contain the login"));
+    }
+    Ok(())
+}
+
+fn generate_temporary_password() -> String {
+    rand::rng()
+        .sample_iter(&rand::distr::Alphanumeric)
+        .take(TEMPORARY_PASSWORD_LEN)
+        .map(char::from)
+        .collect()
+}
+
+const TOO_MANY_ATTEMPTS: &str = "Too many failed attempts. Try again later";
+// failures before backoff and before lockout
+const LOGIN_FREE_ATTEMPTS: u32 = 2;
+const LOGIN_LOCKOUT_THRESHOLD: u32 = 5;
+// higher, since many clients may share one address
+const ADDRESS_FREE_ATTEMPTS: u32 = 10;
+const ADDRESS_LOCKOUT_THRESHOLD: u32 = 20;
+
+#[derive(PartialEq)]
+enum PendingStage {
+    SecondFactor,
+    PasswordChange,
+}
+
+struct PendingLogin {
+    login: Login,
+    stage: PendingStage,
+    created: std::time::Instant,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RejectedRegistration {
+    // kept so that only the applicant can see the reason
+    password_hash: String,
+    reason: String,
+}
+
+/* Users and requests kept across restarts. Sessions, pending logins and
+ * throttles are not, everyone logs in again after a restart. */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AuthState {
+    users: HashMap<Login, InternalUser>,
+    registration_requests: HashMap<Login, InternalUser>,
+    enterprise_requests: HashMap<Login, Enterprise>,
+    rejected_registrations: HashMap<Login, RejectedRegistration>,
+    enterprises: HashMap<Login, Enterprise>,
+}
+
 pub struct AuthService {
     sessions: HashMap<Token, Login>,
+    // logins waiting for the second factor or a new password, by challenge
+    pending_logins: HashMap<Token, PendingLogin>,
     registration_requests: HashMap<Login, InternalUser>,
+    // enterprises waiting for approval, by login of the responsible specialist
+    enterprise_requests: HashMap<Login, Enterprise>,
+    rejected_registrations: HashMap<Login, RejectedRegistration>,
     users: HashMap<Login, InternalUser>,
+    // approved enterprises by name
+    enterprises: HashMap<Login, Enterprise>,
+    login_throttle: LoginThrottle<Login>,
+    address_throttle: LoginThrottle<IpAddr>,
+    audit: Arc<Mutex<AuditService>>,
 }
 
 #[derive(PartialEq)]
@@ -25,314 +161,811 @@ enum LoginDataStatus {
     InvalidLogin,
     InvalidPassword,
     NotAccepted,
+    Rejected,
     Valid,
 }
-impl Into<&str> for LoginDataStatus {
-    fn into(self) -> &'static str {
-        match self {
-            LoginDataStatus::Valid => "Ok",
-            LoginDataStatus::NotAccepted => "Not accepted",
-            LoginDataStatus::InvalidLogin => "Invalid login",
-            LoginDataStatus::InvalidPassword => "Incalid password",
+impl From<LoginDataStatus> for ServerError {
+    fn from(val: LoginDataStatus) -> Self {
+        match val {
+            LoginDataStatus::Valid => ServerError::InternalError("Valid login treated as error".to_string()),
+            LoginDataStatus::NotAccepted => ServerError::Forbidden("Not accepted".to_string()),
+            LoginDataStatus::Rejected => ServerError::Forbidden("Registration request was rejected".to_string()),
+            // the same message for both, so that existing logins can not be guessed
+            LoginDataStatus::InvalidLogin | LoginDataStatus::InvalidPassword => {
+                ServerError::Unauthorized("Invalid login or password".to_string())
+            }
         }
     }
 }
 
 
-const NAMES : &'static [&'static str]= &[
-  "Kostya",
-  "Max",
-  "Dima",
-  "Artsiom",
-  "Xena",
-  "Diana",
-  "Pavel",
-  "Ivan",
-  "Misha",
-  "Stas",
-  "Andrey",
-  "Sergey",
-  "Petr",
-  "Nikita",
-  "Vladimir",
-  "Oleg",
-  "Denis",
-  "Arseniy",
-  "Egor",
-  "Daniil",
-  "Yaroslav",
-];
-const SURNAMES  : &'static [&'static str]= &[
-  "Byteable",
-  "Verible",
-  "Cromable",
-  "Clangly",
-  "Rustable",
-  "Sandable",
-  "Nandy",
-  "Nory",
-  "Pony",
-  "Sandman",
-  "Silverman",
-  "Goldman",
-  "Platinuman",
-  "Rubyman",
-  "Sapphireman",
-  "Cosmer",
-  "Earther",
-  "Deuterer",
-  "Curable",
-  "Fermenter",
-  "Milkman",
-  "Waterman",
-  "Mailer",
-  "Cretor",
-  "Mentor",
-  "Supitar",
-  "Onkir",
-];
-
 impl AuthService {
-    pub fn new() -> Self {
-        let mut service = AuthService {
+    pub fn new(audit: Arc<Mutex<AuditService>>) -> Self {
+        AuthService {
             sessions: HashMap::new(),
+            pending_logins: HashMap::new(),
             registration_requests: HashMap::new(),
+            enterprise_requests: HashMap::new(),
+            rejected_registrations: HashMap::new(),
             users: HashMap::new(),
-        };
-
-        service._fill();
-
-        service
+            enterprises: HashMap::new(),
+            login_throttle: LoginThrottle::new(LOGIN_FREE_ATTEMPTS, LOGIN_LOCKOUT_THRESHOLD),
+            address_throttle: LoginThrottle::new(ADDRESS_FREE_ATTEMPTS, ADDRESS_LOCKOUT_THRESHOLD),
+            audit,
+        }
     }
 
+    pub fn state(&self) -> AuthState {
+        AuthState {
+            users: self.users.clone(),
+            registration_requests: self.registration_requests.clone(),
+            enterprise_requests: self.enterprise_requests.clone(),
+            rejected_registrations: self.rejected_registrations.clone(),
+            enterprises: self.enterprises.clone(),
+        }
+    }
 
-    pub fn _fill(&mut self){
+    pub fn restore(&mut self, state: AuthState) {
+        self.users = state.users;
+        self.registration_requests = state.registration_requests;
+        self.enterprise_requests = state.enterprise_requests;
+        self.rejected_registrations = state.rejected_registrations;
+        self.enterprises = state.enterprises;
+    }
 
-        let mut hasher = sha2::Sha256::new();
-        hasher.update("mng");
-        let hash = format!("{:x}", hasher.finalize());
+    /* Adds an accepted user, used by the fixture loader */
+    pub fn add_user(
+        &mut self,
+        login: &str,
+        password: &str,
+        user_type: UserType,
+        public_user: UserData,
+        password_change_required: bool,
+        bank: Option<BIK>,
+    ) -> Result<(), ServerError> {
+        if self.users.contains_key(login) {
+            return Err(ServerError::Conflict(format!("User {} already exists", login)));
+        }
+        if let UserData::SpecialistData(specialist) = &public_user {
+            if !self.enterprises.contains_key(&specialist.enterprise) {
+                return Err(ServerError::NotFound(format!(
+                    "Enterprise {} not found",
+                    specialist.enterprise
+                )));
+            }
+        }
         self.users.insert(
-            "mng".to_string(),
+            login.to_string(),
             InternalUser {
-                user_type: MANAGER,
-                login: "mng".to_string(),
-                password_hash: hash,
-                public_user: UserData::None
+                user_type,
+                login: login.to_string(),
+                password_hash: hash_password(password),
+                public_user,
+                two_factor: None,
+                password_change_required,
+                bank,
             },
         );
+        Ok(())
+    }
 
-        let mut hasher = sha2::Sha256::new();
-        hasher.update("opr");
-        let hash = format!("{:x}", hasher.finalize());
-        self.users.insert(
-            "opr".to_string(),
-            InternalUser {
-                user_type: OPERATOR,
-                login : "opr".to_string(),
-                password_hash: hash,
-                public_user : UserData::None
-            }
-        );
-
-        for _ in 0..100 {
-
-            let login : String = rand::rng().sample_iter(&rand::distr::Alphanumeric)
-                .take(4)
-                .map(char::from)
-                .collect();
-
-            let mut hasher = sha2::Sha256::new();
-            hasher.update("123");
-            let hash = format!("{:x}", hasher.finalize());
-            self.users.insert(
-                login.clone(),
-                InternalUser {
-                    user_type: CLIENT,
-                    login : login.clone(),
-                    password_hash: hash,
-                    public_user : UserData::None 
-                }
-            ); 
-            log::info!("User with login {} created", login);
+    /* Adds an approved enterprise, used by the fixture loader */
+    pub fn add_enterprise(&mut self, enterprise: Enterprise) -> Result<(), ServerError> {
+        if self.enterprises.contains_key(&enterprise.name) || self.unp_taken(&enterprise.unp) {
+            return Err(ServerError::Conflict(format!(
+                "Enterprise {} already exists",
+                enterprise.name
+            )));
         }
+        self.enterprises.insert(enterprise.name.clone(), enterprise);
+        Ok(())
+    }
 
+    pub fn user_exists(&self, login: &str) -> bool {
+        self.users.contains_key(login)
+    }
 
-        let mut hasher = sha2::Sha256::new();
-        hasher.update("123");
-        let hash = format!("{:x}", hasher.finalize());
-        self.users.insert(
-            "BSUIR".to_string(),
-            InternalUser {
-                user_type: ENTERPRISE,
-                login : "BSUIR".to_string(),
-                password_hash: hash,
-                public_user : UserData::None
-            }
-        );
-
-        let mut hasher = sha2::Sha256::new();
-        hasher.update("123");
-        let hash = format!("{:x}", hasher.finalize());
-        self.users.insert(
-            "Aston".to_string(),
-            InternalUser {
-                user_type: ENTERPRISE,
-                login : "Aston".to_string(),
-                password_hash: hash,
-                public_user : UserData::None
-            }
-        );
-
-        let mut hasher = sha2::Sha256::new();
-        hasher.update("123");
-        let hash = format!("{:x}", hasher.finalize());
+    /* Without any administrator nobody could manage the bank,
+     * so one is created with a temporary password. Returns the login and the password. */
+    pub fn bootstrap_administrator(&mut self) -> Option<(Login, String)> {
+        if self.users.values().any(|usr| usr.user_type & ADMINISTRATOR != 0) {
+            return None;
+        }
+        // `adm` may be taken by a user of the fixture
+        let login = std::iter::once("adm".to_string())
+            .chain((1..).map(|idx| format!("adm{}", idx)))
+            .find(|login| !self.name_taken(login))
+            .unwrap();
+        let password = generate_temporary_password();
         self.users.insert(
-            "Innowise".to_string(),
+            login.clone(),
             InternalUser {
-                user_type: ENTERPRISE,
-                login : "Innowise".to_string(),
-                password_hash: hash,
-                public_user : UserData::None
-            }
+                user_type: ADMINISTRATOR,
+                login: login.clone(),
+                password_hash: hash_password(&password),
+                public_user: UserData::None,
+                two_factor: None,
+                password_change_required: true,
+                bank: None,
+            },
         );
+        Some((login, password))
+    }
 
-
+    /* The bank a staff member acts for. Operators and managers are bound to
+     * their own bank, `requested` may only repeat it. Administrators pick any. */
+    pub fn staff_bank(&self, login: &str, requested: Option<BIK>) -> Result<BIK, ServerError> {
+        let usr = self
+            .users
+            .get(login)
+            .ok_or(ServerError::Unauthorized("No such user".to_string()))?;
+        if usr.user_type & ADMINISTRATOR != 0 {
+            return requested.ok_or(ServerError::BadRequest("No bank".to_string()));
+        }
+        let bank = usr
+            .bank
+            .ok_or(ServerError::Forbidden("You are not assigned to a bank".to_string()))?;
+        match requested {
+            Some(bik) if bik != bank => Err(ServerError::Forbidden(format!(
+                "You may only act for bank {}",
+                bank
+            ))),
+            _ => Ok(bank),
+        }
     }
 
-    pub fn validate_authentification(&self, token: Token, role: UserType) -> Result<Login, String> {
+    pub fn validate_permission(&self, token: Token, permission: Permission) -> Result<Login, ServerError> {
         let usr = self
             .get_user_by_token(token)
-            .ok_or("No session with given token".to_string())?;
-        if (usr.user_type & role) == 0 {
-            Err("Permission denied".to_string())
-        } else {
+            .ok_or(ServerError::Unauthorized("No session with given token".to_string()))?;
+        if has_permission(usr.user_type, permission) {
             Ok(usr.login.clone())
+        } else {
+            Err(ServerError::Forbidden(format!("Permission denied : `{}` required", permission)))
         }
     }
 
+    /* Login the user acts for : the enterprise for its specialists, the user itself otherwise */
+    pub fn principal(&self, login: &Login) -> Login {
+        match self.users.get(login).map(|usr| &usr.public_user) {
+            Some(UserData::SpecialistData(specialist)) => specialist.enterprise.clone(),
+            _ => login.clone(),
+        }
+    }
+
+    pub fn auth_info(&self, token: Token) -> Option<AuthInfoResp> {
+        let usr = self.get_user_by_token(token)?;
+        let enterprise = match &usr.public_user {
+            UserData::SpecialistData(specialist) => self.enterprises.get(&specialist.enterprise).cloned(),
+            _ => None,
+        };
+        Some(AuthInfoResp {
+            login: usr.login.clone(),
+            user_type: usr.user_type,
+            user_data: usr.public_user.clone(),
+            enterprise,
+        })
+    }
+
     fn validate_login_data(&self, login_data: &LoginReq) -> LoginDataStatus {
-        let usr_opt = self.users.get(&login_data.login);
-        match usr_opt {
-            Some(usr)=>{
-                let mut hasher = sha2::Sha256::new();
-                hasher.update(login_data.password.as_str());
-                let hash = hasher.finalize();
-                if format!("{:x}", hash) == usr.password_hash {
-                    LoginDataStatus::Valid
-                } else {
-                    LoginDataStatus::InvalidPassword
-                }
+        let hash = hash_password(&login_data.password);
+
+        match self.users.get(&login_data.login) {
+            Some(usr) if usr.password_hash == hash => LoginDataStatus::Valid,
+            Some(_) => LoginDataStatus::InvalidPassword,
+            None => match self.registration_requests.get(&login_data.login) {
+                // only the one who knows the password learns the request is pending
+                Some(usr) if usr.password_hash == hash => LoginDataStatus::NotAccepted,
+                _ => match self.rejected_registrations.get(&login_data.login) {
+                    Some(rejected) if rejected.password_hash == hash => LoginDataStatus::Rejected,
+                    _ => LoginDataStatus::InvalidLogin,
+                },
+            },
+        }
+    }
+
+    fn check_throttle(&mut self, login: &Login, address: IpAddr) -> Result<(), ServerError> {
+        let now = chrono::Utc::now();
+        self.login_throttle
+            .check(login, now)
+            .and(self.address_throttle.check(&address, now))
+            .map_err(|_| ServerError::TooManyRequests(TOO_MANY_ATTEMPTS.to_string()))
+    }
+
+    fn login_failed(&mut self, login: &Login, address: IpAddr) {
+        let now = chrono::Utc::now();
+        let mut audit = self.audit.lock().expect("Mutex");
+        if let Some(locked_until) = self.login_throttle.record_failure(login, now) {
+            audit.record(
+                AuditEvent::LoginLocked,
+                login,
+                format!("Too many failed logins, last one from {}. Locked until {}", address, locked_until),
+            );
+        }
+        if let Some(locked_until) = self.address_throttle.record_failure(&address, now) {
+            audit.record(
+                AuditEvent::AddressLocked,
+                &address.to_string(),
+                format!("Too many failed logins, last one as `{}`. Locked until {}", login, locked_until),
+            );
+        }
+    }
+
+    pub fn lockouts(&self) -> Vec<LockoutInfo> {
+        let now = chrono::Utc::now();
+        let logins = self
+            .login_throttle
+            .lockouts(now)
+            .into_iter()
+            .map(|(login, failures, locked_until)| LockoutInfo {
+                subject: login,
+                failures,
+                locked_until,
+            });
+        let addresses = self
+            .address_throttle
+            .lockouts(now)
+            .into_iter()
+            .map(|(address, failures, locked_until)| LockoutInfo {
+                subject: address.to_string(),
+                failures,
+                locked_until,
+            });
+        logins.chain(addresses).collect()
+    }
+
+    fn create_session(&mut self, login: &Login) -> Result<SessionResponse, ServerError> {
+        let mut rnd = rand::rng();
+        let new_token = rnd.random::<u64>();
+        match self.sessions.entry(new_token) {
+            Entry::Vacant(entry) => {
+                entry.insert(login.clone());
+                let user_type = self.users.get(login).unwrap().user_type;
+                info!(
+                    "User `{}` initiated a session. Token : {}",
+                    login.as_str(),
+                    new_token
+                );
+                Ok(SessionResponse {
+                    token: new_token,
+                    user_type,
+                })
             }
-            None => {
-                if self.registration_requests.contains_key(&login_data.login){
-                    LoginDataStatus::NotAccepted
-                } else {
-                    LoginDataStatus::InvalidLogin
-                }
+            Entry::Occupied(_) => {
+                error!("You are really lucky! This token already exists!");
+                Err(ServerError::InternalError("Token already exists. Just try again.".to_string()))
             }
         }
     }
 
-    pub fn init_session(&mut self, login_data: LoginReq) -> Result<SessionResponse, &str> {
+    fn create_challenge(&mut self, login: &Login, stage: PendingStage) -> Token {
+        let now = std::time::Instant::now();
+        self.pending_logins
+            .retain(|_, pending| now.duration_since(pending.created) < SECOND_FACTOR_TIMEOUT);
+
+        let challenge = rand::rng().random::<u64>();
+        self.pending_logins.insert(
+            challenge,
+            PendingLogin {
+                login: login.clone(),
+                stage,
+                created: now,
+            },
+        );
+        challenge
+    }
+
+    pub fn init_session(&mut self, login_data: LoginReq, address: IpAddr) -> Result<LoginResponse, ServerError> {
+        self.check_throttle(&login_data.login, address)?;
+
         let login_data_status = self.validate_login_data(&login_data);
-    
-        if login_data_status == LoginDataStatus::Valid {
-            let mut rnd = rand::rng();
-            let new_token = rnd.random::<u64>();
-            match self.sessions.entry(new_token) {
-                Entry::Vacant(entry) => {
-                    entry.insert(login_data.login.clone());
-                    let user_type = self.users.get(&login_data.login).unwrap().user_type;
-                    info!(
-                        "User `{}` initiated a session. Token : {}",
-                        login_data.login.as_str(),
-                        new_token
-                    );
-                    Ok(SessionResponse {
-                        token: new_token,
-                        user_type: user_type,
-                    })
-                }
-                Entry::Occupied(_) => {
-                    error!("You are really lucky! This token already exists!");
-                    Err("Token already exists. Just try again.")
-                }
+        if login_data_status != LoginDataStatus::Valid {
+            if login_data_status == LoginDataStatus::InvalidLogin
+                || login_data_status == LoginDataStatus::InvalidPassword
+            {
+                self.login_failed(&login_data.login, address);
             }
+            return Err(login_data_status.into());
+        }
+
+        let login = login_data.login;
+        self.login_throttle.reset(&login);
+        if self.users[&login].password_change_required {
+            let challenge = self.create_challenge(&login, PendingStage::PasswordChange);
+            info!("User `{}` logged in with temporary password, waiting for a new one", login);
+            return Ok(LoginResponse::PasswordChangeRequired { challenge });
+        }
+        self.continue_login(login)
+    }
+
+    /* Login steps after the password is checked : second factor, if any, or a session */
+    fn continue_login(&mut self, login: Login) -> Result<LoginResponse, ServerError> {
+        let user = self.users.get_mut(&login).expect("Login validated");
+        let two_factor_enabled = user.two_factor.as_ref().is_some_and(|tf| tf.enabled);
+
+        if two_factor_enabled {
+            let challenge = self.create_challenge(&login, PendingStage::SecondFactor);
+            info!("User `{}` passed password check, waiting for TOTP code", login);
+            Ok(LoginResponse::SecondFactorRequired { challenge })
+        } else if two_factor_mandatory(user.user_type) {
+            let secret = &user
+                .two_factor
+                .get_or_insert_with(|| TwoFactor {
+                    secret: totp::generate_secret(),
+                    enabled: false,
+                    recovery_codes: Vec::new(),
+                    last_step: None,
+                })
+                .secret;
+            let enrollment = TotpEnrollResponse {
+                secret: totp::base32_encode(secret),
+                otpauth_uri: totp::otpauth_uri(TOTP_ISSUER, &login, secret),
+            };
+            let challenge = self.create_challenge(&login, PendingStage::SecondFactor);
+            info!("User `{}` has to enroll into two-factor authentication", login);
+            Ok(LoginResponse::EnrollmentRequired {
+                challenge,
+                enrollment,
+            })
         } else {
-            Err(login_data_status.into())
+            Ok(LoginResponse::Session(self.create_session(&login)?))
         }
     }
 
+    pub fn complete_second_factor(
+        &mut self,
+        req: SecondFactorReq,
+        address: IpAddr,
+    ) -> Result<SecondFactorResponse, ServerError> {
+        let pending = self
+            .pending_logins
+            .remove(&req.challenge)
+            .ok_or(ServerError::Unauthorized("No login in progress for this challenge".to_string()))?;
+        if pending.created.elapsed() >= SECOND_FACTOR_TIMEOUT {
+            return Err(ServerError::Unauthorized("Login expired. Please log in again".to_string()));
+        }
+        if pending.stage != PendingStage::SecondFactor {
+            self.pending_logins.insert(req.challenge, pending);
+            return Err(ServerError::Conflict("New password is expected for this challenge".to_string()));
+        }
+        if let Err(err) = self.check_throttle(&pending.login, address) {
+            self.pending_logins.insert(req.challenge, pending);
+            return Err(err);
+        }
+
+        // the user may have disabled the second factor since the challenge was given
+        let Some(two_factor) = self
+            .users
+            .get_mut(&pending.login)
+            .and_then(|user| user.two_factor.as_mut())
+        else {
+            return Err(ServerError::Unauthorized("Login expired. Please log in again".to_string()));
+        };
+
+        let mut recovery_codes = Vec::new();
+        let verified = if two_factor.enabled {
+            verify_second_factor(two_factor, &req.code)
+        } else if verify_totp(two_factor, &req.code) {
+            two_factor.enabled = true;
+            recovery_codes = reset_recovery_codes(two_factor);
+            info!("User `{}` enrolled into two-factor authentication", pending.login);
+            true
+        } else {
+            false
+        };
+
+        if !verified {
+            info!("Wrong second factor for user `{}`", pending.login);
+            self.login_failed(&pending.login, address);
+            self.pending_logins.insert(req.challenge, pending);
+            return Err(ServerError::Unauthorized("Invalid code".to_string()));
+        }
+
+        self.login_throttle.reset(&pending.login);
+        Ok(SecondFactorResponse {
+            session: self.create_session(&pending.login)?,
+            recovery_codes,
+        })
+    }
+
+    pub fn complete_password_change(&mut self, req: NewPasswordReq) -> Result<LoginResponse, ServerError> {
+        let pending = self
+            .pending_logins
+            .remove(&req.challenge)
+            .ok_or(ServerError::Unauthorized("No login in progress for this challenge".to_string()))?;
+        if pending.created.elapsed() >= SECOND_FACTOR_TIMEOUT {
+            return Err(ServerError::Unauthorized("Login expired. Please log in again".to_string()));
+        }
+        if pending.stage != PendingStage::PasswordChange {
+            self.pending_logins.insert(req.challenge, pending);
+            return Err(ServerError::Conflict("Second factor is expected for this challenge".to_string()));
+        }
+        if let Err(err) = self.set_password(&pending.login, &req.new_password) {
+            self.pending_logins.insert(req.challenge, pending);
+            return Err(err);
+        }
+        self.continue_login(pending.login)
+    }
+
+    /* Ends every session and unfinished login of the user */
+    fn revoke_sessions(&mut self, login: &Login) {
+        self.sessions.retain(|_, session_login| session_login != login);
+        self.pending_logins.retain(|_, pending| &pending.login != login);
+    }
+
+    fn set_password(&mut self, login: &Login, new_password: &str) -> Result<(), ServerError> {
+        check_password_strength(login, new_password, "new_password")?;
+        let user = self.users.get_mut(login).ok_or(ServerError::NotFound("User not found".to_string()))?;
+        let hash = hash_password(new_password);
+        if user.password_hash == hash {
+            return Err(ServerError::field("new_password", "New password must differ from the old one"));
+        }
+        user.password_hash = hash;
+        user.password_change_required = false;
+        self.revoke_sessions(login);
+        self.audit.lock().expect("Mutex").record(
+            AuditEvent::PasswordChanged,
+            login,
+            "Password changed, all sessions revoked".to_string(),
+        );
+        Ok(())
+    }
+
+    pub fn change_password(
+        &mut self,
+        login: &Login,
+        req: PasswordChangeReq,
+        address: IpAddr,
+    ) -> Result<(), ServerError> {
+        self.check_throttle(login, address)?;
+        let user = self.users.get(login).ok_or(ServerError::NotFound("User not found".to_string()))?;
+        if user.password_hash != hash_password(&req.old_password) {
+            self.login_failed(login, address);
+            return Err(ServerError::Forbidden("Invalid password".to_string()));
+        }
+        self.set_password(login, &req.new_password)
+    }
+
+    /* Issues a temporary password, which has to be changed at the next login */
+    pub fn reset_password(
+        &mut self,
+        staff_login: &Login,
+        req: PasswordResetReq,
+    ) -> Result<PasswordResetResponse, ServerError> {
+        if &req.login == staff_login {
+            return Err(ServerError::Forbidden("Use password change to change your own password".to_string()));
+        }
+        let staff_type = self.users.get(staff_login).ok_or(ServerError::NotFound("User not found".to_string()))?.user_type;
+        let user = self
+            .users
+            .get_mut(&req.login)
+            .ok_or(ServerError::NotFound("No user with given login".to_string()))?;
+        if user.user_type & STAFF != 0 && staff_type & ADMINISTRATOR == 0 {
+            return Err(ServerError::Forbidden("Only administrator can reset passwords of bank staff".to_string()));
+        }
+
+        let temporary_password = generate_temporary_password();
+        user.password_hash = hash_password(&temporary_password);
+        user.password_change_required = true;
+        self.revoke_sessions(&req.login);
+        // the owner's identity was verified by the staff, let them in
+        self.login_throttle.reset(&req.login);
+        self.audit.lock().expect("Mutex").record(
+            AuditEvent::PasswordReset,
+            &req.login,
+            format!("Temporary password issued by `{}`, all sessions revoked", staff_login),
+        );
+        Ok(PasswordResetResponse {
+            login: req.login,
+            temporary_password,
+        })
+    }
+
+    pub fn totp_enroll(&mut self, login: &Login) -> Result<TotpEnrollResponse, ServerError> {
+        let user = self.users.get_mut(login).ok_or(ServerError::NotFound("User not found".to_string()))?;
+        if user.two_factor.as_ref().is_some_and(|tf| tf.enabled) {
+            return Err(ServerError::Conflict("Two-factor authentication is already enabled".to_string()));
+        }
+
+        let secret = totp::generate_secret();
+        let enrollment = TotpEnrollResponse {
+            secret: totp::base32_encode(&secret),
+            otpauth_uri: totp::otpauth_uri(TOTP_ISSUER, login, &secret),
+        };
+        user.two_factor = Some(TwoFactor {
+            secret,
+            enabled: false,
+            recovery_codes: Vec::new(),
+            last_step: None,
+        });
+        // logins waiting for a code of the old secret
+        self.drop_second_factor_challenges(login);
+        Ok(enrollment)
+    }
+
+    pub fn totp_confirm(&mut self, login: &Login, code: &str) -> Result<RecoveryCodesResponse, ServerError> {
+        let user = self.users.get_mut(login).ok_or(ServerError::NotFound("User not found".to_string()))?;
+        let two_factor = user
+            .two_factor
+            .as_mut()
+            .ok_or(ServerError::Conflict("Two-factor authentication enrollment was not started".to_string()))?;
+        if two_factor.enabled {
+            return Err(ServerError::Conflict("Two-factor authentication is already enabled".to_string()));
+        }
+        if !verify_totp(two_factor, code) {
+            return Err(ServerError::Forbidden("Invalid code".to_string()));
+        }
+
+        two_factor.enabled = true;
+        info!("User `{}` enabled two-factor authentication", login);
+        Ok(RecoveryCodesResponse {
+            recovery_codes: reset_recovery_codes(two_factor),
+        })
+    }
+
+    pub fn totp_disable(&mut self, login: &Login, code: &str) -> Result<(), ServerError> {
+        let user = self.users.get_mut(login).ok_or(ServerError::NotFound("User not found".to_string()))?;
+        if two_factor_mandatory(user.user_type) {
+            return Err(ServerError::Forbidden("Two-factor authentication is mandatory for staff".to_string()));
+        }
+        let two_factor = user
+            .two_factor
+            .as_mut()
+            .filter(|tf| tf.enabled)
+            .ok_or(ServerError::Conflict("Two-factor authentication is not enabled".to_string()))?;
+        if !verify_second_factor(two_factor, code) {
+            return Err(ServerError::Forbidden("Invalid code".to_string()));
+        }
+
+        user.two_factor = None;
+        self.drop_second_factor_challenges(login);
+        info!("User `{}` disabled two-factor authentication", login);
+        Ok(())
+    }
+
+    fn drop_second_factor_challenges(&mut self, login: &Login) {
+        self.pending_logins
+            .retain(|_, pending| !(pending.login == *login && pending.stage == PendingStage::SecondFactor));
+    }
+
+    pub fn totp_recovery_codes(&mut self, login: &Login, code: &str) -> Result<RecoveryCodesResponse, ServerError> {
+        let user = self.users.get_mut(login).ok_or(ServerError::NotFound("User not found".to_string()))?;
+        let two_factor = user
+            .two_factor
+            .as_mut()
+            .filter(|tf| tf.enabled)
+            .ok_or(ServerError::Conflict("Two-factor authentication is not enabled".to_string()))?;
+        if !verify_totp(two_factor, code) {
+            return Err(ServerError::Forbidden("Invalid code".to_string()));
+        }
+        Ok(RecoveryCodesResponse {
+            recovery_codes: reset_recovery_codes(two_factor),
+        })
+    }
+
     pub fn get_user_by_token(&self, token: Token) -> Option<&InternalUser> {
         self.sessions
-            .get(&token)
-            .map_or(None, |login: &String| Some(self.users.get(login).unwrap()))
+            .get(&token).map(|login: &String| self.users.get(login).unwrap())
+    }
+
+    /* Whether some client, registered or waiting for review, already has the passport */
+    fn passport_taken(&self, passport: &UserPassportData) -> bool {
+        self.users
+            .values()
+            .chain(self.registration_requests.values())
+            .any(|usr| match &usr.public_user {
+                UserData::ClientData(client) => {
+                    client.passport.series.eq_ignore_ascii_case(&passport.series)
+                        && client.passport.number == passport.number
+                }
+                _ => false,
+            })
+    }
+
+    /* Logins and enterprise names share one namespace, since both own bank accounts */
+    fn name_taken(&self, name: &str) -> bool {
+        self.users.contains_key(name)
+            || self.registration_requests.contains_key(name)
+            || self.enterprises.contains_key(name)
+            || self.enterprise_requests.values().any(|enterprise| enterprise.name == name)
+    }
+
+    fn unp_taken(&self, unp: &str) -> bool {
+        self.enterprises
+            .values()
+            .chain(self.enterprise_requests.values())
+            .any(|enterprise| enterprise.unp == unp)
     }
 
     pub fn request_add_user(
         &mut self,
         user: l1::common::auth::RegisterUserReq,
-    ) -> Result<(), &str> {
-        if self.users.contains_key(&user.login_data.login) {
-            info!(
-                "Attempt to register another user with login {}",
-                user.login_data.login.as_str()
-            );
-            Err("This login already exists")
-        } else {
-            let mut hasher = sha2::Sha256::new();
-            hasher.update(&user.login_data.password);
-            let hash = format!("{:x}", hasher.finalize());
-            let internal_user = InternalUser {
-                user_type: CLIENT, // by default only client is manually registered.
-                login: user.login_data.password,
-                password_hash: hash,
-                public_user: UserData::ClientData(user.user_data),
-            };
-            info!(
-                "Requested to add new user with login {}",
-                internal_user.login.as_str()
-            );
-            self.registration_requests
-                .insert(internal_user.login.clone(), internal_user);
-            Ok(())
+    ) -> Result<(), ServerError> {
+        let login = user.login_data.login;
+        check_password_strength(&login, &user.login_data.password, "login_data.password")?;
+
+        if self.name_taken(&login) {
+            info!("Attempt to register another user with login {}", login);
+            return Err(ServerError::Conflict("This login already exists".to_string()));
+        }
+        if self.passport_taken(&user.user_data.passport) {
+            info!("Attempt to register user `{}` with already used passport", login);
+            return Err(ServerError::Conflict("Client with this passport is already registered".to_string()));
         }
+
+        let internal_user = InternalUser {
+            user_type: CLIENT, // by default only client is manually registered.
+            login: login.clone(),
+            password_hash: hash_password(&user.login_data.password),
+            public_user: UserData::ClientData(user.user_data),
+            two_factor: None,
+            password_change_required: false,
+            bank: None,
+        };
+        info!("Requested to add new user with login {}", login);
+        // applying again after rejection
+        self.rejected_registrations.remove(&login);
+        self.registration_requests.insert(login, internal_user);
+        Ok(())
     }
 
-    pub fn get_registration_requests(&self) -> Vec<GetRegistrationsReq> {
-        // (Login, InternalUser) => (Login, User)
-        self.registration_requests.iter().map(|kv : _|{
-            if let UserData::ClientData(client) = &kv.1.public_user {
-                GetRegistrationsReq {
-                    login : kv.0.clone(),
-                    user : client.clone()
+    pub fn request_add_enterprise(&mut self, req: RegisterEnterpriseReq) -> Result<(), ServerError> {
+        let login = req.login_data.login;
+        check_password_strength(&login, &req.login_data.password, "login_data.password")?;
+
+        if self.name_taken(&login) {
+            info!("Attempt to register another user with login {}", login);
+            return Err(ServerError::Conflict("This login already exists".to_string()));
+        }
+        if self.name_taken(&req.enterprise.name) {
+            info!("Attempt to register another enterprise with name {}", req.enterprise.name);
+            return Err(ServerError::Conflict("Enterprise with this name already exists".to_string()));
+        }
+        if self.unp_taken(&req.enterprise.unp) {
+            info!("Attempt to register another enterprise with UNP {}", req.enterprise.unp);
+            return Err(ServerError::Conflict("Enterprise with this UNP is already registered".to_string()));
+        }
+
+        let internal_user = InternalUser {
+            user_type: ENTERPRISE,
+            login: login.clone(),
+            password_hash: hash_password(&req.login_data.password),
+            public_user: UserData::SpecialistData(Specialist {
+                enterprise: req.enterprise.name.clone(),
+                full_name: req.full_name,
+                position: req.position,
+            }),
+            two_factor: None,
+            password_change_required: false,
+            bank: None,
+        };
+        info!(
+            "Requested to add new enterprise {} with specialist login {}",
+            req.enterprise.name, login
+        );
+        self.rejected_registrations.remove(&login);
+        self.enterprise_requests.insert(login.clone(), req.enterprise);
+        self.registration_requests.insert(login, internal_user);
+        Ok(())
+    }
+
+    /* Specialists added by a colleague need no approval, they set their own password at first login */
+    pub fn add_specialist(&mut self, colleague: &Login, req: AddSpecialistReq) -> Result<(), ServerError> {
+        let login = req.login_data.login;
+        check_password_strength(&login, &req.login_data.password, "login_data.password")?;
+        if self.name_taken(&login) {
+            return Err(ServerError::Conflict("This login already exists".to_string()));
+        }
+        let enterprise = self.principal(colleague);
+        let internal_user = InternalUser {
+            user_type: ENTERPRISE,
+            login: login.clone(),
+            password_hash: hash_password(&req.login_data.password),
+            public_user: UserData::SpecialistData(Specialist {
+                enterprise: enterprise.clone(),
+                full_name: req.full_name,
+                position: req.position,
+            }),
+            two_factor: None,
+            password_change_required: true,
+            bank: None,
+        };
+        info!("Specialist `{}` of {} added by `{}`", login, enterprise, colleague);
+        self.users.insert(login, internal_user);
+        Ok(())
+    }
+
+    pub fn specialists(&self, colleague: &Login) -> Vec<SpecialistResp> {
+        let enterprise = self.principal(colleague);
+        self.users
+            .values()
+            .filter_map(|usr| match &usr.public_user {
+                UserData::SpecialistData(specialist) if specialist.enterprise == enterprise => {
+                    Some(SpecialistResp {
+                        login: usr.login.clone(),
+                        specialist: specialist.clone(),
+                    })
                 }
-            } else {
-                panic!("Special user shouldn't be in registration requests");
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /* Users and enterprises waiting for approval */
+    pub fn pending_registration_counts(&self) -> (usize, usize) {
+        (self.registration_requests.len(), self.enterprise_requests.len())
+    }
+
+    pub fn get_registration_requests(&self) -> Vec<GetRegistrationsReq> {
+        self.registration_requests.iter().map(|(login, usr)| {
+            let data = match &usr.public_user {
+                UserData::ClientData(client) => RegistrationData::Client(client.clone()),
+                UserData::SpecialistData(specialist) => RegistrationData::Enterprise {
+                    enterprise: self.enterprise_requests[login].clone(),
+                    specialist: specialist.clone(),
+                },
+                _ => panic!("Special user shouldn't be in registration requests"),
+            };
+            GetRegistrationsReq {
+                login : login.clone(),
+                data,
             }
         }).collect()
     }
 
-    pub fn accept_registration_request(&mut self, req: &AcceptRegistrationReq) -> Result<(), &str> {
+    pub fn accept_registration_request(&mut self, req: &AcceptRegistrationReq) -> Result<(), ServerError> {
         let user = self
             .registration_requests
             .remove(&req.login)
-            .ok_or("No registration requests with given login")?;
+            .ok_or(ServerError::NotFound("No registration requests with given login".to_string()))?;
+        info!("Registration of `{}` accepted", req.login);
+        if let Some(enterprise) = self.enterprise_requests.remove(&req.login) {
+            info!("Enterprise {} registered", enterprise.name);
+            self.enterprises.insert(enterprise.name.clone(), enterprise);
+        }
         self.users.insert(req.login.clone(), user);
         Ok(())
     }
 
-    fn validate_user(&self, token: Token, desired_role: UserType) -> Result<(), &str> {
-        let login = self.sessions.get(&token).ok_or("Invalid token")?;
+    pub fn reject_registration_request(&mut self, req: RejectRegistrationReq) -> Result<(), ServerError> {
         let user = self
-            .users
-            .get(login)
-            .expect("Session initiated, but user does not exist");
-        if user.user_type == desired_role {
-            Ok(())
-        } else {
-            Err("Operation not permitted")
-        }
+            .registration_requests
+            .remove(&req.login)
+            .ok_or(ServerError::NotFound("No registration requests with given login".to_string()))?;
+        info!("Registration of `{}` rejected : {}", req.login, req.reason);
+        self.enterprise_requests.remove(&req.login);
+        self.rejected_registrations.insert(
+            req.login,
+            RejectedRegistration {
+                password_hash: user.password_hash,
+                reason: req.reason,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn registration_status(
+        &mut self,
+        login_data: LoginReq,
+        address: IpAddr,
+    ) -> Result<RegistrationStatus, ServerError> {
+        self.check_throttle(&login_data.login, address)?;
+        let status = match self.validate_login_data(&login_data) {
+            LoginDataStatus::Valid => RegistrationStatus::Accepted,
+            LoginDataStatus::NotAccepted => RegistrationStatus::Pending,
+            LoginDataStatus::Rejected => RegistrationStatus::Rejected {
+                reason: self.rejected_registrations[&login_data.login].reason.clone(),
+            },
+            status => {
+                self.login_failed(&login_data.login, address);
+                return Err(status.into());
+            }
+        };
+        Ok(status)
     }
 
 }
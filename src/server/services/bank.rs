@@ -3,43 +3,154 @@ use crate::server::RequestParams;
 use crate::server::ServerError;
 use crate::services::auth::AuthService;
 use crate::services::salary::SalaryService;
+use crate::services::standing_order::{record_attempt, StandingOrderService};
+use crate::scheduler::Scheduler;
 use crate::services::time::TimeService;
-use crate::traits::dynamic::Dynamic;
 
 use l1::common::account::*;
-use l1::common::auth::Token;
+use l1::common::auth::Login;
 use l1::common::bank::*;
 use l1::common::credit::*;
 use l1::common::deposit::*;
+use l1::common::interest::InterestAccrual;
+use l1::common::payment::*;
 use l1::common::salary::*;
-use l1::common::transaction::{Transaction, TransactionEndPoint};
-use l1::common::user::*;
+use l1::common::standing_order::*;
+use l1::common::time::{ScheduledEvent, ScheduledEventKind};
+use l1::common::transaction::{LedgerEntry, Transaction, TransactionEndPoint};
+use l1::common::validate::ValidationErrors;
 
 use l1::common::Money;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/*
+ * Every bank is locked on its own, so requests to different banks run in parallel.
+ * Locks are always taken in this order, and never the other way around :
+ * salary -> standing orders -> banks (ascending BIK) -> ledger.
+ * Auth is never locked while holding the locks above, time locks nothing else.
+ */
 pub struct BankService {
     auth: Arc<Mutex<AuthService>>,
     time: Arc<Mutex<TimeService>>,
+    salary: Mutex<SalaryService>,
+    orders: Mutex<StandingOrderService>,
+
+    // the set of banks is fixed after start, so the map itself needs no lock
+    banks: HashMap<BIK, Mutex<Bank>>,
+    // every booked transaction, entry ids are indexes + 1
+    ledger: Mutex<Vec<LedgerEntry>>,
+}
+
+/* Everything the bank system holds, see `BankService::snapshot` */
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BankState {
     salary: SalaryService,
+    orders: StandingOrderService,
+    banks: BTreeMap<BIK, Bank>,
+    ledger: Vec<LedgerEntry>,
+}
 
-    banks: HashMap<BIK, Bank>,
-    transactions: Vec<Transaction>,
+/* Banks locked for a single operation */
+struct LockedBanks<'a> {
+    guards: BTreeMap<BIK, MutexGuard<'a, Bank>>,
+}
+
+impl LockedBanks<'_> {
+    fn get(&self, bik: BIK) -> Result<&Bank, ServerError> {
+        self.guards
+            .get(&bik)
+            .map(|guard| &**guard)
+            .ok_or(ServerError::NotFound("Bank not found".to_string()))
+    }
+
+    fn get_mut(&mut self, bik: BIK) -> Result<&mut Bank, ServerError> {
+        self.guards
+            .get_mut(&bik)
+            .map(|guard| &mut **guard)
+            .ok_or(ServerError::NotFound("Bank not found".to_string()))
+    }
 }
 
 struct BankRequestContext {
     login: String,
-    token: Token,
     bik: Option<BIK>,
 }
 
-fn credit_monthly_pay(params: &CreditParams) -> Money {
+/* Entries of `account` between `from` and `to` with the balance after each one */
+fn build_statement(
+    account: TransactionEndPoint,
+    current_balance: i32,
+    ledger: &[LedgerEntry],
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Statement {
+    let entries: Vec<&LedgerEntry> = ledger
+        .iter()
+        .filter(|entry| entry.effect_on(&account) != 0)
+        .collect();
+
+    // balances are restored backwards from the current one
+    let after_to: i32 = entries
+        .iter()
+        .filter(|entry| to.is_some_and(|to| entry.time > to))
+        .map(|entry| entry.effect_on(&account))
+        .sum();
+    let in_range: Vec<&&LedgerEntry> = entries
+        .iter()
+        .filter(|entry| from.is_none_or(|from| entry.time >= from))
+        .filter(|entry| to.is_none_or(|to| entry.time <= to))
+        .collect();
+    let closing_balance = current_balance - after_to;
+    let opening_balance =
+        closing_balance - in_range.iter().map(|entry| entry.effect_on(&account)).sum::<i32>();
+
+    let mut balance = opening_balance;
+    let lines = in_range
+        .iter()
+        .map(|entry| {
+            let amount = entry.effect_on(&account);
+            balance += amount;
+            let trans = &entry.transaction;
+            let counterparty = if trans.src == account {
+                trans.dst.clone()
+            } else {
+                trans.src.clone()
+            };
+            StatementLine {
+                entry_id: entry.id,
+                time: entry.time,
+                counterparty,
+                description: entry.description.clone(),
+                amount: Money(amount),
+                balance: Money(balance),
+            }
+        })
+        .collect();
+
+    Statement {
+        bik: account.bik,
+        account_id: account.account_id,
+        from,
+        to,
+        opening_balance: Money(opening_balance),
+        lines,
+        closing_balance: Money(closing_balance),
+    }
+}
+
+/* Annuity payment. Interest actually accrues by days, the last installment pays off the rest. */
+pub fn credit_monthly_pay(params: &CreditParams) -> Money {
     let amount = params.amount;
     let term = params.term as i32;
-    let rate = params.interest_rate as f64 / 100.0;
+    let rate = params.interest_rate as f64 / 100.0 / 12.0;
+    if rate == 0.0 {
+        return Money((*amount + term - 1) / term);
+    }
 
     let res = (*amount as f64) * (rate + rate / ((1.0 + rate).powi(term) - 1.0));
 
@@ -47,199 +158,508 @@ fn credit_monthly_pay(params: &CreditParams) -> Money {
 }
 
 impl BankService {
-    pub fn new(serv: Arc<Mutex<AuthService>>, tm: Arc<Mutex<TimeService>>) -> Self {
-        let mut bs = BankService {
+    pub fn new(
+        serv: Arc<Mutex<AuthService>>,
+        tm: Arc<Mutex<TimeService>>,
+        banks: &[BankPublicInfo],
+    ) -> Self {
+        BankService {
             auth: serv,
             time: tm.clone(),
-            banks: HashMap::new(),
-            transactions: Vec::new(),
-            salary: SalaryService::new(tm.clone()),
-        };
-
-        bs._fill();
-        bs
-    }
-
-
-
-    pub fn _fill(&mut self) {
-        //
-        //TMP
-        self.banks.insert(
-            1003004,
-            Bank::new(BankPublicInfo {
-                name: "BelarusBank".to_string(),
-                bik: 1003004,
-                address: "Nezalezhnasci pr, 4".to_string(),
-            }),
-        );
-        self.banks.insert(
-            1003005,
-            Bank::new(BankPublicInfo {
-                name: "AlphaBank".to_string(),
-                bik: 1003005,
-                address: "Pershamaiski peravulak, 111".to_string(),
-            }),
-        );
-        self.banks.insert(
-            1003006,
-            Bank::new(BankPublicInfo {
-                name: "BelinvestBank".to_string(),
-                bik: 1003006,
-                address: "Masherava pr., 142".to_string(),
-            }),
-        );
-        //TMP
+            banks: banks
+                .iter()
+                .map(|info| (info.bik, Mutex::new(Bank::new(info.clone()))))
+                .collect(),
+            ledger: Mutex::new(Vec::new()),
+            salary: Mutex::new(SalaryService::new(tm.clone())),
+            orders: Mutex::new(StandingOrderService::default()),
+        }
+    }
 
+    /* Banks can only be added before the service is shared */
+    pub fn add_bank(&mut self, info: BankPublicInfo) -> Result<(), ServerError> {
+        if info.bik == 0 || self.banks.contains_key(&info.bik) {
+            return Err(ServerError::Conflict(format!("BIK {} is taken", info.bik)));
+        }
+        self.banks.insert(info.bik, Mutex::new(Bank::new(info)));
+        Ok(())
     }
 
+    /* The router has checked the permission of the route and put the session login into `params` */
     fn get_request_context(
         &self,
         params: &RequestParams,
-        role: UserType,
         bank_required : bool
     ) -> Result<BankRequestContext, ServerError> {
-        let token = params
-            .token
-            .ok_or(ServerError::BadRequest("No token".to_string()))?;
-        let auth = self.auth.lock().expect("Mutex");
-        let login = auth
-            .validate_authentification(token, role)
-            .map_err(|_| ServerError::Forbidden(String::new()))?;
+        // specialists operate the accounts of their enterprise
+        let login = self.auth.lock().expect("Mutex").principal(params.login()?);
         let bik = if bank_required {
      Some(params
             .bik
             .ok_or(ServerError::BadRequest("No bank".to_string()))?)
         } else {None};
-        Ok(BankRequestContext { token, login, bik })
+        Ok(BankRequestContext { login, bik })
+    }
+
+    pub fn has_bank(&self, bik: BIK) -> bool {
+        self.banks.contains_key(&bik)
     }
 
-    fn get_bank_mut(&mut self, bik: BIK) -> Option<&mut Bank> {
-        self.banks.get_mut(&bik)
+    /* Staff act for the bank they are bound to, `bank` of the request is only checked */
+    fn get_staff_context(&self, params: &RequestParams) -> Result<BankRequestContext, ServerError> {
+        let login = params.login()?.clone();
+        let bik = self.auth.lock().expect("Mutex").staff_bank(&login, params.bik)?;
+        Ok(BankRequestContext { login, bik: Some(bik) })
     }
-    fn get_bank(&self, bik: BIK) -> Option<&Bank> {
-        self.banks.get(&bik)
+
+    pub fn lock_bank(&self, bik: BIK) -> Result<MutexGuard<'_, Bank>, ServerError> {
+        Ok(self
+            .banks
+            .get(&bik)
+            .ok_or(ServerError::NotFound("Bank not found".to_string()))?
+            .lock()
+            .expect("Mutex"))
+    }
+
+    /* Locks every bank in ascending BIK order, so two transfers can't deadlock */
+    fn lock_banks(&self, biks: &[BIK]) -> Result<LockedBanks<'_>, ServerError> {
+        // BIK 0 is the bank itself, see `perform_transaction`
+        let biks: BTreeSet<BIK> = biks.iter().copied().filter(|bik| *bik != 0).collect();
+        let mut guards = BTreeMap::new();
+        for bik in biks {
+            guards.insert(bik, self.lock_bank(bik)?);
+        }
+        Ok(LockedBanks { guards })
     }
 
-    /* Performs transaction WITHOUT CHECKING AUTHENTIFICATION */
+    /* Performs transaction WITHOUT CHECKING AUTHENTIFICATION, booked at `time` */
     fn perform_transaction(
-        &mut self,
+        &self,
+        transaction: Transaction,
+        check_balance: bool,
+        description: &str,
+        time: &DateTime<Utc>,
+    ) -> Result<(), ServerError> {
+        let mut banks = self.lock_banks(&[transaction.src.bik, transaction.dst.bik])?;
+        self.apply_transaction(&mut banks, transaction, check_balance, description, time)
+    }
+
+    /* Same as `perform_transaction`, for callers already holding the banks involved */
+    fn apply_transaction(
+        &self,
+        banks: &mut LockedBanks,
         transaction: Transaction,
         check_balance: bool,
-    ) -> Result<(), &str> {
+        description: &str,
+        time: &DateTime<Utc>,
+    ) -> Result<(), ServerError> {
         if transaction.src.account_id == transaction.dst.account_id
             && transaction.src.bik == transaction.dst.bik
         {
-            return Err("Source and destination are the same");
+            return Err(ServerError::field("dst", "Source and destination are the same"));
         }
         if transaction.amount <= Money(0) {
-            return Err("Invalid amount");
+            return Err(ServerError::field("amount", "Invalid amount"));
         }
         // account_id=0 is dumb account, used for client-bank transactions
+        // both ends are checked before any balance changes
         if transaction.src.account_id != 0 {
-            let src_bank = self
-                .banks
-                .get_mut(&transaction.src.bik)
-                .ok_or("Invalid src BIK")?;
-            let src_acc = src_bank
+            let src_acc = banks
+                .get(transaction.src.bik)
+                .map_err(|_| ServerError::NotFound("Invalid src BIK".to_string()))?
                 .accounts
-                .get_mut(&transaction.src.account_id)
-                .ok_or("Invalid account id")?;
+                .get(&transaction.src.account_id)
+                .ok_or(ServerError::NotFound("Invalid account id".to_string()))?;
             if check_balance && src_acc.balance < transaction.amount {
-                return Err("Not enough money on src account");
-            } else {
-                src_acc.balance -= transaction.amount;
+                return Err(ServerError::Conflict("Not enough money on src account".to_string()));
             }
         }
+        if transaction.dst.account_id != 0 {
+            banks
+                .get(transaction.dst.bik)
+                .map_err(|_| ServerError::NotFound("Invalid dst BIK".to_string()))?
+                .accounts
+                .get(&transaction.dst.account_id)
+                .ok_or(ServerError::NotFound("Invalid account id".to_string()))?;
+        }
 
+        if transaction.src.account_id != 0 {
+            let src_acc = banks
+                .get_mut(transaction.src.bik)?
+                .accounts
+                .get_mut(&transaction.src.account_id)
+                .expect("Account was checked above");
+            src_acc.balance -= transaction.amount;
+        }
         if transaction.dst.account_id != 0 {
-            let dst_bank = self
-                .banks
-                .get_mut(&transaction.dst.bik)
-                .ok_or("Invalid dst BIK")?;
-            let dst_acc = dst_bank
+            let dst_acc = banks
+                .get_mut(transaction.dst.bik)?
                 .accounts
                 .get_mut(&transaction.dst.account_id)
-                .ok_or("Invalid account id")?;
-
+                .expect("Account was checked above");
             dst_acc.balance += transaction.amount;
         }
 
-        self.transactions.push(transaction);
+        let mut ledger = self.ledger.lock().expect("Mutex");
+        let id = ledger.len() as u64 + 1;
+        ledger.push(LedgerEntry {
+            id,
+            time: *time,
+            transaction,
+            description: description.to_string(),
+        });
         Ok(())
     }
 
-    pub fn transaction_revert(&mut self, params: &RequestParams) -> Result<(), ServerError> {
-        let _ = self.get_request_context(params, OPERATOR, false);
-        let inv_trans = self
-            .transactions
-            .last()
-            .ok_or(ServerError::Forbidden("No transactions yet".to_string()))?
-            .inverse();
+    /* Reverts the last transaction of the bank the staff member acts for */
+    pub fn transaction_revert(&self, params: &RequestParams) -> Result<(), ServerError> {
+        let ctx = self.get_staff_context(params)?;
+        let bik = ctx.bik.unwrap();
+        let last_of_bank = || {
+            self.ledger
+                .lock()
+                .expect("Mutex")
+                .iter()
+                .rev()
+                .find(|entry| entry.transaction.involves(bik))
+                .cloned()
+        };
+        loop {
+            let last = last_of_bank()
+                .ok_or(ServerError::NotFound("No transactions of this bank yet".to_string()))?;
+
+            let trans = &last.transaction;
+            let mut banks = self.lock_banks(&[trans.src.bik, trans.dst.bik])?;
+            // another transaction of the bank may have sneaked in before it was locked
+            if last_of_bank().map(|entry| entry.id) != Some(last.id) {
+                continue;
+            }
+            let description = format!("Revert of #{}", last.id);
+            let now = self.time.lock().unwrap().get_time();
+            return self.apply_transaction(&mut banks, trans.inverse(), false, &description, &now);
+        }
+    }
 
-        self.perform_transaction(inv_trans, false)
-            .map_err(|e| ServerError::Forbidden(e.to_string()))?;
-        Ok(())
+    pub fn transactions_get(&self) -> Vec<LedgerEntry> {
+        self.ledger.lock().expect("Mutex").clone()
     }
 
-    pub fn transactions_get(&self) -> &Vec<Transaction> {
-        &self.transactions
+    /* Count and volume of booked transactions by bank and direction, the outside world (BIK 0) left out */
+    pub fn transaction_stats(&self) -> BTreeMap<(BIK, &'static str), (u64, i64)> {
+        let mut stats: BTreeMap<(BIK, &'static str), (u64, i64)> = BTreeMap::new();
+        for entry in self.ledger.lock().expect("Mutex").iter() {
+            let transaction = &entry.transaction;
+            for (bik, direction) in [(transaction.src.bik, "out"), (transaction.dst.bik, "in")] {
+                if bik == 0 {
+                    continue;
+                }
+                let (count, volume) = stats.entry((bik, direction)).or_default();
+                *count += 1;
+                *volume += *transaction.amount as i64;
+            }
+        }
+        stats
+    }
+
+    /* Credit applications waiting for review in every bank */
+    pub fn pending_credit_counts(&self) -> BTreeMap<BIK, usize> {
+        self.banks
+            .iter()
+            .map(|(bik, bank)| (*bik, bank.lock().expect("Mutex").credit_service.unaccepted_credits.len()))
+            .collect()
+    }
+
+    pub fn ledger_len(&self) -> usize {
+        self.ledger.lock().expect("Mutex").len()
+    }
+
+    /* Entries booked after the first `len` ones */
+    pub fn ledger_since(&self, len: usize) -> Vec<LedgerEntry> {
+        self.ledger.lock().expect("Mutex")[len..].to_vec()
+    }
+
+    /* Copy of the whole bank system, taken under all of its locks at once */
+    pub fn snapshot(&self) -> BankState {
+        let salary = self.salary.lock().expect("Mutex");
+        let orders = self.orders.lock().expect("Mutex");
+        let biks: Vec<BIK> = self.banks.keys().copied().collect();
+        let banks = self.lock_banks(&biks).expect("Banks are fixed after start");
+        let ledger = self.ledger.lock().expect("Mutex");
+        BankState {
+            salary: salary.clone(),
+            orders: orders.clone(),
+            banks: banks.guards.iter().map(|(bik, bank)| (*bik, (**bank).clone())).collect(),
+            ledger: ledger.clone(),
+        }
+    }
+
+    /* Restores a stored state, banks added by the fixture back then are added again */
+    pub fn load(&mut self, state: BankState) {
+        for bank in state.banks.values() {
+            self.banks
+                .entry(bank.public_info.bik)
+                .or_insert_with(|| Mutex::new(Bank::new(bank.public_info.clone())));
+        }
+        self.restore(state);
+    }
+
+    /* Puts back a state taken by `snapshot`, banks unknown to the service are skipped */
+    pub fn restore(&self, state: BankState) {
+        let mut salary = self.salary.lock().expect("Mutex");
+        let mut orders = self.orders.lock().expect("Mutex");
+        let biks: Vec<BIK> = self.banks.keys().copied().collect();
+        let mut banks = self.lock_banks(&biks).expect("Banks are fixed after start");
+        let mut ledger = self.ledger.lock().expect("Mutex");
+        *salary = state.salary;
+        // a stored state has no time of its own
+        salary.time = self.time.clone();
+        *orders = state.orders;
+        for (bik, bank) in state.banks {
+            match banks.get_mut(bik) {
+                Ok(locked) => *locked = bank,
+                Err(_) => log::warn!("Bank {} is not served, its state is skipped", bik),
+            }
+        }
+        *ledger = state.ledger;
     }
 
     pub fn transaction(
-        &mut self,
+        &self,
         transaction: Transaction,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params, CLIENT | ENTERPRISE, true)?; 
-        let bank = self
-            .banks
-            .get_mut(&ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Invalid BIK".to_string()))?;
+        let ctx = self.get_request_context(params, true)?;
+        let mut banks =
+            self.lock_banks(&[ctx.bik.unwrap(), transaction.src.bik, transaction.dst.bik])?;
 
-        bank.validate_account_identity(transaction.src.account_id, &ctx.login)
-            .map_err(|_| {
-                ServerError::Forbidden("Accound does not exist or belong to user".to_string())
-            })?;
+        // the source account is checked in its own bank, under the same lock as the transfer
+        banks
+            .get(transaction.src.bik)?
+            .validate_account_identity(transaction.src.account_id, &ctx.login)?;
 
-        self.perform_transaction(transaction, true)
-            .map_err(|e| ServerError::Forbidden(e.to_string()))?;
+        let now = self.time.lock().unwrap().get_time();
+        self.apply_transaction(&mut banks, transaction, true, "Transfer", &now)?;
 
         Ok(())
     }
 
 
     pub fn transaction_unprotected(
-        &mut self,
+        &self,
         transaction: Transaction,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let _ctx = self.get_request_context(params,MANAGER, false);
-        self.perform_transaction(transaction, true)
-            .map_err(|e| ServerError::Forbidden(e.to_string()))?;
+        let ctx = self.get_staff_context(params)?;
+        let bik = ctx.bik.unwrap();
+        if !transaction.involves(bik) {
+            return Err(ServerError::Forbidden(format!(
+                "One of the accounts must be in bank {}",
+                bik
+            )));
+        }
+        let now = self.time.lock().unwrap().get_time();
+        self.perform_transaction(transaction, true, "Transfer by staff", &now)?;
 
         Ok(())
     }
 
+    /* Every line is checked up front against accounts and balances, payments of the
+     * same batch included. Atomic batches run only if every line passes. */
+    pub fn payment_batch(
+        &self,
+        payments: Vec<PaymentLine>,
+        mode: BatchMode,
+        params: &RequestParams,
+    ) -> Result<PaymentBatchResp, ServerError> {
+        let ctx = self.get_request_context(params, true)?;
+        let bik = ctx.bik.unwrap();
+        let now = self.time.lock().unwrap().get_time();
+
+        // unknown banks are reported per line instead of failing the whole batch
+        let mut biks: Vec<BIK> = payments
+            .iter()
+            .map(|payment| payment.dst.bik)
+            .filter(|bik| self.banks.contains_key(bik))
+            .collect();
+        biks.push(bik);
+        let mut banks = self.lock_banks(&biks)?;
+
+        let mut balance_changes: HashMap<(BIK, AccountID), i32> = HashMap::new();
+        let mut checks: Vec<Result<Transaction, String>> = Vec::new();
+        for payment in &payments {
+            let transaction = Transaction {
+                src: TransactionEndPoint { bik, account_id: payment.src_account },
+                dst: payment.dst.clone(),
+                amount: payment.amount,
+            };
+            let check = (|| {
+                let src_bank = banks.get(bik)?;
+                src_bank.validate_account_identity(payment.src_account, &ctx.login)?;
+                if transaction.src == transaction.dst {
+                    return Err(ServerError::field("dst", "Source and destination are the same"));
+                }
+                banks
+                    .get(payment.dst.bik)?
+                    .accounts
+                    .get(&payment.dst.account_id)
+                    .ok_or(ServerError::NotFound("Destination account not found".to_string()))?;
+                let change = balance_changes.get(&(bik, payment.src_account)).unwrap_or(&0);
+                let available = *src_bank.accounts[&payment.src_account].balance + change;
+                if available < *payment.amount {
+                    return Err(ServerError::Conflict("Not enough money on src account".to_string()));
+                }
+                Ok(())
+            })();
+            match check {
+                Ok(()) => {
+                    *balance_changes.entry((bik, payment.src_account)).or_default() -= *payment.amount;
+                    *balance_changes.entry((payment.dst.bik, payment.dst.account_id)).or_default() +=
+                        *payment.amount;
+                    checks.push(Ok(transaction));
+                }
+                Err(err) => checks.push(Err(err.message())),
+            }
+        }
+
+        let rejected = mode == BatchMode::Atomic && checks.iter().any(|check| check.is_err());
+        let mut resp = PaymentBatchResp {
+            mode,
+            executed: 0,
+            failed: 0,
+            total: Money(0),
+            lines: Vec::new(),
+        };
+        for (payment, check) in payments.into_iter().zip(checks) {
+            let (status, error) = match check {
+                Err(message) => (PaymentStatus::Failed, Some(message)),
+                Ok(_) if rejected => (PaymentStatus::Skipped, None),
+                Ok(transaction) => {
+                    let description = format!("Bulk payment {}", payment.reference).trim().to_string();
+                    match self.apply_transaction(&mut banks, transaction, true, &description, &now) {
+                        Ok(()) => {
+                            resp.total += payment.amount;
+                            (PaymentStatus::Executed, None)
+                        }
+                        Err(err) => (PaymentStatus::Failed, Some(err.message())),
+                    }
+                }
+            };
+            match status {
+                PaymentStatus::Executed => resp.executed += 1,
+                PaymentStatus::Failed => resp.failed += 1,
+                PaymentStatus::Skipped => {}
+            }
+            resp.lines.push(PaymentLineResult {
+                line: payment.line,
+                reference: payment.reference,
+                status,
+                error,
+            });
+        }
+        Ok(resp)
+    }
+
+    pub fn standing_order_new(
+        &self,
+        req: StandingOrderNewRequest,
+        params: &RequestParams,
+    ) -> Result<StandingOrderNewResp, ServerError> {
+        let ctx = self.get_request_context(params, true)?;
+        let bik = ctx.bik.unwrap();
+        let now = self.time.lock().unwrap().get_time();
+        if req.start.date_naive() < now.date_naive() {
+            return Err(ServerError::field("start", "Must not be in the past"));
+        }
+
+        let src = TransactionEndPoint { bik, account_id: req.src_account };
+        if src == req.dst {
+            return Err(ServerError::field("dst", "Must differ from source account"));
+        }
+        {
+            // both ends are checked now, so that a typo doesn't fail every month
+            let banks = self.lock_banks(&[bik, req.dst.bik])?;
+            banks.get(bik)?.validate_account_identity(req.src_account, &ctx.login)?;
+            banks
+                .get(req.dst.bik)
+                .map_err(|_| ServerError::field("dst.bik", "Bank not found"))?
+                .accounts
+                .get(&req.dst.account_id)
+                .ok_or(ServerError::field("dst.account_id", "Account not found"))?;
+        }
+
+        let id = self.orders.lock().expect("Mutex").add(StandingOrder {
+            id: 0,
+            owner: ctx.login,
+            src,
+            dst: req.dst,
+            amount: req.amount,
+            frequency: req.frequency,
+            reference: req.reference,
+            start: req.start,
+            end: req.end,
+            occurrence: 0,
+            attempts: 0,
+            retry_at: None,
+            status: OrderStatus::Active,
+            history: Vec::new(),
+        });
+        Ok(StandingOrderNewResp { id })
+    }
+
+    pub fn standing_orders_get(&self, params: &RequestParams) -> Result<StandingOrdersGetResp, ServerError> {
+        let ctx = self.get_request_context(params, true)?;
+        let orders = self.orders.lock().expect("Mutex").get(&ctx.login, ctx.bik.unwrap());
+        Ok(StandingOrdersGetResp { orders })
+    }
+
+    pub fn standing_order_cancel(
+        &self,
+        req: StandingOrderCancelRequest,
+        params: &RequestParams,
+    ) -> Result<(), ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        self.orders.lock().expect("Mutex").cancel(&ctx.login, req.id)
+    }
+
+    /* One attempt of the order, if it is due at `due` */
+    fn run_standing_order(&self, id: OrderID, due: &DateTime<Utc>) {
+        let mut orders = self.orders.lock().expect("Mutex");
+        let Some(order) = orders
+            .get_mut(id)
+            .filter(|order| order.next_attempt() == Some(*due))
+        else {
+            return;
+        };
+        let transaction = Transaction {
+            src: order.src.clone(),
+            dst: order.dst.clone(),
+            amount: order.amount,
+        };
+        let description = format!("Standing order #{} {}", order.id, order.reference);
+        let result = self
+            .perform_transaction(transaction, true, description.trim(), due)
+            .map_err(|err| err.message());
+        if let Err(message) = &result {
+            log::warn!("Standing order #{} failed : {}", order.id, message);
+        }
+        record_attempt(order, *due, result);
+    }
+
     pub fn banks_get(&self) -> BanksGetResp {
         let banks = self
             .banks
-            .iter()
-            .map(|priv_bank| priv_bank.1.public_info.clone())
+            .values()
+            .map(|priv_bank| priv_bank.lock().expect("Mutex").public_info.clone())
             .collect();
 
         BanksGetResp { banks }
     }
 
-    pub fn account_open(&mut self, params: &RequestParams) -> Result<AccountOpenResp, ServerError> {
-        let ctx = self.get_request_context(params, CLIENT | ENTERPRISE, true)?;
-        let bank = self
-            .banks
-            .get_mut(&ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Invalid BIK".to_string()))?;
+    pub fn account_open(&self, params: &RequestParams) -> Result<AccountOpenResp, ServerError> {
+        let ctx = self.get_request_context(params, true)?;
+        let mut bank = self.lock_bank(ctx.bik.unwrap())?;
         let new_acc_id = bank
-            .account_new(&ctx.login)
-            .map_err(|e| ServerError::Forbidden(e.to_string()))?;
+            .account_new(&ctx.login)?;
 
         Ok(AccountOpenResp {
             account_id: new_acc_id,
@@ -247,56 +667,65 @@ impl BankService {
     }
 
     pub fn account_close(
-        &mut self,
+        &self,
         req: AccountCloseReq,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params, CLIENT | ENTERPRISE, true)?;
-        let bank = self
-            .banks
-            .get_mut(&ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Invalid BIK".to_string()))?;
+        let ctx = self.get_request_context(params, true)?;
+        let mut bank = self.lock_bank(ctx.bik.unwrap())?;
         let closed_acc_id = req.account_id;
-        bank.account_close(&ctx.login, closed_acc_id)
-            .map_err(|e| ServerError::Forbidden(e.to_string()))?;
+        bank.account_close(&ctx.login, closed_acc_id)?;
 
         Ok(())
     }
 
     pub fn accounts_get(&self, params: &RequestParams) -> Result<AccountsGetResp, ServerError> {
-        let ctx = self.get_request_context(params, CLIENT | ENTERPRISE, true)?;
-        let bank = self
-            .banks
-            .get(&ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Invalid BIK".to_string()))?;
+        let ctx = self.get_request_context(params, true)?;
+        let bank = self.lock_bank(ctx.bik.unwrap())?;
 
-        let accounts = bank
-            .accounts_get(&ctx.login)
-            .map_err(|e| ServerError::Forbidden(e.to_string()))?;
+        let accounts = bank.accounts_get(&ctx.login);
 
         Ok(AccountsGetResp { accounts })
     }
 
+    pub fn account_statement(
+        &self,
+        account_id: AccountID,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        params: &RequestParams,
+    ) -> Result<Statement, ServerError> {
+        let ctx = self.get_request_context(params, true)?;
+        let bik = ctx.bik.unwrap();
+        // the bank stays locked, so that the balance matches the ledger
+        let bank = self.lock_bank(bik)?;
+        if !bank.owns_account(&ctx.login, account_id) {
+            return Err(ServerError::NotFound("Account not found".to_string()));
+        }
+        let current_balance = *bank.accounts[&account_id].balance;
+        let account = TransactionEndPoint { bik, account_id };
+        let ledger = self.ledger.lock().expect("Mutex");
+        Ok(build_statement(account, current_balance, &ledger, from, to))
+    }
+
     pub fn deposit_new(
-        &mut self,
+        &self,
         req: DepositNewRequest,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params, CLIENT, true)?;
+        let ctx = self.get_request_context(params, true)?;
         let now = self.time.lock().unwrap().get_time();
-
-        self.get_bank(ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Bad bank".to_string()))?
-            .validate_account_identity(req.src_account, &ctx.login)
-            .map_err(|_| {
-                ServerError::Forbidden(
-                    "This account does not exist or does not belong to user".to_string(),
-                )
-            })?;
-        self.perform_transaction(
+        let bik = ctx.bik.unwrap();
+        let mut banks = self.lock_banks(&[bik])?;
+
+        banks
+            .get(bik)?
+            .validate_account_identity(req.src_account, &ctx.login)?;
+        self.apply_transaction(
+            &mut banks,
             Transaction {
                 src: TransactionEndPoint {
-                    bik: ctx.bik.unwrap(),
+                    bik,
                     account_id: req.src_account,
                 },
                 dst: TransactionEndPoint {
@@ -306,85 +735,82 @@ impl BankService {
                 amount: req.amount,
             },
             true,
-        )
-        .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
+            "Deposit opened",
+            &now,
+        )?;
 
         let deposit = Deposit {
             owner: ctx.login.clone(),
             interest_rate: 5,
             start_date: now,
-            last_update: now,
             end_date: now + chrono::Months::new(req.months_expires),
             initial_amount: req.amount,
             current_amount: req.amount,
+            capitalization: req.capitalization,
+            accrual: InterestAccrual::new(req.day_count, now),
         };
-        self.get_bank_mut(ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Bad bank".to_string()))?
+        banks
+            .get_mut(bik)?
             .deposit_service
             .add_deposit(ctx.login, deposit);
         Ok(())
     }
 
     pub fn deposit_withdraw(
-        &mut self,
+        &self,
         req: DepositWithdrawRequest,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params, CLIENT, true)?;
+        let ctx = self.get_request_context(params, true)?;
         let cur_time = self.time.lock().unwrap().get_time();
-        self.get_bank(ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Bad bank".to_string()))?
-            .validate_account_identity(req.dst_account, &ctx.login)
-            .map_err(|_| ServerError::Forbidden("This account does not exist".to_string()))?;
-
-        let withdrawn = self
-            .get_bank_mut(ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Bad bank".to_string()))?
+        let bik = ctx.bik.unwrap();
+        let mut banks = self.lock_banks(&[bik])?;
+        banks
+            .get(bik)?
+            .validate_account_identity(req.dst_account, &ctx.login)?;
+
+        let withdrawn = banks
+            .get_mut(bik)?
             .deposit_service
-            .withdraw(ctx.login, req.deposit_idx, cur_time)
-            .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
+            .withdraw(ctx.login, req.deposit_idx, cur_time)?;
 
-        self.perform_transaction(
+        self.apply_transaction(
+            &mut banks,
             Transaction {
                 src: TransactionEndPoint {
                     bik: 0,
                     account_id: 0,
                 },
                 dst: TransactionEndPoint {
-                    bik: ctx.bik.unwrap(),
+                    bik,
                     account_id: req.dst_account,
                 },
                 amount: withdrawn,
             },
             true,
-        )
-        .map_err(|err: _| ServerError::Forbidden(err.to_string()))?;
+            "Deposit withdrawn",
+            &cur_time,
+        )?;
 
         Ok(())
     }
 
     pub fn deposits_get(&self, params: &RequestParams) -> Result<Vec<Deposit>, ServerError> {
-        let ctx = self.get_request_context(params, CLIENT, true)?;
-        let bank = self
-            .banks
-            .get(&ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Invalid BIK".to_string()))?;
+        let ctx = self.get_request_context(params, true)?;
+        let bank = self.lock_bank(ctx.bik.unwrap())?;
 
-        Ok(bank.deposit_service.get(ctx.login).clone())
+        Ok(bank.deposit_service.get(ctx.login))
     }
 
     pub fn credit_new(
-        &mut self,
+        &self,
         req: CreditParams,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params, CLIENT, true)?;
-        let bank = self
-            .get_bank_mut(ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Bad bank".to_string()))?;
+        let ctx = self.get_request_context(params, true)?;
+        let mut bank = self.lock_bank(ctx.bik.unwrap())?;
 
-        bank.validate_account_identity(req.src_account, &ctx.login)
-            .map_err(|s| ServerError::Forbidden(s.to_string()))?;
+        bank.validate_account_identity(req.src_account, &ctx.login)?;
 
         let credit = CreditUnaccepted {
             owner: ctx.login,
@@ -396,23 +822,26 @@ impl BankService {
     }
 
     pub fn credit_accept(
-        &mut self,
+        &self,
         req: CreditAcceptRequest,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params, MANAGER, true)?;
+        let ctx = self.get_staff_context(params)?;
         let now = self.time.lock().unwrap().get_time();
 
-        let credit_template = self
-            .get_bank(ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Bad bank".to_string()))?
+        let bik = ctx.bik.unwrap();
+        let mut banks = self.lock_banks(&[bik])?;
+
+        let credit_template = banks
+            .get(bik)?
             .credit_service
             .unaccepted_credits
             .get(req.idx)
-            .ok_or(ServerError::BadRequest("Index out of range".into()))?
+            .ok_or(ServerError::NotFound("Index out of range".into()))?
             .clone();
 
-        self.perform_transaction(
+        self.apply_transaction(
+            &mut banks,
             Transaction {
                 amount: credit_template.params.amount,
                 src: TransactionEndPoint {
@@ -420,13 +849,14 @@ impl BankService {
                     account_id: 0,
                 },
                 dst: TransactionEndPoint {
-                    bik: ctx.bik.unwrap(),
+                    bik,
                     account_id: credit_template.params.src_account,
                 },
             },
             true,
-        )
-        .map_err(|e| ServerError::Forbidden(e.to_string()))?;
+            "Credit issued",
+            &now,
+        )?;
 
         let credit = Credit {
             owner: credit_template.owner.clone(),
@@ -434,55 +864,53 @@ impl BankService {
             monthly_pay: credit_monthly_pay(&credit_template.params),
             first_pay: now + chrono::Months::new(1),
             last_pay: now + chrono::Months::new(1),
+            outstanding: credit_template.params.amount,
+            accrual: InterestAccrual::new(credit_template.params.day_count, now),
         };
 
-        let bank = self
-            .get_bank_mut(ctx.bik.unwrap())
-            .expect("Bank not found after it was found");
+        let bank = banks.get_mut(bik)?;
         bank.credit_service.unaccepted_credits.swap_remove(req.idx);
 
         bank.credit_service
             .accepted_credits
-            .get_mut(&credit_template.owner)
-            .expect("Bad client")
+            .entry(credit_template.owner)
+            .or_default()
             .push(credit);
 
         Ok(())
     }
 
     pub fn credit_get(&self, params: &RequestParams) -> Result<Vec<Credit>, ServerError> {
-        let ctx = self.get_request_context(params, CLIENT, true)?;
+        let ctx = self.get_request_context(params, true)?;
 
         Ok(self
-            .get_bank(ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Bad bank".into()))?
+            .lock_bank(ctx.bik.unwrap())?
             .credit_service
             .accepted_credits
             .get(&ctx.login)
-            .ok_or(ServerError::BadRequest("Bad token".into()))?
-            .to_vec())
+            .cloned()
+            .unwrap_or_default())
     }
 
     pub fn credit_get_unaccepted(
         &self,
         params: &RequestParams,
     ) -> Result<Vec<CreditUnaccepted>, ServerError> {
-        let ctx = self.get_request_context(params,MANAGER, true)?;
+        let ctx = self.get_staff_context(params)?;
 
         Ok(self
-            .get_bank(ctx.bik.unwrap())
-            .ok_or(ServerError::BadRequest("Bad bank".into()))?
+            .lock_bank(ctx.bik.unwrap())?
             .credit_service
             .unaccepted_credits
             .to_vec())
     }
 
     pub fn salary_request(
-        &mut self,
+        &self,
         req: SalaryClientRequest,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params,CLIENT, true)?;
+        let ctx = self.get_request_context(params, true)?;
         if req.client_login != ctx.login {
             Err(ServerError::BadRequest(
                 "Invalid salary login request".to_string(),
@@ -491,74 +919,434 @@ impl BankService {
             let bik = req.account.bik;
 
             //ensure that requested account belongs to user
-            self.get_bank(bik).ok_or(ServerError::BadRequest("Bad bank".to_string()))?
-                .validate_account_identity(req.account.account_id, &ctx.login)
-                .map_err(|e| ServerError::Forbidden(e.to_string()))?;
+            self.lock_bank(bik)?
+                .validate_account_identity(req.account.account_id, &ctx.login)?;
 
 
-            self.salary.salary_request(req)?;
+            self.salary.lock().expect("Mutex").salary_request(req)?;
             Ok(())
         }
     }
 
     pub fn salary_accept_decline(
-        &mut self,
+        &self,
         req: SalaryAcceptRequest,
         params: &RequestParams,
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params, ENTERPRISE, false)?;
-        self.salary.salary_accept_decline(ctx.login, &req)?;
+        let ctx = self.get_request_context(params, false)?;
+        self.salary
+            .lock()
+            .expect("Mutex")
+            .salary_accept_decline(ctx.login, &req)?;
         Ok(())
     }
 
 
     pub fn init_salary_proj(
-        &mut self,
+        &self,
         req : SalaryInitProjRequest,
         params: &RequestParams
     ) -> Result<(), ServerError> {
-        let ctx = self.get_request_context(params, ENTERPRISE, false)?;
-        self.banks.get(&req.account.bik)
-            .ok_or(ServerError::NotFound("Bank not found".to_string()))?
-            .validate_account_identity(req.account.account_id, &ctx.login)
-            .map_err(|e| ServerError::Forbidden(e.to_string()))?;
-        self.salary.init_salary_proj(ctx.login, req.account);
-        Ok(())
+        let ctx = self.get_request_context(params, false)?;
+        let mut salary = self.salary.lock().expect("Mutex");
+        self.lock_bank(req.account.bik)?
+            .validate_account_identity(req.account.account_id, &ctx.login)?;
+        salary.init_salary_proj(ctx.login, req)
+    }
+
+    /* Sends the draft project to the bank holding the enterprise account */
+    pub fn salary_proj_submit(&self, params: &RequestParams) -> Result<(), ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        self.salary.lock().expect("Mutex").change_status(
+            &ctx.login,
+            None,
+            &[SalaryProjectStatus::Draft],
+            SalaryProjectStatus::Pending,
+            &ctx.login,
+            None,
+        )
+    }
+
+    pub fn salary_proj_close(&self, params: &RequestParams) -> Result<(), ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        self.salary.lock().expect("Mutex").change_status(
+            &ctx.login,
+            None,
+            &[SalaryProjectStatus::Active, SalaryProjectStatus::Suspended],
+            SalaryProjectStatus::Closed,
+            &ctx.login,
+            None,
+        )
     }
 
 
     pub fn salary_accept_decline_get(
         &self,
         params: &RequestParams
-    ) -> Result<&Vec<SalaryClientRequest>, ServerError> {
-        let ctx = self.get_request_context(params, ENTERPRISE, false)?;
-        Ok(self.salary.salary_requests.get(&ctx.login).ok_or(
+    ) -> Result<Vec<SalaryClientRequest>, ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        self.salary.lock().expect("Mutex").salary_requests.get(&ctx.login).cloned().ok_or(
                 ServerError::BadRequest("No salary requests for this enterprise".to_string())
-        )?)
+        )
     }
 
 
     pub fn get_salary_proj(&self, enterprise : String) -> Result<SalaryProject, ServerError> {
-        Ok(self.salary.salary_projects.get(&enterprise).ok_or(
+        Ok(self.salary.lock().expect("Mutex").salary_projects.get(&enterprise).ok_or(
             ServerError::NotFound("Enterprise not found".to_string())
         )?.clone())
     }
 
-    pub fn accept_salary_proj(&mut self, req: SalaryAcceptProjRequest) -> Result<(), ServerError>{
-        self.salary.salary_projects.get_mut(&req.enterprise).ok_or(
-            ServerError::BadRequest("No salary project for this enterprise".to_string())
-        )?.accepted = true;
+    /* Tax, fund and voluntary deduction accounts must exist */
+    pub fn salary_deductions_set(&self, req: PayrollDeductions, params: &RequestParams) -> Result<(), ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        let mut salary = self.salary.lock().expect("Mutex");
+        let accounts = [("tax_account".to_string(), &req.tax_account), ("fund_account".to_string(), &req.fund_account)]
+            .into_iter()
+            .filter_map(|(field, account)| Some((field, account.as_ref()?)))
+            .chain(
+                req.voluntary
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, deduction)| (format!("voluntary[{}].account", idx), &deduction.account)),
+            );
+        let mut errors = ValidationErrors::new();
+        for (field, account) in accounts {
+            let exists = self
+                .banks
+                .get(&account.bik)
+                .is_some_and(|bank| bank.lock().expect("Mutex").accounts.contains_key(&account.account_id));
+            errors.check(exists, &field, "Account not found");
+        }
+        errors.into_result().map_err(ServerError::Unprocessable)?;
+        salary.set_deductions(&ctx.login, req)
+    }
+
+    pub fn payslips_get(&self, period: PayrollPeriod, params: &RequestParams) -> Result<Vec<Payslip>, ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        Ok(self.salary.lock().expect("Mutex").payslips(&ctx.login, period, None))
+    }
+
+    /* Payslip of the employee asking */
+    pub fn payslip_get(
+        &self,
+        enterprise: &Login,
+        period: PayrollPeriod,
+        params: &RequestParams,
+    ) -> Result<Payslip, ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        self.salary
+            .lock()
+            .expect("Mutex")
+            .payslips(enterprise, period, Some(&ctx.login))
+            .pop()
+            .ok_or(ServerError::NotFound("No payslip for this period".to_string()))
+    }
+
+    pub fn payout_submit(&self, req: PayoutBatchRequest, params: &RequestParams) -> Result<PayoutBatchResp, ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        let id = self.salary.lock().expect("Mutex").submit_payouts(&ctx.login, req)?;
+        Ok(PayoutBatchResp { id })
+    }
+
+    pub fn payouts_get(&self, params: &RequestParams) -> Result<Vec<PayoutBatch>, ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        Ok(self.salary.lock().expect("Mutex").payouts(&ctx.login))
+    }
+
+    pub fn payouts_pending_get(&self, params: &RequestParams) -> Result<Vec<PayoutBatch>, ServerError> {
+        let ctx = self.get_staff_context(params)?;
+        Ok(self.salary.lock().expect("Mutex").pending_payouts(ctx.bik.unwrap()))
+    }
+
+    /* Immediate payouts are tried at once, and by the runner later if there is no money */
+    pub fn payout_review(&self, req: PayoutReviewRequest, params: &RequestParams) -> Result<(), ServerError> {
+        let ctx = self.get_staff_context(params)?;
+        let now = self.time.lock().unwrap().get_time();
+        let mut salary = self.salary.lock().expect("Mutex");
+        let enterprise = salary.review_payouts(ctx.bik.unwrap(), req, &ctx.login)?;
+        self.pay_unfinished_runs(&mut salary, &enterprise, &now);
         Ok(())
     }
-    pub fn get_accept_salary_proj(&mut self) -> 
-        Result<Vec<SalaryProjectResp>, ServerError>{
 
-       Ok( self.salary.salary_projects.iter()
-           .filter(|kv| kv.1.accepted  == false)
-           .map(
-               |kv| SalaryProjectResp{enterprise : kv.0.clone(),
-                                    proj : kv.1.clone()}
-       ).collect() )
+    /* Operators decide on the projects paid from the bank they work in */
+    fn review_salary_proj(
+        &self,
+        enterprise: &Login,
+        from: SalaryProjectStatus,
+        to: SalaryProjectStatus,
+        reason: Option<String>,
+        params: &RequestParams,
+    ) -> Result<(), ServerError> {
+        let ctx = self.get_staff_context(params)?;
+        self.salary
+            .lock()
+            .expect("Mutex")
+            .change_status(enterprise, ctx.bik, &[from], to, &ctx.login, reason)
+    }
+
+    pub fn accept_salary_proj(&self, req: SalaryAcceptProjRequest, params: &RequestParams) -> Result<(), ServerError> {
+        self.review_salary_proj(&req.enterprise, SalaryProjectStatus::Pending, SalaryProjectStatus::Active, None, params)
+    }
+
+    pub fn decline_salary_proj(&self, req: SalaryProjReasonRequest, params: &RequestParams) -> Result<(), ServerError> {
+        self.review_salary_proj(
+            &req.enterprise,
+            SalaryProjectStatus::Pending,
+            SalaryProjectStatus::Draft,
+            Some(req.reason),
+            params,
+        )
+    }
+
+    pub fn suspend_salary_proj(&self, req: SalaryProjReasonRequest, params: &RequestParams) -> Result<(), ServerError> {
+        self.review_salary_proj(
+            &req.enterprise,
+            SalaryProjectStatus::Active,
+            SalaryProjectStatus::Suspended,
+            Some(req.reason),
+            params,
+        )
+    }
+
+    pub fn resume_salary_proj(&self, req: SalaryAcceptProjRequest, params: &RequestParams) -> Result<(), ServerError> {
+        self.review_salary_proj(&req.enterprise, SalaryProjectStatus::Suspended, SalaryProjectStatus::Active, None, params)
+    }
+
+    /* Projects paid from the bank of the operator, in one of `statuses` */
+    pub fn get_bank_salary_projs(
+        &self,
+        statuses: &[SalaryProjectStatus],
+        params: &RequestParams,
+    ) -> Result<Vec<SalaryProjectResp>, ServerError> {
+        let ctx = self.get_staff_context(params)?;
+        Ok(self.salary.lock().expect("Mutex").projects_of_bank(ctx.bik.unwrap(), statuses))
+    }
+}
+
+impl BankService {
+    pub fn payroll_runs_get(&self, params: &RequestParams) -> Result<Vec<PayrollRun>, ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        Ok(self.salary.lock().expect("Mutex").payroll_runs(&ctx.login))
+    }
+
+    pub fn employee_salary_change(
+        &self,
+        req: EmployeeSalaryChangeRequest,
+        params: &RequestParams,
+    ) -> Result<(), ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        self.salary.lock().expect("Mutex").change_salary(&ctx.login, req)
+    }
+
+    pub fn employee_account_change(
+        &self,
+        req: EmployeeAccountChangeRequest,
+        params: &RequestParams,
+    ) -> Result<(), ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        let mut salary = self.salary.lock().expect("Mutex");
+        // salary goes to accounts of the employee only
+        self.lock_bank(req.account.bik)?
+            .validate_account_identity(req.account.account_id, &req.login)?;
+        salary.change_account(&ctx.login, req)
+    }
+
+    pub fn employee_dismiss(
+        &self,
+        req: EmployeeDismissRequest,
+        params: &RequestParams,
+    ) -> Result<(), ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        self.end_employment(&ctx.login, &req.login, false)
+    }
+
+    pub fn salary_memberships(&self, params: &RequestParams) -> Result<Vec<SalaryMembership>, ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        Ok(self.salary.lock().expect("Mutex").memberships(&ctx.login))
+    }
+
+    pub fn salary_leave(&self, req: SalaryLeaveRequest, params: &RequestParams) -> Result<(), ServerError> {
+        let ctx = self.get_request_context(params, false)?;
+        self.end_employment(&req.enterprise, &ctx.login, true)
+    }
+
+    /* The final payment is tried at once, and by the runner later if there is no money */
+    fn end_employment(&self, enterprise: &Login, login: &Login, by_employee: bool) -> Result<(), ServerError> {
+        let now = self.time.lock().unwrap().get_time();
+        let mut salary = self.salary.lock().expect("Mutex");
+        salary.dismiss(enterprise, login, by_employee)?;
+        self.pay_unfinished_runs(&mut salary, enterprise, &now);
+        Ok(())
+    }
+
+    /* Opens runs for the periods that came and pays what is still unpaid */
+    fn run_payroll(&self, enterprise: &Login, due: &DateTime<Utc>) {
+        let mut salary = self.salary.lock().expect("Mutex");
+        if salary.next_payroll_due(enterprise) != Some(*due) {
+            return;
+        }
+        salary.open_payroll_runs(enterprise, due);
+        self.pay_unfinished_runs(&mut salary, enterprise, due);
+    }
+
+    fn pay_unfinished_runs(&self, salary: &mut SalaryService, enterprise: &Login, time: &DateTime<Utc>) {
+        // a suspended project keeps its runs until it is resumed
+        let Some(proj) = salary
+            .salary_projects
+            .get_mut(enterprise)
+            .filter(|proj| proj.status == SalaryProjectStatus::Active)
+        else {
+            return;
+        };
+        let Some(runs) = salary.payroll_runs.get_mut(enterprise) else {
+            return;
+        };
+        let src = proj.enterprise_accoint.clone();
+        for run in runs.iter_mut().filter(|run| run.status != PayrollRunStatus::Completed) {
+            self.execute_payroll_run(run, &src, time);
+            if run.status == PayrollRunStatus::Completed {
+                proj.last_paid = Some(*time);
+            }
+        }
+    }
+
+    fn execute_payroll_run(&self, run: &mut PayrollRun, src: &TransactionEndPoint, time: &DateTime<Utc>) {
+        run.attempts += 1;
+        run.last_attempt = Some(*time);
+        let pending: Vec<usize> = (0..run.lines.len())
+            .filter(|idx| run.lines[*idx].status == PayrollLineStatus::Pending)
+            .collect();
+
+        // unknown banks of employees are reported on their lines
+        let mut biks: Vec<BIK> = pending
+            .iter()
+            .flat_map(|idx| {
+                let line = &run.lines[*idx];
+                std::iter::once(line.account.bik).chain(line.deductions.iter().map(|deduction| deduction.account.bik))
+            })
+            .filter(|bik| self.banks.contains_key(bik))
+            .collect();
+        biks.push(src.bik);
+
+        let description = match run.kind {
+            PayrollRunKind::Regular => format!("Salary {}", run.period),
+            PayrollRunKind::Final => format!("Final salary {}", run.period),
+            PayrollRunKind::Payout => format!("Payout {}", run.period),
+        };
+        let result = self.lock_banks(&biks).and_then(|mut banks| {
+            // a line is paid with its withholdings or not at all
+            let transfers: Vec<Vec<(TransactionEndPoint, Money, String)>> = pending
+                .iter()
+                .map(|idx| {
+                    let line = &run.lines[*idx];
+                    // payouts are shown to the employee by their own description
+                    let line_description = line.description.as_ref().unwrap_or(&description);
+                    let mut transfers: Vec<(TransactionEndPoint, Money, String)> = line
+                        .deductions
+                        .iter()
+                        .map(|deduction| {
+                            let text = format!("{} of {}, {}", deduction.description, line.login, line_description);
+                            (deduction.account.clone(), deduction.amount, text)
+                        })
+                        .collect();
+                    transfers.push((line.account.clone(), line.net(), line_description.clone()));
+                    transfers.retain(|(_, amount, _)| **amount > 0);
+                    transfers
+                })
+                .collect();
+            let check_destinations = |banks: &LockedBanks,
+                                      transfers: &[(TransactionEndPoint, Money, String)]|
+             -> Result<(), ServerError> {
+                for (dst, _, _) in transfers {
+                    if dst == src {
+                        return Err(ServerError::field("dst", "Source and destination are the same"));
+                    }
+                    banks
+                        .get(dst.bik)
+                        .map_err(|_| ServerError::NotFound("Invalid dst BIK".to_string()))?
+                        .accounts
+                        .get(&dst.account_id)
+                        .ok_or(ServerError::NotFound("Invalid account id".to_string()))?;
+                }
+                Ok(())
+            };
+
+            if run.policy == PayrollPolicy::AllOrNothing {
+                let needed: i32 = pending.iter().map(|idx| *run.lines[*idx].amount).sum();
+                let available = banks
+                    .get(src.bik)?
+                    .accounts
+                    .get(&src.account_id)
+                    .ok_or(ServerError::NotFound("Enterprise account not found".to_string()))?
+                    .balance;
+                if *available < needed {
+                    return Err(ServerError::Conflict("Not enough money to pay everyone".to_string()));
+                }
+                // every destination is checked before anybody is paid
+                for (idx, line_transfers) in pending.iter().zip(&transfers) {
+                    check_destinations(&banks, line_transfers).map_err(|err| {
+                        ServerError::Conflict(format!("Account of {} : {}", run.lines[*idx].login, err.message()))
+                    })?;
+                }
+            }
+
+            for (idx, line_transfers) in pending.iter().zip(transfers) {
+                let line = &mut run.lines[*idx];
+                let checked = (|| {
+                    let balance = banks
+                        .get(src.bik)?
+                        .accounts
+                        .get(&src.account_id)
+                        .ok_or(ServerError::NotFound("Enterprise account not found".to_string()))?
+                        .balance;
+                    if balance < line.amount {
+                        return Err(ServerError::Conflict("Not enough money on src account".to_string()));
+                    }
+                    check_destinations(&banks, &line_transfers)
+                })();
+                let result = checked.and_then(|()| {
+                    for (dst, amount, text) in line_transfers {
+                        let transaction = Transaction {
+                            src: src.clone(),
+                            dst,
+                            amount,
+                        };
+                        self.apply_transaction(&mut banks, transaction, true, &text, time)?;
+                    }
+                    Ok(())
+                });
+                match result {
+                    Ok(()) => {
+                        line.status = PayrollLineStatus::Paid;
+                        line.error = None;
+                        line.paid_at = Some(*time);
+                    }
+                    Err(err) => line.error = Some(err.message()),
+                }
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            let message = err.message();
+            log::warn!("Payroll {} of {} not paid : {}", run.period, run.enterprise, message);
+            for idx in pending {
+                run.lines[idx].error = Some(message.clone());
+            }
+        }
+
+        let paid = run
+            .lines
+            .iter()
+            .filter(|line| line.status == PayrollLineStatus::Paid)
+            .count();
+        run.status = if paid == run.lines.len() {
+            PayrollRunStatus::Completed
+        } else if paid > 0 {
+            PayrollRunStatus::Partial
+        } else {
+            PayrollRunStatus::Failed
+        };
     }
 }
 
@@ -576,72 +1364,378 @@ pub fn signed_month_difference(start: &DateTime<Utc>, end: &DateTime<Utc>) -> Un
     }
 }
 
-impl Dynamic for BankService {
-    fn update(&mut self, time: &chrono::DateTime<chrono::Utc>) {
-        let mut transactions: Vec<(Transaction, bool)> = Vec::new();
+/* Months paid are counted from the first installment, none are due after the term */
+fn next_installment(credit: &Credit) -> Option<DateTime<Utc>> {
+    let months_paid = signed_month_difference(&credit.first_pay, &credit.last_pay);
+    (months_paid < credit.params.term as i32).then_some(credit.last_pay)
+}
 
-        for (_, bank) in &mut self.banks {
-            bank.update(time);
+/* Interest accrues daily up to the last installment */
+fn next_credit_accrual(credit: &Credit) -> Option<DateTime<Utc>> {
+    next_installment(credit)?;
+    let last_installment = credit.first_pay + chrono::Months::new(credit.params.term as u32 - 1);
+    let next = credit.accrual.next_accrual();
+    (next <= last_installment).then_some(next)
+}
 
-            for (_, credit_list) in &mut bank.credit_service.accepted_credits {
-                for credit in credit_list {
-                    let months_since_last_pay = signed_month_difference(&credit.last_pay, time);
-                    let months_paid = signed_month_difference(&credit.first_pay, &credit.last_pay);
-                    let months_remaining = credit.params.term as i32 - months_paid;
-
-                    if months_since_last_pay > 0 && months_remaining > 0 {
-                        let months_to_pay = std::cmp::min(months_since_last_pay, months_remaining);
-                        transactions.push((Transaction {
-                            amount: Money(months_to_pay * *credit.monthly_pay),
-                            src: TransactionEndPoint {
-                                bik: bank.public_info.bik,
-                                account_id: credit.params.src_account,
-                            },
-                            dst: TransactionEndPoint {
-                                bik: 0,
-                                account_id: 0,
-                            },
-                        }, false));
-
-                        credit.last_pay = credit.first_pay
-                            + chrono::Months::new((months_paid + months_to_pay) as u32);
+impl BankService {
+    /* Fires every event due by `time` in order, each at its own due time.
+     * Banks are locked one at a time so requests keep going. Returns the fired events. */
+    pub fn update(&self, time: &DateTime<Utc>) -> Vec<ScheduledEvent> {
+        let mut scheduler = Scheduler::new(self.scheduled_events());
+        let mut fired = Vec::new();
+        while let Some(event) = scheduler.pop_due(time) {
+            self.fire(&event);
+            // handling an event moves its item on, an item that stays put would fire forever
+            match self.next_due(&event.kind) {
+                Some(due) if due > event.due => scheduler.push(ScheduledEvent { due, kind: event.kind.clone() }),
+                Some(_) => log::error!("Event did not move on, dropped until the next update : {:?}", event),
+                None => {}
+            }
+            fired.push(event);
+        }
+        fired
+    }
+
+    /* First `limit` events to come, earliest first */
+    pub fn upcoming(&self, limit: usize) -> Vec<ScheduledEvent> {
+        Scheduler::new(self.scheduled_events()).upcoming(limit)
+    }
+
+    pub fn next_event_due(&self) -> Option<DateTime<Utc>> {
+        Scheduler::new(self.scheduled_events()).next_due()
+    }
+
+    /* The next event of every item that has one. Rebuilt from the items every time,
+     * so new, cancelled and withdrawn ones are always accounted for. */
+    fn scheduled_events(&self) -> Vec<ScheduledEvent> {
+        let mut events = Vec::new();
+        {
+            let salary = self.salary.lock().expect("Mutex");
+            for enterprise in salary.salary_projects.keys() {
+                if let Some(due) = salary.next_payroll_due(enterprise) {
+                    let kind = ScheduledEventKind::Payroll { enterprise: enterprise.clone() };
+                    events.push(ScheduledEvent { due, kind });
+                }
+            }
+        }
+        for (id, due) in self.orders.lock().expect("Mutex").next_attempts() {
+            events.push(ScheduledEvent { due, kind: ScheduledEventKind::StandingOrder { id } });
+        }
+        for (bik, bank) in &self.banks {
+            let bank = bank.lock().expect("Mutex");
+            for (owner, idx, due) in bank.deposit_service.next_accruals() {
+                let kind = ScheduledEventKind::DepositAccrual { bik: *bik, owner, idx };
+                events.push(ScheduledEvent { due, kind });
+            }
+            for (owner, credits) in &bank.credit_service.accepted_credits {
+                for (idx, credit) in credits.iter().enumerate() {
+                    if let Some(due) = next_credit_accrual(credit) {
+                        let kind = ScheduledEventKind::CreditAccrual { bik: *bik, owner: owner.clone(), idx };
+                        events.push(ScheduledEvent { due, kind });
+                    }
+                    if let Some(due) = next_installment(credit) {
+                        let kind = ScheduledEventKind::CreditInstallment { bik: *bik, owner: owner.clone(), idx };
+                        events.push(ScheduledEvent { due, kind });
                     }
                 }
             }
         }
+        events
+    }
 
+    fn next_due(&self, kind: &ScheduledEventKind) -> Option<DateTime<Utc>> {
+        match kind {
+            ScheduledEventKind::DepositAccrual { bik, owner, idx } => {
+                let bank = self.lock_bank(*bik).ok()?;
+                bank.deposit_service.next_accruals()
+                    .into_iter()
+                    .find(|(login, i, _)| login == owner && i == idx)
+                    .map(|(_, _, due)| due)
+            }
+            ScheduledEventKind::CreditAccrual { bik, owner, idx } => {
+                let bank = self.lock_bank(*bik).ok()?;
+                next_credit_accrual(bank.credit_service.accepted_credits.get(owner)?.get(*idx)?)
+            }
+            ScheduledEventKind::CreditInstallment { bik, owner, idx } => {
+                let bank = self.lock_bank(*bik).ok()?;
+                next_installment(bank.credit_service.accepted_credits.get(owner)?.get(*idx)?)
+            }
+            ScheduledEventKind::Payroll { enterprise } => {
+                self.salary.lock().expect("Mutex").next_payroll_due(enterprise)
+            }
+            ScheduledEventKind::StandingOrder { id } => {
+                self.orders.lock().expect("Mutex").next_attempts().find(|(i, _)| i == id).map(|(_, due)| due)
+            }
+        }
+    }
 
-        for (_, proj) in &mut self.salary.salary_projects {
-            if !proj.accepted {
-                continue;
+    /* Handles the event at its due time. An event its item is no longer due for is ignored. */
+    fn fire(&self, event: &ScheduledEvent) {
+        let due = &event.due;
+        match &event.kind {
+            ScheduledEventKind::DepositAccrual { bik, owner, idx } => {
+                if let Ok(mut bank) = self.lock_bank(*bik) {
+                    bank.deposit_service.accrue(owner, *idx, *due);
+                }
+            }
+            ScheduledEventKind::CreditAccrual { bik, owner, idx } => {
+                let Ok(mut bank) = self.lock_bank(*bik) else {
+                    return;
+                };
+                if let Some(credit) = bank
+                    .credit_service
+                    .accepted_credits
+                    .get_mut(owner)
+                    .and_then(|credits| credits.get_mut(*idx))
+                    .filter(|credit| next_credit_accrual(credit) == Some(*due))
+                {
+                    credit.accrual.accrue(credit.outstanding, credit.params.interest_rate, *due);
+                }
+            }
+            ScheduledEventKind::CreditInstallment { bik, owner, idx } => {
+                let transaction = {
+                    let Ok(mut bank) = self.lock_bank(*bik) else {
+                        return;
+                    };
+                    let Some(credit) = bank
+                        .credit_service
+                        .accepted_credits
+                        .get_mut(owner)
+                        .and_then(|credits| credits.get_mut(*idx))
+                        .filter(|credit| next_installment(credit) == Some(*due))
+                    else {
+                        return;
+                    };
+                    // the accrual of the day may be handled already
+                    credit.accrual.accrue(credit.outstanding, credit.params.interest_rate, *due);
+                    let interest = credit.accrual.take();
+                    let owed = Money(*credit.outstanding + *interest);
+                    let months_paid = signed_month_difference(&credit.first_pay, &credit.last_pay);
+                    let amount = if months_paid + 1 == credit.params.term as i32 {
+                        owed
+                    } else {
+                        Money(std::cmp::min(*credit.monthly_pay, *owed))
+                    };
+                    credit.outstanding = Money(*owed - *amount);
+                    credit.last_pay = credit.last_pay + chrono::Months::new(1);
+                    Transaction {
+                        amount,
+                        src: TransactionEndPoint {
+                            bik: *bik,
+                            account_id: credit.params.src_account,
+                        },
+                        dst: TransactionEndPoint {
+                            bik: 0,
+                            account_id: 0,
+                        },
+                    }
+                };
+                let _ = self
+                    .perform_transaction(transaction, false, "Credit payment", due)
+                    .inspect_err(|e| log::error!("Transaction during update not performed : {:?}", e));
             }
+            ScheduledEventKind::Payroll { enterprise } => self.run_payroll(enterprise, due),
+            ScheduledEventKind::StandingOrder { id } => self.run_standing_order(*id, due),
+        }
+    }
+}
 
-            let months = signed_month_difference(&proj.last_paid.unwrap_or(proj.created), time);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
 
-            if months < 0 {
-                log::error!("Now is the past, last update is the future ");
-                continue;
-            }
+    fn endpoint(bik: BIK, account_id: AccountID) -> TransactionEndPoint {
+        TransactionEndPoint { bik, account_id }
+    }
 
-            for employee in &proj.employees {
-                
-                transactions.push(
-                    (Transaction {
-                        src : proj.enterprise_accoint.clone(),
-                        dst : employee.account.clone(),
-                        amount : employee.salary * months,
-                    }, 
-                    true)
-                );
-            }
+    fn entry(id: u64, day: u32, src: TransactionEndPoint, dst: TransactionEndPoint, amount: i32) -> LedgerEntry {
+        LedgerEntry {
+            id,
+            time: Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap(),
+            transaction: Transaction { src, dst, amount: Money(amount) },
+            description: format!("entry {}", id),
+        }
+    }
 
-            proj.last_paid = Some(time.clone());
+    fn day(day: u32) -> Option<DateTime<Utc>> {
+        Some(Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap())
+    }
+
+    /* Account 1 of bank 1 gets 100 on the 1st, pays 30 on the 5th, gets 50 on the 10th.
+     * Other accounts move money on the 7th. */
+    fn ledger() -> Vec<LedgerEntry> {
+        let (account, other) = (endpoint(1, 1), endpoint(1, 2));
+        vec![
+            entry(1, 1, endpoint(0, 0), account.clone(), 100),
+            entry(2, 5, account.clone(), other.clone(), 30),
+            entry(3, 7, other.clone(), endpoint(2, 1), 10),
+            entry(4, 10, endpoint(2, 1), account.clone(), 50),
+        ]
+    }
+
+    fn balances(statement: &Statement) -> Vec<(u64, i32, i32)> {
+        statement
+            .lines
+            .iter()
+            .map(|line| (line.entry_id, *line.amount, *line.balance))
+            .collect()
+    }
+
+    #[test]
+    fn whole_history() {
+        let statement = build_statement(endpoint(1, 1), 120, &ledger(), None, None);
+        assert_eq!(*statement.opening_balance, 0);
+        assert_eq!(*statement.closing_balance, 120);
+        assert_eq!(balances(&statement), vec![(1, 100, 100), (2, -30, 70), (4, 50, 120)]);
+        assert_eq!(statement.lines[1].counterparty.account_id, 2);
+    }
+
+    #[test]
+    fn balances_at_range_bounds() {
+        let statement = build_statement(endpoint(1, 1), 120, &ledger(), day(2), day(8));
+        assert_eq!(*statement.opening_balance, 100);
+        assert_eq!(*statement.closing_balance, 70);
+        assert_eq!(balances(&statement), vec![(2, -30, 70)]);
+
+        let statement = build_statement(endpoint(1, 1), 120, &ledger(), day(11), None);
+        assert_eq!(*statement.opening_balance, 120);
+        assert_eq!(*statement.closing_balance, 120);
+        assert!(statement.lines.is_empty());
+    }
+
+    fn service() -> BankService {
+        let audit = Arc::new(Mutex::new(crate::services::audit::AuditService::new()));
+        let auth = Arc::new(Mutex::new(AuthService::new(audit)));
+        let time = Arc::new(Mutex::new(TimeService::new()));
+        let info = BankPublicInfo { bik: 1, address: String::new(), name: "Bank".to_string() };
+        BankService::new(auth, time, &[info])
+    }
+
+    #[test]
+    fn installments_are_booked_at_their_due_time() {
+        let service = service();
+        let start = Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap();
+        {
+            let mut bank = service.lock_bank(1).unwrap();
+            let login = "ivan".to_string();
+            let account_id = bank.account_new(&login).unwrap();
+            bank.accounts.get_mut(&account_id).unwrap().balance = Money(1_000_000);
+            let params = CreditParams {
+                src_account: account_id,
+                interest_rate: 12,
+                term: 6,
+                amount: Money(60_000),
+                day_count: l1::common::interest::DayCount::Actual365,
+            };
+            bank.credit_service.accepted_credits.entry(login.clone()).or_default().push(Credit {
+                owner: login,
+                monthly_pay: credit_monthly_pay(&params),
+                outstanding: params.amount,
+                accrual: InterestAccrual::new(params.day_count, start),
+                params,
+                first_pay: start + chrono::Months::new(1),
+                last_pay: start + chrono::Months::new(1),
+            });
+        }
+
+        // one catch-up over four months
+        service.update(&(start + chrono::Months::new(4)));
+
+        let times: Vec<DateTime<Utc>> = service
+            .transactions_get()
+            .into_iter()
+            .filter(|entry| entry.description == "Credit payment")
+            .map(|entry| entry.time)
+            .collect();
+        let expected: Vec<DateTime<Utc>> = (1..=4).map(|month| start + chrono::Months::new(month)).collect();
+        assert_eq!(times, expected);
+    }
+
+    fn payroll_run(policy: PayrollPolicy, accounts: &[AccountID]) -> PayrollRun {
+        let lines = accounts
+            .iter()
+            .map(|account_id| PayrollLine {
+                login: format!("employee{}", account_id),
+                account: endpoint(1, *account_id),
+                amount: Money(1000),
+                status: PayrollLineStatus::Pending,
+                error: None,
+                paid_at: None,
+                payout: None,
+                description: None,
+                deductions: Vec::new(),
+            })
+            .collect();
+        PayrollRun {
+            enterprise: "ent".to_string(),
+            kind: PayrollRunKind::Regular,
+            period: PayrollPeriod { year: 2024, month: 1 },
+            pay_date: Utc.with_ymd_and_hms(2024, 1, 25, 0, 0, 0).unwrap(),
+            policy,
+            attempts: 0,
+            last_attempt: None,
+            status: PayrollRunStatus::Failed,
+            lines,
         }
+    }
 
-        for trans in transactions {
-            let _ = self
-                .perform_transaction(trans.0, trans.1)
-                .inspect_err(|e| log::error!("Transaction during update not performed : {}", e));
+    /* Enterprise account 1 with 10000, employee account 2. Account 99 does not exist. */
+    fn payroll_service() -> BankService {
+        let service = service();
+        {
+            let mut bank = service.lock_bank(1).unwrap();
+            let enterprise_account = bank.account_new(&"ent".to_string()).unwrap();
+            bank.accounts.get_mut(&enterprise_account).unwrap().balance = Money(10000);
+            bank.account_new(&"employee2".to_string()).unwrap();
         }
+        service
+    }
+
+    #[test]
+    fn all_or_nothing_pays_nobody_if_an_account_is_invalid() {
+        let service = payroll_service();
+        let time = Utc.with_ymd_and_hms(2024, 1, 25, 0, 0, 0).unwrap();
+        let mut run = payroll_run(PayrollPolicy::AllOrNothing, &[2, 99]);
+        service.execute_payroll_run(&mut run, &endpoint(1, 1), &time);
+
+        assert!(run.status == PayrollRunStatus::Failed);
+        assert!(run.lines.iter().all(|line| line.status == PayrollLineStatus::Pending));
+        assert!(run.lines.iter().all(|line| line.error.is_some()));
+        assert!(service.transactions_get().is_empty());
+        assert_eq!(*service.lock_bank(1).unwrap().accounts[&1].balance, 10000);
+    }
+
+    #[test]
+    fn partial_pays_the_valid_lines() {
+        let service = payroll_service();
+        let time = Utc.with_ymd_and_hms(2024, 1, 25, 0, 0, 0).unwrap();
+        let mut run = payroll_run(PayrollPolicy::Partial, &[2, 99]);
+        service.execute_payroll_run(&mut run, &endpoint(1, 1), &time);
+
+        assert!(run.status == PayrollRunStatus::Partial);
+        assert!(run.lines[0].status == PayrollLineStatus::Paid);
+        assert!(run.lines[1].status == PayrollLineStatus::Pending);
+        assert_eq!(*service.lock_bank(1).unwrap().accounts[&2].balance, 1000);
+    }
+
+    #[test]
+    fn restore_undoes_what_followed_the_snapshot() {
+        let service = payroll_service();
+        let time = Utc.with_ymd_and_hms(2024, 1, 25, 0, 0, 0).unwrap();
+        let snapshot = service.snapshot();
+
+        let transaction = Transaction { src: endpoint(1, 1), dst: endpoint(1, 2), amount: Money(2500) };
+        service.perform_transaction(transaction, true, "Transfer", &time).unwrap();
+        service.lock_bank(1).unwrap().account_new(&"late".to_string()).unwrap();
+        service.restore(snapshot);
+
+        let bank = service.lock_bank(1).unwrap();
+        assert_eq!(*bank.accounts[&1].balance, 10000);
+        assert_eq!(*bank.accounts[&2].balance, 0);
+        assert!(!bank.clients.contains_key("late"));
+        drop(bank);
+        assert!(service.transactions_get().is_empty());
     }
 }
@@ -3,9 +3,10 @@ use std::collections::HashMap;
 use l1::common::auth::Login;
 use l1::common::credit::*;
 use crate::traits::dynamic::Dynamic;
+use serde::{Deserialize, Serialize};
 
 
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct CreditService {
     pub accepted_credits : HashMap<Login, Vec<Credit>>,
     pub unaccepted_credits : Vec<CreditUnaccepted>
@@ -19,7 +20,7 @@ impl CreditService {
 }
 
 impl  Dynamic for CreditService {
-    fn update(&mut self, time :  &chrono::DateTime<chrono::Utc>) {
+    fn update(&mut self, _time :  &chrono::DateTime<chrono::Utc>) {
         unimplemented!()
     }
 }
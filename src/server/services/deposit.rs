@@ -2,17 +2,15 @@
 use l1::common::auth::Login;
 use l1::common::Money;
 use l1::common::deposit::Deposit;
-use crate::traits::dynamic::Dynamic;
+use crate::server::ServerError;
 use std::collections::hash_map::HashMap;
-use log::*;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 
-use chrono::Datelike;
-
 
 // individual per bank
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct DepositService {
      deposits : HashMap<Login, Vec<Deposit>>,
 }
@@ -31,74 +29,67 @@ impl DepositService {
         deposits.push(deposit);
     }
 
-    pub fn get(&self, client : Login) -> &Vec<Deposit>{
-        self.deposits.get(&client).expect("Client does not exist")
+    pub fn get(&self, client : Login) -> Vec<Deposit>{
+        // clients get registered in the bank with the first account
+        self.deposits.get(&client).cloned().unwrap_or_default()
     }
 
 
-    pub fn withdraw(&mut self, client : Login, idx : usize, now : chrono::DateTime<chrono::Utc>) -> Result<Money, &str>{
-        let deposits = self.deposits.get_mut(&client).ok_or("Client does not exist")?;
-        let deposit =  deposits.get(idx).ok_or("Deposit at specified index does not exist")?;
+    pub fn withdraw(&mut self, client : Login, idx : usize, now : chrono::DateTime<chrono::Utc>) -> Result<Money, ServerError>{
+        let deposits = self.deposits.get_mut(&client)
+            .ok_or(ServerError::NotFound("Client does not exist".to_string()))?;
+        let deposit =  deposits.get(idx)
+            .ok_or(ServerError::NotFound("Deposit at specified index does not exist".to_string()))?;
         if now < deposit.end_date {
-            Err("Deposit can't be withdrawn before the end date")
+            Err(ServerError::Conflict("Deposit can't be withdrawn before the end date".to_string()))
         } else {
-            let deposit = deposits.remove(idx);
-            let money = deposit.current_amount;
-            Ok(money)
+            let mut deposit = deposits.remove(idx);
+            // interest the runner has not added yet
+            let end_date = deposit.end_date;
+            capitalize(&mut deposit, end_date);
+            Ok(deposit.current_amount)
         }
     }
 
-
-}
-
-
-type UnitSize = i32;
-
-pub fn signed_month_difference(start: &DateTime<Utc>, end: &DateTime<Utc>) -> UnitSize {
-    let end_naive = end.date_naive();
-    let start_naive = start.date_naive();
-
-    let month_diff = end_naive.month() as UnitSize - start_naive.month() as UnitSize;
-    let years_diff = (end_naive.year() - start_naive.year()) as UnitSize;
-    if month_diff >= 0 {
-        (years_diff * 12) + month_diff
-    } else {
-        (years_diff - 1) * 12 + (month_diff + 12)
+    /* Next daily accrual of every deposit that has one, by owner and index */
+    pub fn next_accruals(&self) -> Vec<(Login, usize, DateTime<Utc>)> {
+        self.deposits
+            .iter()
+            .flat_map(|(owner, deposits)| {
+                deposits
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, deposit)| Some((owner.clone(), idx, next_accrual(deposit)?)))
+            })
+            .collect()
     }
-}
 
-
-impl Dynamic for DepositService {
-    fn update(&mut self, now : &chrono::DateTime<chrono::Utc>) {
-        for (_, vec) in &mut self.deposits {
-            for deposit in vec {
-                
-                //relative to last update
-                let months_to_now = signed_month_difference(&deposit.last_update, now);
-                let months_to_end = signed_month_difference(&deposit.last_update, &deposit.end_date);
-
-                if months_to_end < 0 {
-                    info!("Deposit skipped since it is overdue");
-                    continue;
-                } else if months_to_now < 0 {
-                    error!("Now is the past, last update is the future!");
-                    error!("Something is wrong. Skipping deposit.");
-                    continue;
-                } else  {
-                    let months = std::cmp::min(months_to_now, months_to_end);
-                    deposit.last_update = now.clone();
-                    let koef = (1.0 + (deposit.interest_rate as f64)/100.0/12.0).powi(months);
-                    deposit.current_amount = Money( ((*deposit.current_amount as f64) * koef).floor() as i32 );
-                }
-
-            }
+    /* Accrues the interest of a day if the deposit is due at `due`,
+     * and adds what was accrued on capitalization dates */
+    pub fn accrue(&mut self, owner : &Login, idx : usize, due : DateTime<Utc>) -> bool {
+        let Some(deposit) = self.deposits.get_mut(owner).and_then(|deposits| deposits.get_mut(idx)) else {
+            return false;
+        };
+        if next_accrual(deposit) != Some(due) {
+            return false;
+        }
+        deposit.accrual.accrue(deposit.current_amount, deposit.interest_rate, due);
+        if deposit.capitalization.is_due(&deposit.start_date, &deposit.end_date, &due) {
+            deposit.current_amount += deposit.accrual.take();
         }
-        
+        true
     }
-}
-
-
 
+}
 
 
+/* A day after the last accrual, none after the end date */
+pub fn next_accrual(deposit : &Deposit) -> Option<DateTime<Utc>> {
+    let next = deposit.accrual.next_accrual();
+    (next <= deposit.end_date).then_some(next)
+}
 
+fn capitalize(deposit : &mut Deposit, to : DateTime<Utc>) {
+    deposit.accrual.accrue(deposit.current_amount, deposit.interest_rate, to);
+    deposit.current_amount += deposit.accrual.take();
+}
@@ -0,0 +1,111 @@
+/*
+ * Request counters and latencies for `/metrics`, and the writer of
+ * the Prometheus text format. Other metrics are read from the services
+ * at the moment of the scrape.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+// upper bounds of the latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Default)]
+struct RequestStats {
+    // requests per bucket, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+/* Key is method, route pattern and status */
+#[derive(Default)]
+pub struct MetricsService {
+    requests: BTreeMap<(String, &'static str, u16), RequestStats>,
+}
+
+impl MetricsService {
+    pub fn new() -> Self {
+        MetricsService::default()
+    }
+
+    pub fn record_request(&mut self, method: &str, route: &'static str, status: u16, elapsed: Duration) {
+        let stats = self.requests.entry((method.to_string(), route, status)).or_default();
+        let seconds = elapsed.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            stats.buckets[bucket] += 1;
+        }
+        stats.count += 1;
+        stats.sum += seconds;
+    }
+
+    pub fn write_requests(&self, out: &mut MetricsWriter) {
+        out.header("bank_http_requests_total", "counter", "HTTP requests by route and status");
+        for ((method, route, status), stats) in &self.requests {
+            let status = status.to_string();
+            let labels = [("method", method.as_str()), ("route", route), ("status", status.as_str())];
+            out.sample("bank_http_requests_total", &labels, stats.count as f64);
+        }
+
+        out.header("bank_http_request_duration_seconds", "histogram", "Time to handle HTTP requests");
+        for ((method, route, status), stats) in &self.requests {
+            let status = status.to_string();
+            let labels = [("method", method.as_str()), ("route", route), ("status", status.as_str())];
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(stats.buckets) {
+                cumulative += count;
+                let le = bound.to_string();
+                let bucket_labels = [labels[0], labels[1], labels[2], ("le", le.as_str())];
+                out.sample("bank_http_request_duration_seconds_bucket", &bucket_labels, cumulative as f64);
+            }
+            let bucket_labels = [labels[0], labels[1], labels[2], ("le", "+Inf")];
+            out.sample("bank_http_request_duration_seconds_bucket", &bucket_labels, stats.count as f64);
+            out.sample("bank_http_request_duration_seconds_sum", &labels, stats.sum);
+            out.sample("bank_http_request_duration_seconds_count", &labels, stats.count as f64);
+        }
+    }
+}
+
+/* Prometheus text exposition format */
+#[derive(Default)]
+pub struct MetricsWriter {
+    text: String,
+}
+
+impl MetricsWriter {
+    pub fn new() -> Self {
+        MetricsWriter::default()
+    }
+
+    pub fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+
+    /* Metric without labels */
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
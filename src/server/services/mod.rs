@@ -1,8 +1,14 @@
 
+pub mod audit;
 pub mod auth;
 pub mod deposit;
 pub mod time;
 pub mod bank;
+pub mod metrics;
+pub mod snapshot;
 pub mod storage;
+pub mod throttle;
 pub mod credit;
 pub mod salary;
+pub mod standing_order;
+pub mod totp;
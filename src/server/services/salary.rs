@@ -1,19 +1,30 @@
 use crate::server::ServerError;
 use l1::common::auth::Login;
+use l1::common::bank::BIK;
 use l1::common::salary::*;
-use l1::common::transaction::TransactionEndPoint;
-use l1::common::Money;
-use serde::{Serialize, Deserialize};
 use std::collections::hash_map::*;
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use crate::services::time::TimeService;
+use chrono::{DateTime, Datelike, Utc};
+use l1::common::Money;
+use l1::common::validate::ValidationErrors;
+use serde::{Deserialize, Serialize};
 
+// virtual time between attempts to pay an unfinished run
+pub const PAYROLL_RETRY_DELAY: chrono::Duration = chrono::Duration::days(1);
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SalaryService {
+    // not stored, set again when the state is restored
+    #[serde(skip)]
     pub time : Arc<Mutex<TimeService>>,
     pub salary_requests: HashMap<Login, Vec<SalaryClientRequest>>, // enterprise name -> list of
     // salary requests
     pub salary_projects: HashMap<Login, SalaryProject>, // enterprise name -> one salary project
+    pub payroll_runs: HashMap<Login, Vec<PayrollRun>>, // enterprise name -> runs, oldest first
+    pub payout_batches: BTreeMap<PayoutID, PayoutBatch>,
+    next_payout_id: PayoutID,
 }
 
 impl SalaryService {
@@ -22,7 +33,10 @@ impl SalaryService {
         SalaryService{
             time,
             salary_requests : HashMap::new(),
-            salary_projects : HashMap::new()
+            salary_projects : HashMap::new(),
+            payroll_runs : HashMap::new(),
+            payout_batches : BTreeMap::new(),
+            next_payout_id : 0
         }
     }
     pub fn salary_request(
@@ -54,20 +68,29 @@ impl SalaryService {
                     "No salary project for this enterprise".to_string(),
                 ))?;
 
-        if !salary_proj.accepted{
-            return Err(ServerError::Forbidden("Salary project not acccepted".to_string()))
+        if salary_proj.status != SalaryProjectStatus::Active {
+            return Err(ServerError::Forbidden(format!("Salary project is {}", salary_proj.status)))
         }
 
+        let now = self.time.lock().unwrap().get_time();
         if let Entry::Occupied(mut en) = self.salary_requests.entry(enterprise_name) {
             let salary_requests = en.get_mut();
 
+            let already_employed = salary_requests.get(req.idx).is_some_and(|request| {
+                salary_proj.employees.iter()
+                    .any(|employee| employee.is_active() && employee.login == request.client_login)
+            });
+            if req.accept && already_employed {
+                return Err(ServerError::Conflict("Client is already an employee".to_string()));
+            }
+
             let request = if req.idx < salary_requests.len() {
                 salary_requests.swap_remove(req.idx)
             } else {
                 return Err(ServerError::BadRequest("Index out of range".to_string()));
             };
 
-            if salary_requests.len() == 0 {
+            if salary_requests.is_empty() {
                 en.remove();
             }
 
@@ -76,6 +99,13 @@ impl SalaryService {
                     salary: req.salary,
                     login: request.client_login,
                     account: request.account,
+                    hired: now,
+                    salary_changes: Vec::new(),
+                    dismissed: None,
+                    history: vec![EmployeeHistoryEntry {
+                        time: now,
+                        event: EmployeeEvent::Hired { salary: req.salary },
+                    }],
                 });
             }
 
@@ -86,17 +116,437 @@ impl SalaryService {
     }
 
 
-    pub fn init_salary_proj(&mut self, enterprise_name: Login, account : TransactionEndPoint ){
+    /* Sets up a draft project. A draft may be set up again, an open project
+     * has to be closed first, so its employees are not lost. */
+    pub fn init_salary_proj(&mut self, enterprise_name: Login, req : SalaryInitProjRequest) -> Result<(), ServerError> {
         let now = self.time.lock().unwrap().get_time();
+        let mut status_history = Vec::new();
+        if let Some(proj) = self.salary_projects.get(&enterprise_name) {
+            match proj.status {
+                SalaryProjectStatus::Draft => status_history = proj.status_history.clone(),
+                SalaryProjectStatus::Closed => {}
+                status => {
+                    return Err(ServerError::Conflict(format!("Salary project is {}", status)));
+                }
+            }
+        }
+        status_history.push(SalaryProjectStatusChange {
+            time : now,
+            status : SalaryProjectStatus::Draft,
+            by : enterprise_name.clone(),
+            reason : None
+        });
         self.salary_projects.insert(enterprise_name, SalaryProject{
             employees : Vec::new(),
-            enterprise_accoint : account, 
+            enterprise_accoint : req.account,
             last_paid : None,
             created : now,
-            accepted : false
+            status : SalaryProjectStatus::Draft,
+            status_history,
+            pay_day : req.pay_day,
+            policy : req.policy,
+            last_period : None,
+            deductions : PayrollDeductions::default()
+        });
+        Ok(())
+    }
+
+    /* Moves the project from one of `from` to `to`. Operators pass the bank
+     * they work in, only projects paid from that bank are theirs. */
+    pub fn change_status(
+        &mut self,
+        enterprise : &Login,
+        bik : Option<BIK>,
+        from : &[SalaryProjectStatus],
+        to : SalaryProjectStatus,
+        by : &Login,
+        reason : Option<String>
+    ) -> Result<(), ServerError> {
+        let now = self.time.lock().unwrap().get_time();
+        let proj = self.salary_projects
+            .get_mut(enterprise)
+            .filter(|proj| bik.is_none_or(|bik| proj.enterprise_accoint.bik == bik))
+            .ok_or(ServerError::NotFound("No salary project for this enterprise".to_string()))?;
+        if !from.contains(&proj.status) {
+            return Err(ServerError::Conflict(format!("Salary project is {}", proj.status)));
+        }
+        if to == SalaryProjectStatus::Closed {
+            if proj.employees.iter().any(|employee| employee.is_active()) {
+                return Err(ServerError::Conflict("Salary project has employees".to_string()));
+            }
+            let runs = self.payroll_runs.get(enterprise).map(Vec::as_slice).unwrap_or_default();
+            if runs.iter().any(|run| run.status != PayrollRunStatus::Completed) {
+                return Err(ServerError::Conflict("Salary project has unpaid payroll runs".to_string()));
+            }
+        }
+        proj.status = to;
+        proj.status_history.push(SalaryProjectStatusChange {
+            time : now,
+            status : to,
+            by : by.clone(),
+            reason
+        });
+        Ok(())
+    }
+
+    pub fn projects_of_bank(&self, bik : BIK, statuses : &[SalaryProjectStatus]) -> Vec<SalaryProjectResp> {
+        self.salary_projects
+            .iter()
+            .filter(|(_, proj)| proj.enterprise_accoint.bik == bik && statuses.contains(&proj.status))
+            .map(|(enterprise, proj)| SalaryProjectResp { enterprise : enterprise.clone(), proj : proj.clone() })
+            .collect()
+    }
+
+    /* Creates a run for every period of the enterprise whose pay date has come, one per period */
+    pub fn open_payroll_runs(&mut self, enterprise : &Login, now : &DateTime<Utc>) {
+        // periods missed while suspended are paid once the project is resumed
+        let Some(proj) = self.salary_projects
+            .get_mut(enterprise)
+            .filter(|proj| proj.status == SalaryProjectStatus::Active)
+        else {
+            return;
+        };
+        let mut period = first_open_period(proj);
+        let runs = self.payroll_runs.entry(enterprise.clone()).or_default();
+        while period.pay_date(proj.pay_day) <= *now {
+            let pay_date = period.pay_date(proj.pay_day);
+            let mut lines: Vec<PayrollLine> = proj.employees.iter()
+                .filter(|employee| employee.is_active() && employee.hired <= pay_date)
+                .map(|employee| payroll_line(employee, employee.salary_on(&pay_date), &proj.deductions, true))
+                .collect();
+            // approved payouts go with the first run after the approval
+            for batch in self.payout_batches.values_mut().filter(|batch| {
+                batch.enterprise == *enterprise
+                    && batch.status == PayoutBatchStatus::Approved
+                    && batch.timing == PayoutTiming::NextRun
+            }) {
+                lines.extend(release_payouts(batch, proj, period));
+            }
+            runs.push(new_run(enterprise, PayrollRunKind::Regular, period, pay_date, proj.policy, lines));
+            proj.last_period = Some(period);
+            period = period.next();
+        }
+    }
+
+    /* The next pay date of an active project, or the retry of an unfinished run if it is sooner */
+    pub fn next_payroll_due(&self, enterprise : &Login) -> Option<DateTime<Utc>> {
+        let proj = self.salary_projects
+            .get(enterprise)
+            .filter(|proj| proj.status == SalaryProjectStatus::Active)?;
+        let pay_date = first_open_period(proj).pay_date(proj.pay_day);
+        self.payroll_runs
+            .get(enterprise)
+            .into_iter()
+            .flatten()
+            .filter(|run| run.status != PayrollRunStatus::Completed)
+            .map(|run| run.last_attempt.map_or(run.pay_date, |last| last + PAYROLL_RETRY_DELAY))
+            .chain(std::iter::once(pay_date))
+            .min()
+    }
+
+    fn active_employee(&mut self, enterprise : &Login, login : &Login) -> Result<&mut Employee, ServerError> {
+        self.salary_projects
+            .get_mut(enterprise)
+            .ok_or(ServerError::NotFound("No salary project for this enterprise".to_string()))?
+            .employees
+            .iter_mut()
+            .find(|employee| employee.is_active() && employee.login == *login)
+            .ok_or(ServerError::NotFound("Employee not found".to_string()))
+    }
+
+    /* New salary from `effective` on, runs opened already are not changed */
+    pub fn change_salary(&mut self, enterprise : &Login, req : EmployeeSalaryChangeRequest) -> Result<(), ServerError> {
+        let now = self.time.lock().unwrap().get_time();
+        if req.effective.date_naive() < now.date_naive() {
+            return Err(ServerError::field("effective", "Must not be in the past"));
+        }
+        let employee = self.active_employee(enterprise, &req.login)?;
+        // a later change with the same date replaces the earlier one
+        employee.salary_changes.retain(|change| change.effective != req.effective);
+        let idx = employee.salary_changes.partition_point(|change| change.effective < req.effective);
+        employee.salary_changes.insert(idx, SalaryChange { effective : req.effective, salary : req.salary });
+        employee.history.push(EmployeeHistoryEntry {
+            time : now,
+            event : EmployeeEvent::SalaryChanged { salary : req.salary, effective : req.effective }
+        });
+        Ok(())
+    }
+
+    /* The caller checks that the account belongs to the employee */
+    pub fn change_account(&mut self, enterprise : &Login, req : EmployeeAccountChangeRequest) -> Result<(), ServerError> {
+        let now = self.time.lock().unwrap().get_time();
+        let employee = self.active_employee(enterprise, &req.login)?;
+        employee.account = req.account.clone();
+        employee.history.push(EmployeeHistoryEntry {
+            time : now,
+            event : EmployeeEvent::AccountChanged { account : req.account }
+        });
+        Ok(())
+    }
+
+    /* Ends the employment now. Days of the current month not covered by a run yet
+     * are paid by a final run, which the caller executes. */
+    pub fn dismiss(&mut self, enterprise : &Login, login : &Login, by_employee : bool) -> Result<Money, ServerError> {
+        let now = self.time.lock().unwrap().get_time();
+        // periods that came already are paid in full
+        self.open_payroll_runs(enterprise, &now);
+
+        let proj = self.salary_projects
+            .get(enterprise)
+            .ok_or(ServerError::NotFound("No salary project for this enterprise".to_string()))?;
+        let (policy, last_period, deductions) = (proj.policy, proj.last_period, proj.deductions.clone());
+        let employee = self.active_employee(enterprise, login)?;
+
+        let period = PayrollPeriod::of(&now);
+        let final_payment = if last_period.is_some_and(|last| last >= period) {
+            Money(0)
+        } else {
+            period.prorate(employee.salary_on(&now), now.day())
+        };
+
+        employee.dismissed = Some(now);
+        employee.history.push(EmployeeHistoryEntry {
+            time : now,
+            event : if by_employee {
+                EmployeeEvent::Left { final_payment }
+            } else {
+                EmployeeEvent::Dismissed { final_payment }
+            }
+        });
+
+        if *final_payment > 0 {
+            let line = payroll_line(employee, final_payment, &deductions, false);
+            self.payroll_runs
+                .entry(enterprise.clone())
+                .or_default()
+                .push(new_run(enterprise, PayrollRunKind::Final, period, now, policy, vec![line]));
+        }
+        Ok(final_payment)
+    }
+
+    pub fn memberships(&self, login : &Login) -> Vec<SalaryMembership> {
+        self.salary_projects
+            .iter()
+            .flat_map(|(enterprise, proj)| {
+                proj.employees
+                    .iter()
+                    .filter(|employee| employee.login == *login)
+                    .map(|employee| SalaryMembership {
+                        enterprise : enterprise.clone(),
+                        employee : employee.clone()
+                    })
+            })
+            .collect()
+    }
+
+    pub fn payroll_runs(&self, enterprise : &Login) -> Vec<PayrollRun> {
+        self.payroll_runs.get(enterprise).cloned().unwrap_or_default()
+    }
+
+    /* New withholdings apply to runs opened from now on. The caller checks the accounts. */
+    pub fn set_deductions(&mut self, enterprise : &Login, deductions : PayrollDeductions) -> Result<(), ServerError> {
+        let proj = self.salary_projects
+            .get_mut(enterprise)
+            .ok_or(ServerError::NotFound("No salary project for this enterprise".to_string()))?;
+        let mut errors = ValidationErrors::new();
+        for (idx, deduction) in deductions.voluntary.iter().enumerate() {
+            let employed = proj.employees
+                .iter()
+                .any(|employee| employee.is_active() && employee.login == deduction.login);
+            errors.check(employed, &format!("voluntary[{}].login", idx), "Not an employee");
+        }
+        errors.into_result().map_err(ServerError::Unprocessable)?;
+        proj.deductions = deductions;
+        Ok(())
+    }
+
+    /* Payslips of the period, one per employee paid by some run of it */
+    pub fn payslips(&self, enterprise : &Login, period : PayrollPeriod, login : Option<&Login>) -> Vec<Payslip> {
+        let mut payslips: Vec<Payslip> = Vec::new();
+        let runs = self.payroll_runs.get(enterprise).map(Vec::as_slice).unwrap_or_default();
+        for run in runs.iter().filter(|run| run.period == period) {
+            for line in run.lines.iter().filter(|line| login.is_none_or(|login| line.login == *login)) {
+                let description = match (&line.description, run.kind) {
+                    (Some(description), _) => description.clone(),
+                    (None, PayrollRunKind::Final) => "Final salary".to_string(),
+                    (None, _) => "Salary".to_string(),
+                };
+                let entry = PayslipEntry {
+                    kind : run.kind,
+                    description,
+                    gross : line.amount,
+                    deductions : line.deductions.clone(),
+                    net : line.net(),
+                    status : line.status,
+                    paid_at : line.paid_at
+                };
+                match payslips.iter_mut().find(|payslip| payslip.login == line.login) {
+                    Some(payslip) => payslip.entries.push(entry),
+                    None => payslips.push(Payslip {
+                        enterprise : enterprise.clone(),
+                        login : line.login.clone(),
+                        period,
+                        entries : vec![entry]
+                    }),
+                }
+            }
+        }
+        payslips
+    }
+
+    /* Payouts are for active employees of an active project only */
+    pub fn submit_payouts(&mut self, enterprise : &Login, req : PayoutBatchRequest) -> Result<PayoutID, ServerError> {
+        let now = self.time.lock().unwrap().get_time();
+        let proj = self.salary_projects
+            .get(enterprise)
+            .ok_or(ServerError::NotFound("No salary project for this enterprise".to_string()))?;
+        if proj.status != SalaryProjectStatus::Active {
+            return Err(ServerError::Conflict(format!("Salary project is {}", proj.status)));
+        }
+        let mut errors = ValidationErrors::new();
+        for (idx, payout) in req.payouts.iter().enumerate() {
+            let employed = proj.employees
+                .iter()
+                .any(|employee| employee.is_active() && employee.login == payout.login);
+            errors.check(employed, &format!("payouts[{}].login", idx), "Not an employee");
+        }
+        errors.into_result().map_err(ServerError::Unprocessable)?;
+
+        self.next_payout_id += 1;
+        self.payout_batches.insert(self.next_payout_id, PayoutBatch {
+            id : self.next_payout_id,
+            enterprise : enterprise.clone(),
+            timing : req.timing,
+            status : PayoutBatchStatus::Pending,
+            payouts : req.payouts,
+            submitted : now,
+            reviewed_by : None,
+            reason : None,
+            period : None
         });
+        Ok(self.next_payout_id)
     }
 
+    pub fn payouts(&self, enterprise : &Login) -> Vec<PayoutBatch> {
+        self.payout_batches
+            .values()
+            .filter(|batch| batch.enterprise == *enterprise)
+            .cloned()
+            .collect()
+    }
+
+    /* Batches waiting for the operators of bank `bik` */
+    pub fn pending_payouts(&self, bik : BIK) -> Vec<PayoutBatch> {
+        self.payout_batches
+            .values()
+            .filter(|batch| batch.status == PayoutBatchStatus::Pending)
+            .filter(|batch| {
+                self.salary_projects
+                    .get(&batch.enterprise)
+                    .is_some_and(|proj| proj.enterprise_accoint.bik == bik)
+            })
+            .cloned()
+            .collect()
+    }
 
+    /* An approved immediate batch becomes a payout run, which the caller executes.
+     * Returns the enterprise of the batch. */
+    pub fn review_payouts(&mut self, bik : BIK, req : PayoutReviewRequest, by : &Login) -> Result<Login, ServerError> {
+        let now = self.time.lock().unwrap().get_time();
+        let batch = self.payout_batches
+            .get_mut(&req.id)
+            .filter(|batch| {
+                self.salary_projects
+                    .get(&batch.enterprise)
+                    .is_some_and(|proj| proj.enterprise_accoint.bik == bik)
+            })
+            .ok_or(ServerError::NotFound("Payout batch not found".to_string()))?;
+        if batch.status != PayoutBatchStatus::Pending {
+            return Err(ServerError::Conflict("Payout batch is already reviewed".to_string()));
+        }
+        batch.reviewed_by = Some(by.clone());
+        if !req.accept {
+            batch.status = PayoutBatchStatus::Declined;
+            batch.reason = Some(req.reason);
+            return Ok(batch.enterprise.clone());
+        }
+        batch.status = PayoutBatchStatus::Approved;
+        if batch.timing == PayoutTiming::NextRun {
+            return Ok(batch.enterprise.clone());
+        }
 
+        let proj = &self.salary_projects[&batch.enterprise];
+        let period = PayrollPeriod::of(&now);
+        let lines = release_payouts(batch, proj, period);
+        self.payroll_runs
+            .entry(batch.enterprise.clone())
+            .or_default()
+            .push(new_run(&batch.enterprise, PayrollRunKind::Payout, period, now, proj.policy, lines));
+        Ok(batch.enterprise.clone())
+    }
+}
+
+/* The first period without a run */
+fn first_open_period(proj : &SalaryProject) -> PayrollPeriod {
+    match proj.last_period {
+        Some(last) => last.next(),
+        // the first pay date after the project was created
+        None => {
+            let period = PayrollPeriod::of(&proj.created);
+            if period.pay_date(proj.pay_day) < proj.created { period.next() } else { period }
+        }
+    }
+}
+
+/* Line of `amount` gross, withholdings are fixed with the line */
+fn payroll_line(employee : &Employee, amount : Money, deductions : &PayrollDeductions, regular : bool) -> PayrollLine {
+    PayrollLine {
+        login : employee.login.clone(),
+        account : employee.account.clone(),
+        amount,
+        status : PayrollLineStatus::Pending,
+        error : None,
+        paid_at : None,
+        payout : None,
+        description : None,
+        deductions : deductions.withhold(&employee.login, amount, regular)
+    }
+}
+
+/* Lines of the batch, paid to the current accounts of the employees */
+fn release_payouts(batch : &mut PayoutBatch, proj : &SalaryProject, period : PayrollPeriod) -> Vec<PayrollLine> {
+    batch.status = PayoutBatchStatus::Released;
+    batch.period = Some(period);
+    batch.payouts
+        .iter()
+        .filter_map(|payout| {
+            let employee = proj.employees.iter().rev().find(|employee| employee.login == payout.login)?;
+            Some(PayrollLine {
+                payout : Some(batch.id),
+                description : Some(payout.description.clone()),
+                ..payroll_line(employee, payout.amount, &proj.deductions, false)
+            })
+        })
+        .collect()
+}
+
+fn new_run(
+    enterprise : &Login,
+    kind : PayrollRunKind,
+    period : PayrollPeriod,
+    pay_date : DateTime<Utc>,
+    policy : PayrollPolicy,
+    lines : Vec<PayrollLine>
+) -> PayrollRun {
+    PayrollRun {
+        enterprise : enterprise.clone(),
+        kind,
+        period,
+        pay_date,
+        policy,
+        attempts : 0,
+        last_attempt : None,
+        status : PayrollRunStatus::Failed,
+        lines
+    }
 }
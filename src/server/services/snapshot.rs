@@ -0,0 +1,40 @@
+use crate::server::ServerError;
+use crate::services::bank::BankState;
+use chrono::{DateTime, Utc};
+use l1::common::time::{SnapshotID, TimeSnapshot};
+use std::collections::BTreeMap;
+
+// older snapshots are dropped
+pub const MAX_SNAPSHOTS: usize = 20;
+
+/* States of the bank system saved at some virtual time, so that time can be
+ * moved back to them. Kept in memory only. */
+#[derive(Default)]
+pub struct SnapshotService {
+    snapshots: BTreeMap<SnapshotID, (TimeSnapshot, BankState)>,
+    next_id: SnapshotID,
+}
+
+impl SnapshotService {
+    pub fn add(&mut self, time: DateTime<Utc>, state: BankState) -> TimeSnapshot {
+        self.next_id += 1;
+        let snapshot = TimeSnapshot { id: self.next_id, time };
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_first();
+        }
+        self.snapshots.insert(snapshot.id, (snapshot.clone(), state));
+        snapshot
+    }
+
+    pub fn get(&self, id: SnapshotID) -> Result<(TimeSnapshot, BankState), ServerError> {
+        self.snapshots
+            .get(&id)
+            .cloned()
+            .ok_or(ServerError::NotFound("Snapshot not found".to_string()))
+    }
+
+    /* Oldest first */
+    pub fn list(&self) -> Vec<TimeSnapshot> {
+        self.snapshots.values().map(|(snapshot, _)| snapshot.clone()).collect()
+    }
+}
@@ -0,0 +1,93 @@
+use crate::server::ServerError;
+use chrono::{DateTime, Utc};
+use l1::common::auth::Login;
+use l1::common::bank::BIK;
+use l1::common::standing_order::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// attempts of a single occurrence before it is given up
+pub const MAX_ATTEMPTS: u32 = 3;
+// virtual time between attempts, so a retry waits for the next runner tick at least
+pub const RETRY_DELAY: chrono::Duration = chrono::Duration::days(1);
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct StandingOrderService {
+    orders: BTreeMap<OrderID, StandingOrder>,
+    next_id: OrderID,
+}
+
+impl StandingOrderService {
+    pub fn add(&mut self, mut order: StandingOrder) -> OrderID {
+        self.next_id += 1;
+        order.id = self.next_id;
+        self.orders.insert(order.id, order);
+        self.next_id
+    }
+
+    pub fn get(&self, owner: &Login, bik: BIK) -> Vec<StandingOrder> {
+        self.orders
+            .values()
+            .filter(|order| order.owner == *owner && order.src.bik == bik)
+            .cloned()
+            .collect()
+    }
+
+    pub fn cancel(&mut self, owner: &Login, id: OrderID) -> Result<(), ServerError> {
+        let order = self
+            .orders
+            .get_mut(&id)
+            .filter(|order| order.owner == *owner)
+            .ok_or(ServerError::NotFound("Standing order not found".to_string()))?;
+        if order.status != OrderStatus::Active {
+            return Err(ServerError::Conflict("Standing order is not active".to_string()));
+        }
+        order.status = OrderStatus::Cancelled;
+        order.retry_at = None;
+        Ok(())
+    }
+
+    /* Next attempt of every order that has one */
+    pub fn next_attempts(&self) -> impl Iterator<Item = (OrderID, DateTime<Utc>)> + '_ {
+        self.orders
+            .values()
+            .filter_map(|order| Some((order.id, order.next_attempt()?)))
+    }
+
+    pub fn get_mut(&mut self, id: OrderID) -> Option<&mut StandingOrder> {
+        self.orders.get_mut(&id)
+    }
+}
+
+/* Writes down the attempt and moves the order to its next attempt.
+ * A failed occurrence is retried `MAX_ATTEMPTS` times, then skipped. */
+pub fn record_attempt(order: &mut StandingOrder, now: DateTime<Utc>, result: Result<(), String>) {
+    let Some(due) = order.next_due() else {
+        return;
+    };
+    order.attempts += 1;
+    let failed = result.is_err();
+    order.history.push(OrderExecution {
+        due,
+        time: now,
+        attempt: order.attempts,
+        error: result.err(),
+    });
+
+    if failed && order.attempts < MAX_ATTEMPTS {
+        order.retry_at = Some(now + RETRY_DELAY);
+        return;
+    }
+
+    order.attempts = 0;
+    order.retry_at = None;
+    if order.frequency == Frequency::Once {
+        order.status = if failed { OrderStatus::Failed } else { OrderStatus::Completed };
+        return;
+    }
+    order.occurrence += 1;
+    let next = order.due_date(order.occurrence);
+    if order.end.is_some_and(|end| next > end) {
+        order.status = OrderStatus::Completed;
+    }
+}
@@ -1,28 +1,49 @@
+use crate::config::ServerConfig;
 use crate::server::Server;
 use crate::traits::storable::Storable;
 use log::*;
-use std::sync::{Arc, Mutex, Weak};
-
-const STORAGE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60 * 5);
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 pub struct StorageService {
-    server: Weak<Mutex<Server>>,
+    server: Weak<Server>,
+    dir: PathBuf,
+    timeout: Duration,
+    // dropping the sender stops the thread too
+    shutdown: Option<Sender<()>>,
+    thread_handle: Option<JoinHandle<()>>,
 }
 
 impl StorageService {
-    pub fn new(serv: &Arc<Mutex<Server>>) -> Self {
+    pub fn new(serv: &Arc<Server>, config: &ServerConfig) -> Self {
         StorageService {
             server: Arc::downgrade(serv),
+            dir: config.data_dir.clone(),
+            timeout: Duration::from_secs(config.storage_interval),
+            shutdown: None,
+            thread_handle: None,
         }
     }
 
     pub fn run(&mut self) {
         let local_server = self.server.clone();
-        std::thread::spawn(move || {
+        let dir = self.dir.clone();
+        let timeout = self.timeout;
+        let (sender, receiver) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
             log::info!("This is you cuting-edge noSQL BD - StorageService!");
             log::info!("StorageService thread spawned");
             loop {
-                std::thread::park_timeout(STORAGE_TIMEOUT);
+                match receiver.recv_timeout(timeout) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+                        info!("StorageService stopped. Good bye!");
+                        return;
+                    }
+                }
                 log::info!("Good morning!");
                 let maybe_server = local_server.upgrade();
                 match maybe_server {
@@ -30,13 +51,29 @@ impl StorageService {
                         info!("Server is dead. So am I. Good bye!");
                         return;
                     }
-                    Some(mserver) => {
+                    Some(server) => {
                         log::info!("I'm gonna perform some job to save your data.");
-                        let server = mserver.lock().expect("Mutex");
-                        server.store(std::path::Path::new("server"));
+                        if let Err(err) = server.store(&dir) {
+                            error!("{}", err);
+                        }
                     }
                 }
             }
         });
+        self.shutdown = Some(sender);
+        self.thread_handle = Some(handle);
+    }
+
+    /* Waits for the store in progress, if any, to finish */
+    pub fn stop(&mut self) -> Result<(), String> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let Some(handle) = self.thread_handle.take() else {
+            return Ok(());
+        };
+        handle
+            .join()
+            .map_err(|_| "StorageService panicked".to_string())
     }
 }
@@ -0,0 +1,166 @@
+/*
+ * Failed login attempts counter with exponential backoff and
+ * temporary lockout. Keyed either by login or by client address.
+ */
+
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// delay after the first failure beyond free attempts, doubled after each next one
+const BACKOFF_BASE: TimeDelta = TimeDelta::seconds(1);
+const BACKOFF_MAX: TimeDelta = TimeDelta::seconds(60);
+const LOCKOUT_DURATION: TimeDelta = TimeDelta::minutes(15);
+// failures older than this are forgotten
+const FAILURE_WINDOW: TimeDelta = TimeDelta::minutes(15);
+// forgotten failures are dropped this often, keys sprayed once are never checked again
+const SWEEP_INTERVAL: TimeDelta = TimeDelta::minutes(1);
+// past this, the oldest counters that are not locked out give way to new ones
+const MAX_COUNTERS: usize = 100_000;
+
+struct FailureCounter {
+    failures: u32,
+    last_failure: DateTime<Utc>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+impl FailureCounter {
+    fn retry_after(&self, free_attempts: u32) -> DateTime<Utc> {
+        if let Some(locked_until) = self.locked_until {
+            return locked_until;
+        }
+        if self.failures < free_attempts {
+            return self.last_failure;
+        }
+        let backoff = BACKOFF_BASE * 2i32.saturating_pow(self.failures - free_attempts);
+        self.last_failure + std::cmp::min(backoff, BACKOFF_MAX)
+    }
+
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        match self.locked_until {
+            Some(locked_until) => now >= locked_until,
+            None => now - self.last_failure >= FAILURE_WINDOW,
+        }
+    }
+}
+
+pub struct LoginThrottle<K> {
+    counters: HashMap<K, FailureCounter>,
+    last_sweep: DateTime<Utc>,
+    // failures allowed without any delay
+    free_attempts: u32,
+    lockout_threshold: u32,
+}
+
+impl<K> LoginThrottle<K>
+where
+    K: Hash + Eq + Clone,
+{
+    pub fn new(free_attempts: u32, lockout_threshold: u32) -> Self {
+        LoginThrottle {
+            counters: HashMap::new(),
+            last_sweep: Utc::now(),
+            free_attempts,
+            lockout_threshold,
+        }
+    }
+
+    /* Returns the moment the next attempt is allowed at, if it is not allowed now. */
+    pub fn check(&mut self, key: &K, now: DateTime<Utc>) -> Result<(), DateTime<Utc>> {
+        let Some(counter) = self.counters.get(key) else {
+            return Ok(());
+        };
+        if counter.expired(now) {
+            self.counters.remove(key);
+            return Ok(());
+        }
+        let retry_after = counter.retry_after(self.free_attempts);
+        if now < retry_after {
+            Err(retry_after)
+        } else {
+            Ok(())
+        }
+    }
+
+    /* Returns lockout end if this failure caused the lockout. */
+    pub fn record_failure(&mut self, key: &K, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if now - self.last_sweep >= SWEEP_INTERVAL {
+            self.counters.retain(|_, counter| !counter.expired(now));
+            self.last_sweep = now;
+        }
+        if self.counters.len() >= MAX_COUNTERS && !self.counters.contains_key(key) {
+            self.evict_oldest();
+        }
+        let counter = self.counters.entry(key.clone()).or_insert(FailureCounter {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        counter.failures += 1;
+        counter.last_failure = now;
+        if counter.locked_until.is_none() && counter.failures >= self.lockout_threshold {
+            counter.locked_until = Some(now + LOCKOUT_DURATION);
+            counter.locked_until
+        } else {
+            None
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .counters
+            .iter()
+            .filter(|(_, counter)| counter.locked_until.is_none())
+            .min_by_key(|(_, counter)| counter.last_failure)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            self.counters.remove(&oldest);
+        }
+    }
+
+    pub fn reset(&mut self, key: &K) {
+        self.counters.remove(key);
+    }
+
+    /* Currently locked keys with their failures count and lockout end */
+    pub fn lockouts(&self, now: DateTime<Utc>) -> Vec<(K, u32, DateTime<Utc>)> {
+        self.counters
+            .iter()
+            .filter_map(|(key, counter)| match counter.locked_until {
+                Some(locked_until) if locked_until > now => {
+                    Some((key.clone(), counter.failures, locked_until))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweeps_forgotten_failures() {
+        let start = Utc::now();
+        let mut throttle = LoginThrottle::new(3, 10);
+        for idx in 0..100 {
+            throttle.record_failure(&idx, start);
+        }
+        throttle.record_failure(&0, start + FAILURE_WINDOW);
+        assert_eq!(throttle.counters.len(), 1);
+    }
+
+    #[test]
+    fn keeps_lockouts_when_full() {
+        let now = Utc::now();
+        let mut throttle = LoginThrottle::new(0, 2);
+        throttle.record_failure(&0, now);
+        throttle.record_failure(&0, now);
+        for idx in 1..=MAX_COUNTERS {
+            throttle.record_failure(&idx, now + TimeDelta::milliseconds(idx as i64));
+        }
+        assert_eq!(throttle.counters.len(), MAX_COUNTERS);
+        assert!(throttle.check(&0, now).is_err());
+    }
+}
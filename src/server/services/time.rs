@@ -1,8 +1,35 @@
+use crate::server::ServerError;
 use chrono::{DateTime, Utc};
+use l1::common::time::{TimeEvent, TimeEventKind};
+use l1::common::transaction::LedgerEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// older events are dropped
+pub const MAX_TIME_EVENTS: usize = 1000;
+
+/* Clock and event feed kept across restarts, virtual time goes on from where it was stored */
+#[derive(Serialize, Deserialize)]
+pub struct TimeState {
+    virtual_time: Option<DateTime<Utc>>,
+    paused: bool,
+    events: VecDeque<TimeEvent>,
+    next_event_id: u64,
+}
 
 pub struct TimeService {
     real_time: DateTime<Utc>,
     virtual_time: Option<DateTime<Utc>>,
+    // virtual time stands still until resumed
+    paused: bool,
+    events: VecDeque<TimeEvent>,
+    next_event_id: u64,
+}
+
+impl Default for TimeService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TimeService {
@@ -10,18 +37,101 @@ impl TimeService {
         TimeService {
             real_time: chrono::Utc::now(),
             virtual_time: None,
+            paused: false,
+            events: VecDeque::new(),
+            next_event_id: 0,
         }
     }
 
-    pub fn set_time(&mut self, dt: &DateTime<Utc>) {
+    /* Time only goes forward, fired events are not undone. Going back is
+     * only possible along with the state of the banks, see `restore`. */
+    pub fn set_time(&mut self, dt: &DateTime<Utc>) -> Result<(), ServerError> {
+        let now = self.get_time();
+        if *dt < now {
+            return Err(ServerError::Conflict(format!(
+                "Time can't go back, it is {} already. Restore a snapshot instead",
+                now.to_rfc3339()
+            )));
+        }
         self.real_time = chrono::Utc::now();
-        self.virtual_time = Some(dt.clone());
+        self.virtual_time = Some(*dt);
+        Ok(())
+    }
+
+    /* Sets the time of a restored snapshot, even an earlier one */
+    pub fn restore(&mut self, dt: &DateTime<Utc>) {
+        self.real_time = chrono::Utc::now();
+        self.virtual_time = Some(*dt);
+    }
+
+    pub fn state(&self) -> TimeState {
+        TimeState {
+            virtual_time: self.virtual_time.map(|_| self.get_time()),
+            paused: self.paused,
+            events: self.events.clone(),
+            next_event_id: self.next_event_id,
+        }
+    }
+
+    pub fn restore_state(&mut self, state: TimeState) {
+        self.real_time = Utc::now();
+        self.virtual_time = state.virtual_time;
+        self.paused = state.paused;
+        self.events = state.events;
+        self.next_event_id = state.next_event_id;
     }
 
     pub fn get_time(&self) -> DateTime<Utc> {
         match self.virtual_time {
-            Some(vt) => vt.clone() + (Utc::now() - self.real_time),
+            Some(vt) if self.paused => vt,
+            Some(vt) => vt + (Utc::now() - self.real_time),
             None => Utc::now(),
         }
     }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) -> Result<(), ServerError> {
+        if self.paused {
+            return Err(ServerError::Conflict("Time is paused already".to_string()));
+        }
+        let now = self.get_time();
+        self.virtual_time = Some(now);
+        self.paused = true;
+        self.record(TimeEventKind::Paused, "Time paused".to_string(), Vec::new());
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<(), ServerError> {
+        if !self.paused {
+            return Err(ServerError::Conflict("Time is not paused".to_string()));
+        }
+        // runs on from where it stood
+        self.real_time = Utc::now();
+        self.paused = false;
+        self.record(TimeEventKind::Resumed, "Time resumed".to_string(), Vec::new());
+        Ok(())
+    }
+
+    pub fn record(&mut self, kind: TimeEventKind, message: String, transactions: Vec<LedgerEntry>) {
+        self.next_event_id += 1;
+        let event = TimeEvent {
+            id: self.next_event_id,
+            time: self.get_time(),
+            kind,
+            message,
+            transactions,
+        };
+        if self.events.len() == MAX_TIME_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /* Events with ids greater than `since`, oldest first */
+    pub fn events(&self, since: u64) -> Vec<TimeEvent> {
+        self.events.iter().filter(|event| event.id > since).cloned().collect()
+    }
 }
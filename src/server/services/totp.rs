@@ -0,0 +1,145 @@
+/*
+ * Time-based one-time passwords (RFC 6238) on top of HMAC-SHA1,
+ * compatible with common authenticator applications.
+ */
+
+use hmac::{Hmac, Mac};
+use rand::prelude::Rng;
+use sha2::Digest;
+
+const SECRET_LEN: usize = 20;
+const TIME_STEP: u64 = 30;
+const DIGITS: u32 = 6;
+// accepted clock drift, in time steps, in each direction
+const DRIFT_STEPS: u64 = 1;
+
+const RECOVERY_CODES_COUNT: usize = 8;
+const RECOVERY_CODE_LEN: usize = 10;
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut rng = rand::rng();
+    (0..SECRET_LEN).map(|_| rng.random::<u8>()).collect()
+}
+
+/* RFC 4648 base32 without padding, as expected in otpauth URIs */
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut result = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        result.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    result
+}
+
+pub fn otpauth_uri(issuer: &str, login: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{login}?secret={}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={TIME_STEP}",
+        base32_encode(secret)
+    )
+}
+
+/* HOTP value (RFC 4226) for the given counter */
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<sha1::Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = ((hash[offset] as u32 & 0x7f) << 24)
+        | ((hash[offset + 1] as u32) << 16)
+        | ((hash[offset + 2] as u32) << 8)
+        | (hash[offset + 3] as u32);
+    binary % 10u32.pow(DIGITS)
+}
+
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("System time before UNIX epoch")
+        .as_secs()
+}
+
+/* Returns the time step the code belongs to. Steps up to `last_step` are refused,
+ * so an accepted code can not be used again. */
+pub fn verify(secret: &[u8], code: &str, last_step: Option<u64>) -> Option<u64> {
+    verify_at(secret, code, last_step, unix_time())
+}
+
+pub fn verify_at(secret: &[u8], code: &str, last_step: Option<u64>, unix_time: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+    let code = code.parse::<u32>().ok()?;
+
+    let counter = unix_time / TIME_STEP;
+    (counter.saturating_sub(DRIFT_STEPS)..=counter + DRIFT_STEPS)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| hotp(secret, *step) == code)
+}
+
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            rand::rng()
+                .sample_iter(&rand::distr::Alphanumeric)
+                .take(RECOVERY_CODE_LEN)
+                .map(char::from)
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> String {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(code.trim().to_lowercase());
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 test secret, the codes are the last 6 digits of its SHA1 vectors
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn accepts_rfc_vectors() {
+        assert_eq!(verify_at(SECRET, "287082", None, 59), Some(1));
+        assert_eq!(verify_at(SECRET, "081804", None, 1111111109), Some(37037036));
+        assert_eq!(verify_at(SECRET, " 050471 ", None, 1111111111), Some(37037037));
+    }
+
+    #[test]
+    fn accepts_one_step_of_drift() {
+        assert_eq!(verify_at(SECRET, "287082", None, 59 + TIME_STEP), Some(1));
+        assert_eq!(verify_at(SECRET, "287082", None, 59 + 2 * TIME_STEP), None);
+    }
+
+    #[test]
+    fn refuses_used_steps() {
+        assert_eq!(verify_at(SECRET, "287082", Some(1), 59), None);
+        assert_eq!(verify_at(SECRET, "287082", Some(2), 59 + TIME_STEP), None);
+        assert_eq!(verify_at(SECRET, "287082", Some(0), 59), Some(1));
+    }
+
+    #[test]
+    fn refuses_malformed_codes() {
+        assert_eq!(verify_at(SECRET, "28708", None, 59), None);
+        assert_eq!(verify_at(SECRET, "2870820", None, 59), None);
+        assert_eq!(verify_at(SECRET, "28708a", None, 59), None);
+        assert_eq!(verify_at(SECRET, "", None, 59), None);
+    }
+}
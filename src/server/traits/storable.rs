@@ -1,7 +1,7 @@
 
 pub trait Storable {
-    fn store(&self, dir : &std::path::Path);
-    fn load(&mut self, dir : &std::path::Path);
+    fn store(&self, dir : &std::path::Path) -> Result<(), String>;
+    fn load(&mut self, dir : &std::path::Path) -> Result<(), String>;
 }
 
 
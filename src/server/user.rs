@@ -1,12 +1,30 @@
 
-use l1::common::user::{Client, UserType, UserData};
+use l1::common::bank::BIK;
+use l1::common::user::{UserType, UserData};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InternalUser{
     pub user_type : UserType,
     pub login : String,
     pub password_hash : String,
-    pub public_user : UserData
+    pub public_user : UserData,
+    pub two_factor : Option<TwoFactor>,
+    // set when the password was reset by bank staff
+    pub password_change_required : bool,
+    // the bank an operator or manager works in, they may not act for other banks
+    pub bank : Option<BIK>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactor {
+    pub secret : Vec<u8>,
+    // false until the user proved the secret was saved by entering a valid code
+    pub enabled : bool,
+    // sha256 hashes of unused recovery codes
+    pub recovery_codes : Vec<String>,
+    // time step of the last accepted code, codes up to it are not accepted again
+    pub last_step : Option<u64>,
 }
 
 